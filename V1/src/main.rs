@@ -7,6 +7,15 @@ use std::fs::File;
 use clap::{Parser};
 use colored::Colorize;
 
+/// Distribution of the random phase delays
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum PhaseshiftDist {
+    /// Every delay within the interval is equally likely
+    Uniform,
+    /// Delays cluster around the middle of the interval, which spans 6 standard deviations
+    Normal,
+}
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 
@@ -17,6 +26,16 @@ struct Arguments {
     #[arg(long, default_value_t = 4)]
     channels : i64,
 
+    /// Number of groups (hands, feet, ...) of channels, each with its own independent channel-pattern.
+    /// The output contains the channels of all groups, one group after the other
+    #[arg(long, default_value_t = 2)]
+    groups : i64,
+
+    /// Write the channels finger by finger instead of group by group: the same finger of every group (hand) is
+    /// adjacent in the frame, [L0,R0,L1,R1,...] instead of [L0..L3,R0..R3]
+    #[arg(long, default_value_t = false)]
+    finger_pairs: bool,
+
     /// Output file sample rate in Hz
     #[arg(long, default_value_t = 44100)]
     samplerate : i64,
@@ -29,6 +48,11 @@ struct Arguments {
     #[arg(long, default_value_t = 100)]
     stimperiod : i64,
 
+    /// Adjust the stimulation frequency to the nearest frequency that completes a whole number of
+    /// sine periods within the stimulation duration
+    #[arg(long, default_value_t = false)]
+    snap_stimfreq: bool,
+
     /// Duration of one cycle (stimulation of all fingers)
     #[arg(long, default_value_t = 666)]
     cycleperiod : i64,
@@ -38,15 +62,58 @@ struct Arguments {
     #[arg(long)]
     phaseshift : Option<i64>,
 
+    /// Smallest random phase delay in ms, so the delayed channels never start (almost) with the undelayed one.
+    /// The delays are drawn from this minimum up to the phase shift interval
+    #[arg(long, default_value_t = 0, requires = "phaseshift")]
+    min_phaseshift: i64,
+
+    /// Distribution of the random phase delays within the interval
+    #[arg(long, value_enum, default_value_t = PhaseshiftDist::Uniform, requires = "phaseshift")]
+    phaseshift_dist: PhaseshiftDist,
+
     /// Select 'Simultaneous Stimulation'-mode (as opposed to default Blocked-mode) with fixed shift intervals (quarter of stim period randomized per channel)
     #[arg(short, long, default_value_t = false)]
     fixedphaseshift: bool,
 
+    /// Synchronize the stimulation onsets of all groups (hands), while each group keeps its own channel-pattern.
+    /// In blocked mode the groups always share the cycle timing. In phaseshift mode all groups get the same set
+    /// of phase delays each cycle, every group assigns them to its channels in its own random order.
+    #[arg(long, default_value_t = false)]
+    sync_hands: bool,
+
+    /// In phaseshift mode, let the groups (hands) take turns within every cycle instead of stimulating at the
+    /// same time: the delays of a group start after the last stimulation of the previous group has ended, so the
+    /// active windows of different groups never overlap. All groups must fit in the cycle
+    #[arg(long, default_value_t = false, conflicts_with = "sync_hands")]
+    alternate_hands: bool,
+
+    /// In phaseshift mode, draw the phase delays once and rotate them over the channels every cycle (the delay of
+    /// channel 0 goes to channel 1, ...) instead of drawing new ones, so over `channels` cycles every channel gets
+    /// every delay exactly once. Repetitions have no effect
+    #[arg(long, default_value_t = false)]
+    phase_shift_per_cycle_rotation: bool,
+
+    /// In blocked mode, start every group this number of cycles further in its channel-pattern than the group
+    /// before it (the right hand 1 further than the left with 1), so the groups don't progress through their
+    /// fingers in step. Must be less than the channels
+    #[arg(long, default_value_t = 0)]
+    hand_offset: i64,
+
+    /// In blocked mode, keep every channel of the channel-pattern active for this number of consecutive cycles
+    /// before the pattern advances, as one continuous tone that ends a stimulation period into its last cycle
+    #[arg(long, default_value_t = 1)]
+    burst_cycles: i64,
 
     /// Number of repetitions before new random channel-pattern is calculated
     #[arg(short, long, default_value_t = 25)]
     repetitions: i64,
 
+    /// Fade the amplitude down by F % towards each change of channel-pattern in blocked mode, and back up afterwards.
+    /// The fade spans one cycle around the change: the second half of the last cycle of the old pattern and the first
+    /// half of the first cycle of the new pattern. With 1 repetition every cycle is faded.
+    #[arg(long)]
+    fade_between_patterns: Option<i64>,
+
     /// Duration (in cycles) of one pauze-cycle
     #[arg(long, default_value_t = 5)]
     pauzecycleperiod : i64,
@@ -55,14 +122,28 @@ struct Arguments {
     #[arg(short, long)]
     pauzes : Vec<i64>,
 
+    /// The only cycles (within the pauze-cycle) with stimulation output produced, all others are pauzed. The inverse
+    /// of --pauzes. You can use this option more than once.
+    #[arg(long)]
+    active_cycles : Vec<i64>,
+
     /// Duration in sec of output
     #[arg(short,long)]
     secondsoutput: i64,
 
+    /// Render only the first seconds of output for a quick check, the filename gets a PREVIEW marker
+    #[arg(long)]
+    preview: Option<i64>,
+
     /// Random seed (default from timer)
     #[arg(long)]
     randomseed: Option<i64>,
 
+    /// Discard the first draws of the random generator after seeding, for the same sequences as tools that warm
+    /// up their generator
+    #[arg(long, default_value_t = 0)]
+    rng_warmup: u64,
+
     /// Disable randomization of channels in blocked mode, and thus plays channels in order 1->2->3->4
     #[arg(long, default_value_t = false)]
     norandom: bool,
@@ -76,12 +157,37 @@ struct Arguments {
 
 impl Arguments {
 
+    /// Stimulation frequencies that end on the end of the stimulation period are a multiple of this (Hz): f * P / 1000
+    /// is a whole number of periods when f is a multiple of 1000 / gcd(1000, P)
+    fn stimfreq_frame(&self) -> i64 {
+        fn gcd(a: i64, b: i64) -> i64 { if b == 0 { a } else { gcd(b, a % b) } }
+
+        if !(1..=1000).contains(&self.stimperiod) {
+            println!("\n{}",
+                format!("ERROR: Stimulation period must be between 1 and 1000ms: {}ms", self.stimperiod).red().bold());
+            panic!("!!!ERROR: Invalid stimulation period");
+        }
+
+        1000 / gcd(1000, self.stimperiod)
+    }
+
+    /// Snap the stimulation frequency so the stimulation sine ends on the end of the stimulation period
+    fn apply_snap_stimfreq(&mut self) {
+        let stimfreq_frame = self.stimfreq_frame();
+        let snapped = ((self.stimfreq as f64 / stimfreq_frame as f64).round() as i64).max(1) * stimfreq_frame;
+
+        if snapped != self.stimfreq {
+            println!("Stimulation frequency snapped from {}Hz to {}Hz", self.stimfreq, snapped);
+            self.stimfreq = snapped;
+        }
+    }
+
     /// Verify the supplied arguments make sense for generating output
     fn verify_argvalues(&self) {
 
         // Do the stimulation frequency en period match, otherwise said, does the stimulation sine
         // end on period end
-        let stimfreq_frame = 1000 / self.stimperiod;
+        let stimfreq_frame = self.stimfreq_frame();
         let smooth_stim_badend = (self.stimfreq % stimfreq_frame) != 0;
 
         if smooth_stim_badend {
@@ -94,6 +200,14 @@ impl Arguments {
                 format!("WARNING: overlapping stimulation periods not supported!").red().bold());
         }
 
+        // A preview only makes sense as an excerpt of the full output
+        if let Some(preview) = self.preview {
+            if preview > self.secondsoutput {
+                println!("\n{}",
+                    format!("WARNING: Preview is longer than the output: {}s", preview).red().bold());
+            }
+        }
+
         // Are the selected pauzes within the pauze period
         for pauze in self.pauzes.iter() {
             if pauze >= &self.pauzecycleperiod {
@@ -103,6 +217,23 @@ impl Arguments {
             }
         }
 
+        // Pauzes and active cycles are each other's inverse, only one of them can be used
+        if !self.pauzes.is_empty() && !self.active_cycles.is_empty() {
+            println!("\n{}",
+                "ERROR: Conflicting command line options, choose either pauzes or active cycles.".red().bold());
+            panic!("!!!ERROR: Conflict in command line");
+        }
+
+        // The delays are drawn from the minimum up to the phase shift
+        if let Some(phaseshift) = self.phaseshift {
+            if self.min_phaseshift < 0 || self.min_phaseshift >= phaseshift {
+                println!("\n{}",
+                    format!("ERROR: Minimum phase shift must be between 0 and the phase shift of {}ms: {}ms",
+                        phaseshift, self.min_phaseshift).red().bold());
+                panic!("!!!ERROR: Invalid minimum phase shift");
+            }
+        }
+
         // Is the phaseshift small enough to allow stim signal to end before the next one starts
         if !self.phaseshift.is_none() {
             if (self.phaseshift.unwrap() + self.stimperiod) * self.channels > self.cycleperiod {
@@ -132,34 +263,153 @@ impl Arguments {
             
         }
 
+        // Rotation only applies to phase delays
+        if self.phase_shift_per_cycle_rotation && self.phaseshift.is_none() && !self.fixedphaseshift {
+            println!("\n{}",
+                "ERROR: Conflicting command line options, phase shift rotation only applicable in phaseshift mode.".red().bold());
+            panic!("!!!ERROR: Conflict in command line");
+        }
+
+        // The offset is a position within the channel-pattern, only used in blocked mode
+        if !(0..self.channels).contains(&self.hand_offset) {
+            println!("\n{}",
+                format!("ERROR: Hand offset must be between 0 and the {} channels: {}", self.channels, self.hand_offset).red().bold());
+            panic!("!!!ERROR: Invalid hand offset");
+        }
+
+        if self.hand_offset != 0 && (self.phaseshift.is_some() || self.fixedphaseshift) {
+            println!("\n{}",
+                "WARNING: Hand offset has no effect in phaseshift mode".red().bold());
+        }
+
+        // Long tones replace the bursts of blocked mode, a pauze must not cut one
+        if self.burst_cycles < 1 {
+            println!("\n{}",
+                format!("ERROR: Burst cycles must be at least 1: {}", self.burst_cycles).red().bold());
+            panic!("!!!ERROR: Invalid burst cycles");
+        }
+
+        if self.burst_cycles > 1 {
+            if self.phaseshift.is_some() || self.fixedphaseshift || self.fade_between_patterns.is_some() {
+                println!("\n{}",
+                    "ERROR: Conflicting command line options, burst cycles only applicable in blocked mode without fade.".red().bold());
+                panic!("!!!ERROR: Conflict in command line");
+            }
+
+            if self.pauze_cuts_tone() {
+                println!("\n{}",
+                    format!("ERROR: Pauzes cut tones of {} cycles, a pauze (of {} cycles) must start and end between tones",
+                        self.burst_cycles, self.channels).red().bold());
+                panic!("!!!ERROR: Pauzes don't fit the burst cycles");
+            }
+        }
+
+        // Taking turns only applies to phase delays, and the turns of all groups must fit in a cycle
+        if self.alternate_hands {
+            if self.phaseshift.is_none() && !self.fixedphaseshift {
+                println!("\n{}",
+                    "ERROR: Conflicting command line options, alternating hands only applicable in phaseshift mode.".red().bold());
+                panic!("!!!ERROR: Conflict in command line");
+            }
+
+            let turn = self.max_phasedelay() + self.stimperiod * self.samplerate / 1_000;
+            let cycle = self.cycleperiod * self.samplerate / 1_000 / self.channels;
+            if self.groups * turn > cycle {
+                println!("\n{}",
+                    format!("ERROR: {} alternating groups need up to {} samples per cycle, the cycle has {}",
+                        self.groups, self.groups * turn, cycle).red().bold());
+                panic!("!!!ERROR: Alternating hands don't fit in the cycle");
+            }
+        }
+
+        // Fading only applies to channel-pattern changes in blocked mode
+        if let Some(fade) = self.fade_between_patterns {
+            if !(0..=100).contains(&fade) {
+                println!("\n{}",
+                    format!("ERROR: Fade between patterns must be a percentage: {}", fade).red().bold());
+                panic!("!!!ERROR: Invalid fade between patterns");
+            }
+
+            if self.phaseshift.is_some() || self.fixedphaseshift {
+                println!("\n{}",
+                    "WARNING: Fade between patterns has no effect in phaseshift mode".red().bold());
+            }
+        }
+
         // The 4 channels are hardcoded in several places, so force them on 4 for now...
         assert_eq!(self.channels,4,"!!!ERROR: Only 4 channels supported for now");
 
+        // FLAC supports up to 8 channels
+        if self.groups < 1 || self.groups * self.channels > 8 {
+            println!("\n{}",
+                format!("ERROR: {} groups of {} channels not supported, FLAC output has 1 up to 8 channels", 
+                    self.groups, self.channels).red().bold());
+            panic!("!!!ERROR: Unsupported number of groups");
+        }
+
     }
 
 
     /// Display overview of configured parameters for this run1
     fn display_config(&self) {
         println!("Generating FLAC output for:");
-        println!("   Channels [L/R]          : {}", self.channels);
+        if self.groups == 2 {
+            println!("   Channels [L/R]          : {}", self.channels);
+        } else {
+            println!("   Channels                : {}", self.channels);
+            println!("   Groups                  : {}", self.groups);
+        }
+        if self.finger_pairs {
+            println!("   Output                  : finger by finger");
+        }
         println!("   Sample Rate             : {}Hz", self.samplerate);
         println!("   Duration                : {}s", self.secondsoutput);
+        if let Some(preview) = self.preview {
+            println!("   Preview                 : {}s", preview);
+        }
         println!("");
         println!("   Stimulation details:");
         println!("     Stimulation Frequency : {}Hz", self.stimfreq);
         println!("     Stimulation Period    : {}ms", self.stimperiod);
         println!("     Cycle Period          : {}ms", self.cycleperiod);
         println!("     Cycle repetitions     : {}", self.repetitions);
+        if let Some(fade) = self.fade_between_patterns {
+            println!("     Pattern fade          : {}%", fade);
+        }
         println!("");
         if !self.phaseshift.is_none() {
             println!("     Phaseshifted, random interval : {}ms", self.phaseshift.unwrap());
+            if self.min_phaseshift > 0 {
+                println!("     Minimum phase shift   : {}ms", self.min_phaseshift);
+            }
+            if self.phaseshift_dist != PhaseshiftDist::Uniform {
+                println!("     Phase shift distribution : {:?}", self.phaseshift_dist);
+            }
         } else if self.fixedphaseshift {
             println!("     Phaseshifted, fixed interval");
         } else {
             println!("     Interleaved");
         }
+        if self.burst_cycles > 1 {
+            println!("     Tones of             : {} cycles", self.burst_cycles);
+        }
+        if self.sync_hands {
+            println!("     Onsets synchronized over groups");
+        }
+        if self.alternate_hands {
+            println!("     Groups alternating within every cycle");
+        }
+        if self.phase_shift_per_cycle_rotation {
+            println!("     Phase delays rotated every cycle");
+        }
+        if self.hand_offset != 0 {
+            println!("     Groups offset by      : {} cycle(s)", self.hand_offset);
+        }
         println!("");
-        if self.pauzes.is_empty() {
+        if !self.active_cycles.is_empty() {
+            println!("   Pauze cycle period      : {}", self.pauzecycleperiod);
+            println!("   Active on cycles        : {:?}", self.active_cycles);
+        } else if self.pauzes.is_empty() {
             println!("   Without pauzes");
         } else {
             println!("   Pauze cycle period      : {}", self.pauzecycleperiod);
@@ -171,9 +421,57 @@ impl Arguments {
         } else {
             println!("   Random seed             : {}", self.randomseed.unwrap());
         }
+        if self.rng_warmup > 0 {
+            println!("   Random warm-up          : {} draws", self.rng_warmup);
+        }
   
     }
 
+    /// Whether a pauze starts or ends within a tone of --burst-cycles. Pauzes are whole cycle periods of `channels`
+    /// cycles, so tones must fit within one (burst cycles divide the channels) or span whole cycle periods with
+    /// the same pauze state
+    fn pauze_cuts_tone(&self) -> bool {
+        if (self.pauzes.is_empty() && self.active_cycles.is_empty()) || self.channels % self.burst_cycles == 0 {
+            return false;
+        }
+        if self.burst_cycles % self.channels != 0 {
+            return true;
+        }
+
+        let periods = self.burst_cycles / self.channels;
+        let pauzed = |paucycle: i64| if self.active_cycles.is_empty() {
+            self.pauzes.contains(&paucycle)
+        } else {
+            !self.active_cycles.contains(&paucycle)
+        };
+
+        // the tones start at every multiple of periods, the pauze-cycle period repeats the pattern
+        (0..self.pauzecycleperiod * periods).step_by(periods as usize)
+            .any(|start| (start..start + periods).any(|period| pauzed(period % self.pauzecycleperiod) != pauzed(start % self.pauzecycleperiod)))
+    }
+
+    /// Largest phase delay in samples a channel can get
+    fn max_phasedelay(&self) -> i64 {
+        match self.phaseshift {
+            Some(phaseshift) => (phaseshift - 1) * self.samplerate / 1_000,
+            None => 3 * 1_000 / self.stimfreq / 4 * self.samplerate / 1_000,
+        }
+    }
+
+    /// Index in the output frame of channel of hand (group)
+    fn output_index(&self, hand: i64, channel: i64) -> usize {
+        if self.finger_pairs {
+            (channel * self.groups + hand) as usize
+        } else {
+            (channel + hand * self.channels) as usize
+        }
+    }
+
+    /// Duration in sec to render, the preview length if requested
+    fn render_seconds(&self) -> i64 {
+        self.preview.unwrap_or(self.secondsoutput)
+    }
+
     /// Set filename with all parameters included
     fn construct_fname(&self) -> String {
         let mut result: String = "output/Sine-".to_owned();
@@ -181,17 +479,48 @@ impl Arguments {
         if !self.phaseshift.is_none() {
             result.push_str(&self.phaseshift.unwrap().to_string());
             result.push_str("PhaseShifted--");
+            if self.min_phaseshift > 0 {
+                result.push_str(&self.min_phaseshift.to_string());
+                result.push_str("MIN-");
+            }
+            if self.phaseshift_dist == PhaseshiftDist::Normal {
+                result.push_str("NORMAL-");
+            }
         } else if self.fixedphaseshift {
             result.push_str("FixPhaseShifted--"); 
         } else {
             result.push_str("Interleaved--");
         }
 
+        if self.burst_cycles > 1 {
+            result.push_str(&self.burst_cycles.to_string());
+            result.push_str("LONG--");
+        }
+
+        if self.sync_hands {
+            result.push_str("SYNC--");
+        }
+        if self.alternate_hands {
+            result.push_str("ALT--");
+        }
+        if self.phase_shift_per_cycle_rotation {
+            result.push_str("ROT--");
+        }
+        if self.hand_offset != 0 {
+            result.push_str(&self.hand_offset.to_string());
+            result.push_str("HOFF--");
+        }
+
         result.push_str(&self.stimfreq.to_string());    result.push_str("SFREQ-");
         result.push_str(&self.stimperiod.to_string());  result.push_str("SPER-");
         result.push_str(&self.cycleperiod.to_string()); result.push_str("CPER-");
         result.push_str(&self.repetitions.to_string()); result.push_str("R--");
 
+        if let Some(fade) = self.fade_between_patterns {
+            result.push_str(&fade.to_string());
+            result.push_str("FADE--");
+        }
+
         if !self.pauzes.is_empty() {
             let mut first : bool = true;
 
@@ -209,15 +538,42 @@ impl Arguments {
             result.push_str("--");
         }
 
+        if !self.active_cycles.is_empty() {
+            let cycles : Vec<String> = self.active_cycles.iter().map(|cycle| cycle.to_string()).collect();
+            result.push_str(&cycles.join("_"));
+            result.push('A');
+            result.push_str(&self.pauzecycleperiod.to_string());
+            result.push_str("--");
+        }
+
         if !self.randomseed.is_none() {
             result.push_str(&self.randomseed.unwrap().to_string());
             result.push_str("RSEED--");
         }
 
-        result.push_str(&self.channels.to_string());      result.push_str("LR-");
+        if self.rng_warmup > 0 {
+            result.push_str(&self.rng_warmup.to_string());
+            result.push_str("WARM--");
+        }
+
+        if self.finger_pairs {
+            result.push_str("FPAIRS-");
+        }
+        if self.groups == 2 {
+            result.push_str(&self.channels.to_string());  result.push_str("LR-");
+        } else {
+            result.push_str(&self.channels.to_string());  result.push('x');
+            result.push_str(&self.groups.to_string());    result.push_str("G-");
+        }
         result.push_str(&self.samplerate.to_string());    result.push_str("Hz-");
         result.push_str(&self.secondsoutput.to_string()); result.push_str("s");
 
+        if let Some(preview) = self.preview {
+            result.push_str("-PREVIEW");
+            result.push_str(&preview.to_string());
+            result.push('s');
+        }
+
         result.push_str(".flac");
 
         result
@@ -235,8 +591,10 @@ struct SeqGen {
     sample : i64,
     cycle: i64,
     cyclestart: i64,
+    tonestart: i64,
     repcycle: i64,
-    channelorder : [ AtomSeq; 2],
+    patterns: i64,
+    channelorder : Vec<AtomSeq>,
 }
 
 impl SeqGen {
@@ -248,12 +606,15 @@ impl SeqGen {
         if !args.randomseed.is_none() {
             new_rng = ChaCha8Rng::seed_from_u64(args.randomseed.unwrap() as u64);
         } 
+        for _ in 0..args.rng_warmup {
+            new_rng.next_u64();
+        }
 
 
         // TODO: this restricts channels to 4 (2)
-        let seq = [ [0; 4], [0; 4] ];
+        let seq = vec![ [0; 4]; args.groups as usize ];
         
-        SeqGen { rng: new_rng, sample : 0, cycle: 0, cyclestart: 0, repcycle: 1, channelorder : seq }
+        SeqGen { rng: new_rng, sample : 0, cycle: 0, cyclestart: 0, tonestart: 0, repcycle: 1, patterns: 0, channelorder : seq }
     }
 
     /// Init SegGen1 state from supplied arguments
@@ -265,10 +626,14 @@ impl SeqGen {
         }
     }
 
-    /// Generates new random pattern for each hand (for interleaved mode - not phaseshifted)
+    /// Generates new random pattern for each group (for interleaved mode - not phaseshifted)
     fn gen_channelorder(&mut self, args: &Arguments) {
-        for h in 0..2 {
+        for h in 0..args.groups as usize {
             let mut nums : AtomSeq = [0; 4];
+
+            // positions in the pattern of the first and last cycle, shifted by the hand offset
+            let first = (h as i64 * args.hand_offset % args.channels) as usize;
+            let last = ((args.channels - 1 + h as i64 * args.hand_offset) % args.channels) as usize;
             
             loop {
                 let mut counter = 0;
@@ -281,7 +646,7 @@ impl SeqGen {
                 
 
                     // this protects us from triggering the same finger twice in sequence
-                    if nums[0] != *self.channelorder[h].last().unwrap() {
+                    if nums[first] != self.channelorder[h][last] {
                         break;
                     }
                 }
@@ -290,8 +655,10 @@ impl SeqGen {
             self.channelorder[h] = nums;
         }
 
+        self.patterns += 1;
+
         if args.verbosity > 1 {
-            println!(" * New Channel Order: {:?}-{:?}", self.channelorder[0], self.channelorder[1]);
+            println!(" * New Channel Order: {}", self.format_channelorder());
         }
 
     }
@@ -299,27 +666,91 @@ impl SeqGen {
 
     /// Generate new randomized phase delay for each channel (when phaseshift - not interleaved mode)
     fn gen_phasedelay(&mut self, args: &Arguments) {
-        for h in 0..2 {
-            let mut nums : AtomSeq = [0; 4];
-
-            // we don't touch the first element, it will be the zero-delay one ico randomized delays
-            for i in 1..4 {
-                if args.fixedphaseshift {
-                    nums[i] = i as i64 * 1_000 /  args.stimfreq / 4 * args.samplerate / 1_000;
-                } else {
-                    nums[i] = self.rng.gen_range(0..args.phaseshift.unwrap()) * args.samplerate / 1_000;
-                }
+        let mut shared : Option<AtomSeq> = None;
+        let mut turn_start = 0;
+
+        for h in 0..args.groups as usize {
+            let mut nums : AtomSeq = match shared {
+                // synchronized groups reuse the delays of the first group, in their own order
+                Some(delays) => delays,
+                None => self.gen_delays(args),
+            };
+
+            if args.sync_hands {
+                shared = Some(nums);
             }
 
             nums.shuffle(&mut self.rng);
 
+            // alternating groups start once the previous group is done
+            if args.alternate_hands {
+                nums = nums.map(|delay| delay + turn_start);
+                turn_start = nums.iter().max().unwrap() + args.stimperiod * args.samplerate / 1_000;
+            }
+
             self.channelorder[h] = nums;
         }
 
         if args.verbosity > 1 {
-            println!(" * New Phase Shift: {:?}-{:?}", self.channelorder[0], self.channelorder[1]);
+            println!(" * New Phase Shift: {}", self.format_channelorder());
+        }
+
+    }
+
+    /// Rotate the phase delays of every group one channel up (when phaseshift with rotation)
+    fn rotate_phasedelay(&mut self, args: &Arguments) {
+        for nums in self.channelorder.iter_mut() {
+            nums.rotate_right(1);
+        }
+
+        if args.verbosity > 1 {
+            println!(" * Rotated Phase Shift: {}", self.format_channelorder());
+        }
+    }
+
+    /// Random phase delays for the channels of one group
+    fn gen_delays(&mut self, args: &Arguments) -> AtomSeq {
+        let mut nums : AtomSeq = [0; 4];
+
+        // we don't touch the first element, it will be the zero-delay one ico randomized delays
+        for i in 1..4 {
+            if args.fixedphaseshift {
+                nums[i] = i as i64 * 1_000 /  args.stimfreq / 4 * args.samplerate / 1_000;
+            } else {
+                nums[i] = self.gen_phaseshift(args) * args.samplerate / 1_000;
+            }
+        }
+
+        nums
+    }
+
+    /// Random phase delay in ms, from the minimum phase shift up to the phase shift
+    fn gen_phaseshift(&mut self, args: &Arguments) -> i64 {
+        let (min, max) = (args.min_phaseshift, args.phaseshift.unwrap());
+
+        match args.phaseshift_dist {
+            PhaseshiftDist::Uniform => self.rng.gen_range(min..max),
+            PhaseshiftDist::Normal => {
+                // Box-Muller, draws outside the interval are drawn again
+                let mean = (min + max) as f64 / 2.0;
+                let sd = (max - min) as f64 / 6.0;
+                loop {
+                    let u1 : f64 = 1.0 - self.rng.gen::<f64>();
+                    let u2 : f64 = self.rng.gen();
+                    let z = (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos();
+                    let delay = (mean + z * sd).floor() as i64;
+                    if (min..max).contains(&delay) {
+                        return delay;
+                    }
+                }
+            }
         }
+    }
 
+    /// Channel pattern or phase delays of all groups, for display
+    fn format_channelorder(&self) -> String {
+        let groups : Vec<String> = self.channelorder.iter().map(|seq| format!("{:?}", seq)).collect();
+        groups.join("-")
     }
 
     // Set internal counter to next sample. Renew internal structures where necessary
@@ -328,31 +759,28 @@ impl SeqGen {
 
         if self.curr_cycle(args) < self.cycle  {
             // we went back to cycle 0:
-            //  - generate new random pattern for both hands (unless phaseshift)
+            //  - generate new random pattern for all groups (unless phaseshift)
 
-            if args.phaseshift.is_none() && !args.fixedphaseshift {
-                if self.repcycle < args.repetitions {
-                    self.repcycle += 1;
-                } else {
-                    self.repcycle = 1;
-                    self.gen_channelorder(&args);
-                }
-            }  
+            if args.phaseshift.is_none() && !args.fixedphaseshift && self.next_repetition(args) {
+                self.gen_channelorder(&args);
+            }
         }
 
         if self.curr_cycle(args) != self.cycle {
             // cycle changed:
             //  - set cyclestart
-            //  - generate delay per channel (for phaseshift)
+            //  - generate delay per channel (for phaseshift), or rotate them
             self.cyclestart = self.sample;
 
-            if !args.phaseshift.is_none() || args.fixedphaseshift {
-                if self.repcycle < args.repetitions {
-                    self.repcycle += 1;
-                } else {
-                    self.repcycle = 1;
-                    self.gen_phasedelay(&args);
-                }
+            // a tone of --burst-cycles starts with the first of its cycles
+            if self.curr_cycle(args) % args.burst_cycles == 0 {
+                self.tonestart = self.sample;
+            }
+
+            if args.phase_shift_per_cycle_rotation {
+                self.rotate_phasedelay(args);
+            } else if (!args.phaseshift.is_none() || args.fixedphaseshift) && self.next_repetition(args) {
+                self.gen_phasedelay(&args);
             }
 
             if args.verbosity > 2 {
@@ -363,27 +791,86 @@ impl SeqGen {
         self.cycle = self.curr_cycle(args);
     }
 
-    /// Returns the current cycle (in range 0..args.channels)
+    /// Count the next repetition of the current pattern, returns whether a new pattern is due.
+    /// The first pattern is generated by `init` as repetition 1, so every pattern (the first one included)
+    /// lasts exactly `repetitions` blocks of `channels` cycles (times the burst cycles) in blocked mode, as it
+    /// repeats once per pass over the channel-pattern, or `repetitions` cycles in phaseshift mode
+    fn next_repetition(&mut self, args: &Arguments) -> bool {
+        if self.repcycle < args.repetitions {
+            self.repcycle += 1;
+            false
+        } else {
+            self.repcycle = 1;
+            true
+        }
+    }
+
+    /// Returns the current cycle (in range 0..args.channels, times the burst cycles)
     fn curr_cycle(&mut self, args: &Arguments) -> i64{
-        ( self.sample * 1_000 * args.channels / args.samplerate / args.cycleperiod ) % args.channels
+        ( self.sample * 1_000 * args.channels / args.samplerate / args.cycleperiod ) % (args.channels * args.burst_cycles)
     }
 
     /// Returns whether channel is in pauze
     fn in_pauze(&self, args: &Arguments) -> bool {
         let curr_paucycle = ( self.sample * 1_000 / args.samplerate / args.cycleperiod ) % args.pauzecycleperiod;
 
-        args.pauzes.contains(&curr_paucycle)
+        if args.active_cycles.is_empty() {
+            args.pauzes.contains(&curr_paucycle)
+        } else {
+            !args.active_cycles.contains(&curr_paucycle)
+        }
+    }
+
+    /// Returns the output frame of the current sample, a sample of every channel of every group in output order
+    fn frame(&mut self, args: &Arguments) -> Vec<i32> {
+        let mut frame = vec![0; (args.groups * 4) as usize];
+
+        if !self.in_pauze(args) {
+            for hand in 0..args.groups {  
+                for channel in 0..4 {    
+                    let sample = self.sample(args, hand as usize, channel);
+                    let amplitude = i16::MAX as f64;
+                        
+                    frame[args.output_index(hand, channel)] = (sample*amplitude) as i32;
+                }
+            }
+        }
+
+        frame
     }
 
     // Returns value of current sample for hand/channel combination
     fn sample(&mut self, args: &Arguments, hand: usize, channel: i64) -> f64 {
         if args.phaseshift.is_none() && !args.fixedphaseshift {
-            self.sample_blocked(args, hand, channel)
+            self.sample_blocked(args, hand, channel) * self.pattern_fade(args)
         } else {
             self.sample_phaseshifted(args, hand, channel)
         }
     }
 
+    /// Amplitude gain for fading between channel-patterns (blocked mode), 1.0 outside of the fade
+    fn pattern_fade(&self, args: &Arguments) -> f64 {
+        let depth = match args.fade_between_patterns {
+            Some(fade) => fade as f64 / 100.0,
+            None => return 1.0,
+        };
+
+        // position within the current cycle, in range [0, 1[
+        let pos = (self.sample * 1_000) as f64 / args.samplerate as f64 % args.cycleperiod as f64 / args.cycleperiod as f64;
+
+        if self.repcycle == args.repetitions && pos >= 0.5 {
+            // fade out towards the next pattern
+            let t = (pos - 0.5) * 2.0;
+            1.0 - depth * (1.0 - (PI * t).cos()) / 2.0
+        } else if self.repcycle == 1 && pos < 0.5 && self.patterns > 1 {
+            // fade in from the previous pattern
+            let t = pos * 2.0;
+            1.0 - depth * (1.0 + (PI * t).cos()) / 2.0
+        } else {
+            1.0
+        }
+    }
+
     /// Value of sample in phaseshifted mode
     fn sample_phaseshifted(&mut self, args: &Arguments, hand: usize, channel: i64) -> f64 {
         let cycle_active_from = self.cyclestart + self.channelorder[hand][channel as usize];
@@ -399,9 +886,15 @@ impl SeqGen {
         }
     }
 
+    /// Position in the channel-pattern of hand in the current cycle, every group is hand_offset further than the
+    /// previous one
+    fn pattern_position(&self, args: &Arguments, hand: usize) -> usize {
+        ((self.cycle / args.burst_cycles + hand as i64 * args.hand_offset) % args.channels) as usize
+    }
+
     /// Value of sample in blocked mode
     fn sample_blocked(&mut self, args: &Arguments, hand: usize, channel: i64) -> f64 {
-        let active_channel = self.channelorder[hand][self.cycle as usize];
+        let active_channel = self.channelorder[hand][self.pattern_position(args, hand)];
 
         if channel != active_channel {
             return 0.0;
@@ -409,12 +902,13 @@ impl SeqGen {
 
         let cycle_active_time = args.stimperiod * args.samplerate / 1000;
 
-        let rel_sample = self.sample - self.cyclestart; 
-
-        if rel_sample > cycle_active_time {
+        // a tone of --burst-cycles keeps sounding until the stimulation period of its last cycle
+        let last_cycle = self.cycle % args.burst_cycles == args.burst_cycles - 1;
+        if last_cycle && self.sample - self.cyclestart > cycle_active_time {
             return 0.0;
         }
 
+        let rel_sample = self.sample - self.tonestart;
         let arg = rel_sample * args.stimfreq * 2;
         (arg as f64 * PI / args.samplerate as f64).sin()
     } 
@@ -424,7 +918,11 @@ impl SeqGen {
 
 fn main() {
 
-    let args = Arguments::parse();
+    let mut args = Arguments::parse();
+
+    if args.snap_stimfreq {
+        args.apply_snap_stimfreq();
+    }
  
     if args.verbosity > 0 {
         args.display_config();
@@ -435,12 +933,12 @@ fn main() {
 
     println!("Writing output to: {}", fname);
 
-    let samples_to_go = args.secondsoutput * args.samplerate;
+    let samples_to_go = args.render_seconds() * args.samplerate;
   
     let mut flac_outfile = File::create(fname).unwrap();
     let mut flac_outwrap = flac_bound::WriteWrapper(&mut flac_outfile);
     let mut flac_encoder = flac_bound::FlacEncoder::new().unwrap()
-        .channels((2*args.channels).try_into().unwrap())
+        .channels((args.groups*args.channels).try_into().unwrap())
         .bits_per_sample(16)
         .sample_rate(args.samplerate as u32)
         .total_samples_estimate(samples_to_go as u64)
@@ -452,18 +950,7 @@ fn main() {
     seq1.init(&args);
 
     for _ in 0..samples_to_go {
-        let mut next_sample : [i32; 2*4 as usize] = [0; 2*4 as usize];
-
-        if !seq1.in_pauze(&args) {
-            for hand in 0..2 {  
-                for channel in 0..4 {    
-                    let sample = seq1.sample(&args, hand as usize, channel);
-                    let amplitude = i16::MAX as f64;
-                        
-                    next_sample[(channel + hand * 4) as usize] = (sample*amplitude) as i32;
-                }
-            }
-        }
+        let next_sample = seq1.frame(&args);
 
         flac_encoder.process_interleaved(&next_sample,1).unwrap();
         
@@ -472,3 +959,350 @@ fn main() {
 
     
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Arguments of a short, seeded render at a low rate, with options
+    fn test_args(options: &[&str]) -> Arguments {
+        let base = ["f2heal-v1", "-s", "1", "--samplerate", "8000", "--randomseed", "1"];
+        Arguments::parse_from(base.iter().chain(options))
+    }
+
+    /// Generator of args, initialized as for a render
+    fn generator(args: &Arguments) -> SeqGen {
+        let mut seq = SeqGen::new(args);
+        seq.init(args);
+        seq
+    }
+
+    /// Frames of the render of args, as written to the output
+    fn render(args: &Arguments) -> Vec<Vec<i32>> {
+        let mut seq = generator(args);
+        let mut frames = Vec::new();
+
+        for _ in 0..args.render_seconds() * args.samplerate {
+            frames.push(seq.frame(args));
+            seq.next_sample(args);
+        }
+
+        frames
+    }
+
+    /// Channel sounding in every frame of the channels of group, None when all are silent. Panics when more than
+    /// one channel of the group sounds
+    fn sounding(args: &Arguments, frames: &[Vec<i32>], group: i64) -> Vec<Option<i64>> {
+        frames.iter().enumerate().map(|(i, frame)| {
+            let channels : Vec<i64> = (0..args.channels).filter(|channel| frame[args.output_index(group, *channel)] != 0).collect();
+            assert!(channels.len() <= 1, "channels {:?} of group {} sound together at sample {}", channels, group, i);
+            channels.first().copied()
+        }).collect()
+    }
+
+    /// Channels of group in the order they are stimulated, a stimulation counted once
+    fn stimulated(args: &Arguments, frames: &[Vec<i32>], group: i64) -> Vec<i64> {
+        let mut order : Vec<i64> = sounding(args, frames, group).into_iter().flatten().collect();
+        order.dedup();
+        order
+    }
+
+    /// First and last sounding sample of every stimulation of channel of group, a stimulation ends at a run of
+    /// silent samples (a sine crosses 0 on single samples)
+    fn channel_windows(args: &Arguments, frames: &[Vec<i32>], group: i64, channel: i64) -> Vec<(usize, usize)> {
+        const SILENCE: usize = 8;
+
+        let mut windows : Vec<(usize, usize)> = Vec::new();
+        for (i, frame) in frames.iter().enumerate() {
+            if frame[args.output_index(group, channel)] == 0 {
+                continue;
+            }
+
+            match windows.last_mut() {
+                Some((_, last)) if i - *last < SILENCE => *last = i,
+                _ => windows.push((i, i)),
+            }
+        }
+
+        windows
+    }
+
+    /// Stimulation windows of all channels of group, in order
+    fn group_windows(args: &Arguments, frames: &[Vec<i32>], group: i64) -> Vec<(usize, usize)> {
+        let mut windows : Vec<(usize, usize)> = (0..args.channels)
+            .flat_map(|channel| channel_windows(args, frames, group, channel))
+            .collect();
+        windows.sort_unstable();
+        windows
+    }
+
+    /// Step the generator of args over cycles and return the pattern (count and channel orders or phase delays of
+    /// all groups) in every cycle
+    fn cycle_patterns(args: &Arguments, cycles: usize) -> Vec<(i64, Vec<AtomSeq>)> {
+        let mut seq = generator(args);
+        let mut patterns = vec![(seq.patterns, seq.channelorder.clone())];
+
+        while patterns.len() < cycles {
+            let cycle = seq.cycle;
+            seq.next_sample(args);
+            if seq.cycle != cycle {
+                patterns.push((seq.patterns, seq.channelorder.clone()));
+            }
+        }
+
+        patterns
+    }
+
+    /// Count the cycles of every pattern in turn over slots cycles, the last pattern left out as it may be cut off
+    fn pattern_lengths(args: &Arguments, slots: usize) -> Vec<usize> {
+        let patterns = cycle_patterns(args, slots);
+        let mut lengths = vec![1];
+
+        for pair in patterns.windows(2) {
+            if pair[0] != pair[1] {
+                lengths.push(0);
+            }
+            *lengths.last_mut().unwrap() += 1;
+        }
+
+        lengths.pop();
+        lengths
+    }
+
+    /// Stimulation period (ms) of the snap test, which doesn't divide a second, the frequency asked and the snapped one
+    const SNAP_STIMPERIOD: i64 = 30;
+    const SNAP_STIMFREQ: i64 = 250;
+    const SNAPPED_STIMFREQ: i64 = 300;
+
+    /// --snap-stimfreq snaps to the nearest frequency with a whole number of periods in the stimulation period, also
+    /// for a period that doesn't divide a second
+    #[test]
+    fn snap_stimfreq_gives_whole_periods() {
+        let mut args = test_args(&["--stimperiod", &SNAP_STIMPERIOD.to_string(), "--stimfreq", &SNAP_STIMFREQ.to_string(), "--snap-stimfreq"]);
+
+        args.apply_snap_stimfreq();
+        assert_eq!(args.stimfreq, SNAPPED_STIMFREQ);
+        assert_eq!(args.stimfreq * SNAP_STIMPERIOD % 1000, 0, "{}Hz is no whole number of periods in {}ms", args.stimfreq, SNAP_STIMPERIOD);
+    }
+
+    /// Repetitions of the pattern tests, and the patterns checked
+    const TEST_REPETITIONS: usize = 3;
+    const TEST_PATTERNS: usize = 4;
+
+    /// In blocked mode every channel order, the first one included, lasts `repetitions` blocks of `channels` cycles
+    #[test]
+    fn blocked_pattern_lasts_repetitions_blocks() {
+        let args = test_args(&["--repetitions", &TEST_REPETITIONS.to_string()]);
+        let block = args.channels as usize;
+        let lengths = pattern_lengths(&args, (TEST_PATTERNS + 1) * TEST_REPETITIONS * block);
+
+        assert!(lengths.len() >= TEST_PATTERNS && lengths.iter().all(|length| *length == TEST_REPETITIONS * block),
+            "channel orders last {:?} cycles, expected {} blocks of {} cycles", lengths, TEST_REPETITIONS, block);
+    }
+
+    /// In phaseshift mode every set of phase delays, the first one included, lasts `repetitions` cycles
+    #[test]
+    fn phase_delays_last_repetitions_cycles() {
+        let args = test_args(&["--phaseshift", "50", "--repetitions", &TEST_REPETITIONS.to_string()]);
+        let lengths = pattern_lengths(&args, (TEST_PATTERNS + 1) * TEST_REPETITIONS);
+
+        assert!(lengths.len() >= TEST_PATTERNS && lengths.iter().all(|length| *length == TEST_REPETITIONS),
+            "phase delays last {:?} cycles, expected {}", lengths, TEST_REPETITIONS);
+    }
+
+    /// Repetitions and cycle period (ms, 4000 samples) of the pattern fade test
+    const FADE_REPETITIONS: usize = 2;
+    const FADE_CYCLEPERIOD: i64 = 500;
+
+    /// With --fade-between-patterns 100 the gain is 1.0 except in the last half cycle of a pattern and the first half
+    /// cycle of the next one, and reaches 0 at the change of pattern. The first pattern doesn't fade in
+    #[test]
+    fn pattern_fade_spans_change() {
+        let args = test_args(&["--fade-between-patterns", "100", "--repetitions", &FADE_REPETITIONS.to_string(),
+            "--cycleperiod", &FADE_CYCLEPERIOD.to_string()]);
+        let block = (FADE_CYCLEPERIOD * args.samplerate / 1_000) as usize;
+        let half = block / 2;
+
+        let mut seq = generator(&args);
+        let mut steps : Vec<(i64, f64)> = Vec::new();
+        for _ in 0..(TEST_PATTERNS + 1) * FADE_REPETITIONS * block + half {
+            steps.push((seq.patterns, seq.pattern_fade(&args)));
+            seq.next_sample(&args);
+        }
+
+        let changes : Vec<usize> = (1..steps.len()).filter(|i| steps[*i].0 != steps[i - 1].0).collect();
+        assert!(changes.len() >= TEST_PATTERNS && changes.iter().all(|change| change % (FADE_REPETITIONS * block) == 0),
+            "patterns change at samples {:?}, expected every {} samples", changes, FADE_REPETITIONS * block);
+
+        let first = steps[..half].iter().position(|(_, gain)| *gain != 1.0);
+        assert!(first.is_none(), "the first pattern fades in at sample {:?}", first);
+
+        for (i, (_, gain)) in steps.iter().enumerate() {
+            let fading = changes.iter().any(|change| i + half >= *change && i < change + half);
+            assert!(fading || *gain == 1.0, "gain {} at sample {}, more than half a cycle from a change at {:?}", gain, i, changes);
+        }
+
+        for change in changes.iter() {
+            let (before, at) = (steps[change - 1].1, steps[*change].1);
+            assert!(before < 1e-3 && at < 1e-9, "gain {} and {} around the change at sample {}", before, at, change);
+        }
+    }
+
+    /// With rotation the phase delays drawn for the first cycle rotate over the channels, so over `channels` cycles
+    /// every channel of every group gets every delay exactly once
+    #[test]
+    fn rotation_covers_every_assignment() {
+        let args = test_args(&["--phaseshift", "50", "--phase-shift-per-cycle-rotation"]);
+        let patterns = cycle_patterns(&args, args.channels as usize);
+
+        for group in 0..args.groups as usize {
+            let mut delays = patterns[0].1[group];
+            delays.sort_unstable();
+
+            for channel in 0..args.channels as usize {
+                let mut received : Vec<i64> = patterns.iter().map(|(_, orders)| orders[group][channel]).collect();
+                received.sort_unstable();
+                assert_eq!(received, delays, "delays of channel {} of group {} over {} cycles", channel, group, args.channels);
+            }
+        }
+    }
+
+    /// Groups of the group test, more than the 2 of the FLAC output of 4 channels
+    const TEST_GROUPS: i64 = 3;
+
+    /// The generator of 3 groups gives a frame of 12 channels in which every group stimulates its channels one at a
+    /// time, all of them, in its own random channel order
+    #[test]
+    fn three_groups_have_own_channel_orders() {
+        let args = test_args(&["--groups", &TEST_GROUPS.to_string(), "--cycleperiod", "200"]);
+        let frames = render(&args);
+        assert!(frames.iter().all(|frame| frame.len() == (TEST_GROUPS * args.channels) as usize));
+
+        let orders : Vec<Vec<i64>> = (0..TEST_GROUPS).map(|group| stimulated(&args, &frames, group)).collect();
+        for (group, order) in orders.iter().enumerate() {
+            assert!((0..args.channels).all(|channel| order.contains(&channel)), "group {} stimulates {:?}", group, order);
+        }
+        assert!(orders.iter().any(|order| *order != orders[0]), "the groups share their channel order: {:?}", orders[0]);
+    }
+
+    /// With --sync-hands the stimulations of both hands start and end together, in blocked mode as well as in
+    /// phaseshift mode, where both hands get the same delays every cycle (each in its own channel order)
+    #[test]
+    fn sync_hands_aligns_windows() {
+        for mode in [&[][..], &["--phaseshift", "50"]] {
+            let args = test_args(&[&["--sync-hands"], mode].concat());
+            let frames = render(&args);
+            let (left, right) = (group_windows(&args, &frames, 0), group_windows(&args, &frames, 1));
+
+            assert!(!left.is_empty() && left == right, "windows of the hands differ with --sync-hands {}: {:?} and {:?}",
+                mode.join(" "), left, right);
+        }
+    }
+
+    /// With --hand-offset N and the channels in order (--norandom) the right hand stimulates the finger N further
+    /// than the left hand, at every sample of every cycle
+    #[test]
+    fn hand_offset_shifts_right_hand() {
+        for offset in 1..4 {
+            let args = test_args(&["--norandom", "--hand-offset", &offset.to_string()]);
+            let frames = render(&args);
+            let pairs : Vec<(i64, i64)> = sounding(&args, &frames, 0).into_iter().zip(sounding(&args, &frames, 1))
+                .filter_map(|pair| match pair {
+                    (Some(left), Some(right)) => Some((left, right)),
+                    _ => None,
+                })
+                .collect();
+
+            let wrong = pairs.iter().find(|(left, right)| *right != (left + offset) % args.channels);
+            assert!(!pairs.is_empty() && wrong.is_none(), "the right hand isn't {} fingers further than the left: {:?}", offset, wrong);
+        }
+    }
+
+    /// Phaseshift mode with turns of both hands fitting in a cycle, for --alternate-hands
+    const ALTERNATE_ARGS: [&str; 5] = ["--phaseshift", "20", "--stimperiod", "40", "--alternate-hands"];
+
+    /// With --alternate-hands no stimulation of the left hand overlaps one of the right hand, over the whole render
+    #[test]
+    fn alternate_hands_never_overlap() {
+        let args = test_args(&ALTERNATE_ARGS);
+        let frames = render(&args);
+        let (left, right) = (group_windows(&args, &frames, 0), group_windows(&args, &frames, 1));
+        assert!(!left.is_empty() && !right.is_empty());
+
+        for window in left.iter() {
+            let overlap = right.iter().find(|other| other.0 <= window.1 && window.0 <= other.1);
+            assert!(overlap.is_none(), "left window {:?} overlaps right window {:?}", window, overlap);
+        }
+    }
+
+    /// With --finger-pairs and 4 fingers per hand, channel 2 * i of the frame is finger i of the left hand and
+    /// channel 2 * i + 1 finger i of the right hand, of the same render group by group ([L0..L3,R0..R3])
+    #[test]
+    fn finger_pairs_put_hands_side_by_side() {
+        let by_group = render(&test_args(&[]));
+        let args = test_args(&["--finger-pairs"]);
+        let by_finger = render(&args);
+        assert_eq!(by_group.len(), by_finger.len());
+
+        for (i, (grouped, paired)) in by_group.iter().zip(&by_finger).enumerate() {
+            for finger in 0..args.channels as usize {
+                assert!(paired[2 * finger] == grouped[finger] && paired[2 * finger + 1] == grouped[args.channels as usize + finger],
+                    "finger {} at sample {} isn't paired: {:?} from {:?}", finger, i, paired, grouped);
+            }
+        }
+        assert!(by_finger.iter().flatten().any(|sample| *sample != 0), "output is silent");
+    }
+
+    /// Phase shift interval (ms) of the minimum delay test, and the cycles drawn
+    const MIN_PHASESHIFT: i64 = 10;
+    const MAX_PHASESHIFT: i64 = 30;
+    const DELAY_TEST_CYCLES: usize = 200;
+
+    /// Every cycle one channel of every hand is undelayed, the delays of the others are at least --min-phaseshift
+    /// and below the phase shift, with either distribution
+    #[test]
+    fn delays_respect_min_phaseshift() {
+        for dist in ["uniform", "normal"] {
+            let args = test_args(&["--phaseshift", &MAX_PHASESHIFT.to_string(), "--min-phaseshift", &MIN_PHASESHIFT.to_string(),
+                "--phaseshift-dist", dist, "--repetitions", "1"]);
+            let (min, max) = (MIN_PHASESHIFT * args.samplerate / 1_000, MAX_PHASESHIFT * args.samplerate / 1_000);
+
+            for (cycle, (_, delays)) in cycle_patterns(&args, DELAY_TEST_CYCLES).iter().enumerate() {
+                for hand in delays.iter() {
+                    let delayed : Vec<i64> = hand.iter().copied().filter(|delay| *delay != 0).collect();
+                    assert!(delayed.len() == hand.len() - 1 && delayed.iter().all(|delay| (min..max).contains(delay)),
+                        "delays {:?} of cycle {} ({}) not from {} up to {} samples", hand, cycle, dist, min, max);
+                }
+            }
+        }
+    }
+
+    /// Cycles of a tone of the burst cycles test
+    const TEST_BURST_CYCLES: i64 = 2;
+
+    /// With --burst-cycles N (channels in order) a channel sounds as one tone from the start of its first cycle
+    /// until a stimulation period into its N-th cycle, then the next channel starts on the next cycle
+    #[test]
+    fn burst_cycles_hold_channel() {
+        let args = test_args(&["--norandom", "--burst-cycles", &TEST_BURST_CYCLES.to_string()]);
+        let frames = render(&args);
+        let slot = (args.cycleperiod * args.samplerate / 1_000 / args.channels) as usize;
+        let stimulation = (args.stimperiod * args.samplerate / 1_000) as usize;
+        let tone = (TEST_BURST_CYCLES as usize - 1) * slot + stimulation;
+
+        let mut tones : Vec<(usize, usize, i64)> = (0..args.channels)
+            .flat_map(|channel| channel_windows(&args, &frames, 0, channel).into_iter().map(move |(first, last)| (first, last, channel)))
+            .collect();
+        tones.sort_unstable();
+        tones.retain(|(first, _, _)| first + tone < frames.len());
+        assert!(tones.len() >= 2, "only {} complete tones", tones.len());
+
+        for (i, (first, last, channel)) in tones.iter().enumerate() {
+            let start = i * TEST_BURST_CYCLES as usize * slot;
+            assert!(*channel == i as i64 % args.channels && first.abs_diff(start) <= 1 && last.abs_diff(start + tone) <= 1,
+                "tone {} of channel {} sounds from {} to {}, expected channel {} from {} to {}",
+                i, channel, first, last, i as i64 % args.channels, start, start + tone);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! Throughput of the FLAC encoding per sample (--buffer-frames 1) against buffered (the default 4096 frames), for a
+//! render long enough that the calls to the encoder count. Run with `cargo bench --bench buffer_frames`.
+
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// Render of the benchmark: a minute of 8 channels at 44100Hz
+const BENCH_OPTIONS: [&str; 8] = ["-s", "60", "--samplerate", "44100", "--channels", "8", "--randomseed", "1"];
+const BENCH_FRAMES: u64 = 60 * 44100;
+
+/// Buffer sizes compared, and the runs of each of which the fastest counts
+const BUFFER_FRAMES: [u32; 2] = [1, 4096];
+const RUNS: usize = 3;
+
+/// Time of the fastest of the runs of the binary rendering with buffer frames to fname
+fn fastest_render(buffer_frames: u32, fname: &PathBuf) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            let output = Command::new(env!("CARGO_BIN_EXE_f2heal-v2"))
+                .args(BENCH_OPTIONS)
+                .args(["--buffer-frames", &buffer_frames.to_string()])
+                .arg("--name-template").arg(fname)
+                .output()
+                .unwrap_or_else(|e| panic!("cannot run f2heal-v2: {}", e));
+            assert!(output.status.success(), "f2heal-v2 failed ({}): {}", output.status, String::from_utf8_lossy(&output.stdout));
+
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let dir = std::env::temp_dir().join(format!("f2heal-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap_or_else(|e| panic!("cannot create {}: {}", dir.display(), e));
+
+    println!("Rendering {}, fastest of {} runs", BENCH_OPTIONS.join(" "), RUNS);
+    let mut per_sample = None;
+    for buffer_frames in BUFFER_FRAMES {
+        let elapsed = fastest_render(buffer_frames, &dir.join(format!("bench-{}.flac", buffer_frames)));
+        let speedup = per_sample.map_or(String::new(), |per_sample: Duration| format!(", {:.2}x", per_sample.as_secs_f64() / elapsed.as_secs_f64()));
+        per_sample.get_or_insert(elapsed);
+
+        println!("   --buffer-frames {:<5} : {:>8.3}s, {:>6.2} Mframes/s{}",
+            buffer_frames, elapsed.as_secs_f64(), BENCH_FRAMES as f64 / elapsed.as_secs_f64() / 1e6, speedup);
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,49 @@
+//! Build information for `--version-full`: the git commit and the versions of the random crates, passed to
+//! the program as environment variables at compile time. Outside a git checkout (e.g. a source archive)
+//! they are `unknown`.
+
+use std::process::Command;
+
+/// Short hash of the checked out commit, with a `-dirty` suffix when tracked files are modified
+fn git_commit() -> Option<String> {
+    let output = Command::new("git").args(["rev-parse", "--short", "HEAD"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let mut commit = String::from_utf8(output.stdout).ok()?.trim().to_owned();
+
+    let status = Command::new("git").args(["status", "--porcelain", "--untracked-files=no"]).output().ok()?;
+    if !status.stdout.is_empty() {
+        commit.push_str("-dirty");
+    }
+
+    Some(commit)
+}
+
+/// Version of the package name in the workspace lock file
+fn locked_version(lock: &str, name: &str) -> Option<String> {
+    let mut lines = lock.lines();
+
+    while let Some(line) = lines.next() {
+        if line == format!("name = \"{}\"", name) {
+            let version = lines.next()?.strip_prefix("version = \"")?.strip_suffix('"')?;
+            return Some(version.to_owned());
+        }
+    }
+
+    None
+}
+
+fn main() {
+    let lock = std::fs::read_to_string("../Cargo.lock").unwrap_or_default();
+
+    println!("cargo:rustc-env=F2HEAL_GIT_COMMIT={}", git_commit().unwrap_or("unknown".to_owned()));
+    for (name, var) in [("rand", "F2HEAL_RAND_VERSION"), ("rand_chacha", "F2HEAL_RAND_CHACHA_VERSION")] {
+        println!("cargo:rustc-env={}={}", var, locked_version(&lock, name).unwrap_or("unknown".to_owned()));
+    }
+
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/refs/heads");
+    println!("cargo:rerun-if-changed=../.git/index");
+    println!("cargo:rerun-if-changed=../Cargo.lock");
+}
@@ -0,0 +1,156 @@
+//! Statistical audit of the channel order randomization (`--rng-audit PATTERNS`).
+//!
+//! The scheduler's own `gen_channelorder` draws the given number of channel orders from the seeded generator,
+//! with the random options of the command line (`--rng`, `--deterministic-shuffle`, `--channel-seeding`, ...),
+//! and two chi-square tests check them:
+//!
+//! 1. Position: every channel appears at every position of the order equally often. With every order a
+//!    permutation the table of counts has (channels - 1)^2 degrees of freedom.
+//! 2. Repeat: a new order never starts with the last channel of the previous one. Drawing again until it
+//!    doesn't leaves the rest of the order uniform, so that channel is equally likely at every later
+//!    position, with channels - 2 degrees of freedom. A test that rejects means the constraint biases the
+//!    orders beyond that.
+//!
+//! A test rejects at a p-value below `ALPHA`. The generation itself is not changed.
+
+use crate::{Arguments, Render};
+
+/// Significance level of the tests
+const ALPHA: f64 = 0.001;
+
+/// Natural logarithm of the gamma function (Lanczos approximation, g = 7)
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [0.999_999_999_999_809_9, 676.520_368_121_885_1, -1_259.139_216_722_402_8,
+        771.323_428_777_653_1, -176.615_029_162_140_6, 12.507_343_278_686_905, -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6, 1.505_632_735_149_311_6e-7];
+
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let sum = COEFFICIENTS[1..].iter().enumerate().fold(COEFFICIENTS[0], |sum, (i, c)| sum + c / (x + i as f64 + 1.0));
+
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+/// Regularized upper incomplete gamma function Q(a, x), by its series below a + 1 and its continued fraction
+/// above
+fn gamma_q(a: f64, x: f64) -> f64 {
+    const EPSILON: f64 = 1e-14;
+    const ITERATIONS: usize = 1000;
+
+    if x <= 0.0 {
+        return 1.0;
+    }
+
+    let prefix = (a * x.ln() - x - ln_gamma(a)).exp();
+
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        for n in 1..ITERATIONS {
+            term *= x / (a + n as f64);
+            sum += term;
+            if term < sum * EPSILON {
+                break;
+            }
+        }
+
+        (1.0 - sum * prefix).max(0.0)
+    } else {
+        // modified Lentz
+        let tiny = f64::MIN_POSITIVE / EPSILON;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut fraction = d;
+        for n in 1..ITERATIONS {
+            let an = -(n as f64) * (n as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            d = if d.abs() < tiny { 1.0 / tiny } else { 1.0 / d };
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            let delta = d * c;
+            fraction *= delta;
+            if (delta - 1.0).abs() < EPSILON {
+                break;
+            }
+        }
+
+        fraction * prefix
+    }
+}
+
+/// Chi-square statistic of the observed counts against the expected ones, cells expected empty left out
+fn chi_square(observed: &[u64], expected: &[f64]) -> f64 {
+    observed.iter().zip(expected)
+        .filter(|(_, expected)| **expected > 0.0)
+        .map(|(observed, expected)| (*observed as f64 - expected).powi(2) / expected)
+        .sum()
+}
+
+/// Print the result of a test, returns whether it passes
+fn report(name: &str, chi2: f64, df: u32) -> bool {
+    let p = gamma_q(df as f64 / 2.0, chi2 / 2.0);
+    let passed = p >= ALPHA;
+
+    println!("   {:<23} : chi2 {:.2}, df {}, p {:.4} ({})", name, chi2, df, p,
+        if passed { "uniform" } else { "BIASED" });
+
+    passed
+}
+
+/// Draw patterns channel orders with the scheduler and test them, returns whether no test rejects
+pub fn rng_audit(args: &Arguments, patterns: u64) -> bool {
+    let args = Arguments { verbosity: 0, ..args.clone() };
+    let channels = args.channels as usize;
+    let mut sg = Render::verified(&args).sg;
+
+    // counts[position * channels + channel], and the position of the previous order's last channel
+    let mut counts = vec![0; channels * channels];
+    let mut repeat_positions = vec![0; channels];
+
+    for _ in 0..patterns {
+        let last = *sg.channelorder.last().unwrap() as usize;
+        sg.gen_channelorder(&args);
+
+        for (position, channel) in sg.channelorder.iter().enumerate() {
+            counts[position * channels + *channel as usize] += 1;
+            if *channel as usize == last {
+                repeat_positions[position] += 1;
+            }
+        }
+    }
+
+    println!("RNG audit: {} channel orders of {} channels, seed {}, {} shuffle", patterns, channels, args.seed(),
+        args.shuffle_algorithm());
+    println!("   Counts per position     : (rows positions, columns channels)");
+    for position in counts.chunks(channels) {
+        let row : Vec<String> = position.iter().map(|count| format!("{:>8}", count)).collect();
+        println!("     {}", row.join(""));
+    }
+    let repeats : Vec<String> = repeat_positions.iter().map(|count| format!("{:>8}", count)).collect();
+    println!("   Previous last channel   : (per position)");
+    println!("     {}", repeats.join(""));
+
+    let mut passed = true;
+
+    if channels > 1 {
+        let expected = vec![patterns as f64 / channels as f64; channels * channels];
+        passed &= report("Position", chi_square(&counts, &expected), ((channels - 1) * (channels - 1)) as u32);
+    }
+
+    if repeat_positions[0] > 0 && channels > 1 {
+        println!("   {:<23} : {} orders start with the previous last channel (BIASED)", "Immediate repeats", repeat_positions[0]);
+        passed = false;
+    }
+
+    if channels > 2 {
+        let mut expected = vec![patterns as f64 / (channels - 1) as f64; channels];
+        expected[0] = 0.0;
+        passed &= report("Repeat", chi_square(&repeat_positions, &expected), (channels - 2) as u32);
+    }
+
+    passed
+}
@@ -0,0 +1,95 @@
+//! Decoding of FLAC files, to check generated output.
+//!
+//! flac-bound only wraps the encoder, so the decoder of libFLAC is used directly through
+//! flac-sys. The complete file is decoded in memory as interleaved samples.
+
+use std::ffi::CString;
+use std::io;
+use std::os::raw::c_void;
+
+use flac_sys::*;
+
+/// Decoded FLAC file
+pub struct Decoded {
+    pub channels: u32,
+    pub samplerate: u32,
+    /// Interleaved samples of all channels
+    pub samples: Vec<i32>,
+}
+
+impl Decoded {
+    /// Number of frames (samples per channel)
+    pub fn frames(&self) -> usize {
+        if self.channels == 0 { 0 } else { self.samples.len() / self.channels as usize }
+    }
+}
+
+unsafe extern "C" fn on_write(
+    _decoder: *const FLAC__StreamDecoder,
+    frame: *const FLAC__Frame,
+    buffer: *const *const FLAC__int32,
+    client_data: *mut c_void,
+) -> FLAC__StreamDecoderWriteStatus {
+    let decoded = &mut *(client_data as *mut Decoded);
+    let header = &(*frame).header;
+
+    decoded.channels = header.channels;
+    decoded.samplerate = header.sample_rate;
+
+    let channels : Vec<&[i32]> = (0..header.channels as usize)
+        .map(|channel| std::slice::from_raw_parts(*buffer.add(channel), header.blocksize as usize))
+        .collect();
+
+    for i in 0..header.blocksize as usize {
+        for channel in channels.iter() {
+            decoded.samples.push(channel[i]);
+        }
+    }
+
+    FLAC__StreamDecoderWriteStatus_FLAC__STREAM_DECODER_WRITE_STATUS_CONTINUE
+}
+
+unsafe extern "C" fn on_error(
+    _decoder: *const FLAC__StreamDecoder,
+    _status: FLAC__StreamDecoderErrorStatus,
+    _client_data: *mut c_void,
+) {
+    // a corrupt frame ends up as missing samples, which shows in the comparison
+}
+
+/// Decode the FLAC file fname
+pub fn decode(fname: &str) -> io::Result<Decoded> {
+    let error = |msg: &str| io::Error::other(format!("{}: {}", fname, msg));
+
+    let path = CString::new(fname).map_err(|_| error("invalid filename"))?;
+    let mut decoded = Decoded { channels: 0, samplerate: 0, samples: Vec::new() };
+
+    // SAFETY: the decoder is only used within this function, and decoded outlives it
+    unsafe {
+        let decoder = FLAC__stream_decoder_new();
+        if decoder.is_null() {
+            return Err(error("cannot create FLAC decoder"));
+        }
+
+        let status = FLAC__stream_decoder_init_file(
+            decoder,
+            path.as_ptr(),
+            Some(on_write),
+            None,
+            Some(on_error),
+            &mut decoded as *mut Decoded as *mut c_void,
+        );
+
+        let ok = status == FLAC__StreamDecoderInitStatus_FLAC__STREAM_DECODER_INIT_STATUS_OK
+            && FLAC__stream_decoder_process_until_end_of_stream(decoder) != 0;
+
+        FLAC__stream_decoder_finish(decoder);
+        FLAC__stream_decoder_delete(decoder);
+
+        if !ok {
+            return Err(error("cannot decode FLAC file"));
+        }
+    }
+
+    Ok(decoded)
+}
@@ -0,0 +1,328 @@
+//! Problems detected in the supplied arguments.
+
+use std::fmt;
+
+use crate::manifest;
+
+/// Problem in the supplied arguments, either a warning (output can be generated, but
+/// probably not as intended) or an error (no output can be generated)
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+    /// The stimulation sine does not end on the end of the stimulation period
+    StimFreqMismatch,
+    /// The stimulation (ms) is longer than the slot (ms) of a channel in the cycle
+    OverlappingStimulation { stimulation: i64, slot: i64 },
+    /// A pauze outside of the pauze-cycle period
+    IneffectivePauze(i64),
+    /// Both pauzes and active cycles given
+    PauzesAndActiveCycles,
+    /// More random pauzes than cycles in the pauze-cycle
+    RandomPauzeCount { count: i64, pauzecycleperiod: i64 },
+    /// A session fade (in seconds) where fade in and fade out overlap
+    SessionFadeTooLong(i64),
+    /// An intercycle gap (max, in ms) that is not small compared to the cycle period
+    IntercycleGapTooLarge { max: i64, cycleperiod: i64 },
+    /// A stimulation frequency (Hz) above the passband (Hz) of the resampler
+    ResamplePassband { freq: i64, passband: i64 },
+    /// A preview longer than the output
+    PreviewTooLong(i64),
+    /// Attack, decay and release (total in ms) longer than the stimulation
+    EnvelopeTooLong(i64),
+    /// Jitter large enough to cut stimulations short
+    JitterTooLarge { jitter: i64, max: i64 },
+    /// A beat frequency that is not slow compared to the carrier
+    BeatTooFast(i64),
+    /// A percentage outside of 0..=100
+    InvalidPercentage(&'static str, i64),
+    /// The number of finger labels does not match the channels
+    FingerLabelCount { labels: usize, channels: u32 },
+    /// The number of channel delays does not match the channels
+    ChannelDelayCount { delays: usize, channels: u32 },
+    /// The number of channel offsets does not match the channels
+    ChannelOffsetCount { offsets: usize, channels: u32 },
+    /// A channel offset before the start of the slot
+    NegativeChannelOffset(u32),
+    /// A channel offset that moves the stimulation past the end of the slot
+    ChannelOffsetOutsideSlot(u32),
+    /// The name template uses an unknown placeholder
+    UnknownPlaceholder(String),
+    /// The FSK data file contains no symbols
+    EmptyFskData,
+    /// The FSK data contains a symbol without a frequency
+    FskSymbol { symbol: usize, position: usize, symbols: usize },
+    /// The emphasis curve contains no points
+    EmptyEmphasisCurve,
+    /// A stimulation frequency outside of the emphasis curve
+    EmphasisRange { freq: i64, min: f64, max: f64 },
+    /// An input file of the option that can't be read, or has an invalid line (from 1, None for the file)
+    InvalidFile { parameter: &'static str, file: String, line: Option<usize>, reason: String },
+    /// The sequence file contains no cycles
+    EmptySequence,
+    /// The sequence file refers to a channel that does not exist
+    SequenceChannel { channel: u32, line: usize, channels: u32 },
+    /// More output channels (including the trigger) than the output format supports
+    TooManyChannels { channels: u32, max: u32 },
+    /// The estimated session energy (full scale seconds) exceeds the budget
+    EnergyBudget { energy: f64, budget: f64 },
+    /// Channels not stimulated in the first cycles of the schedule
+    UnscheduledChannels { missing: Vec<u32>, cycles: i64 },
+    /// The chord size is not in 1..=channels
+    ChordSize { size: u32, channels: u32 },
+    /// The grid anchor (ms) is negative
+    NegativeGridAnchor(f64),
+    /// The downmix map doesn't have an entry for every output channel
+    DownmixMapCount { entries: usize, channels: u32 },
+    /// The downmix map sends a channel to a device channel that doesn't exist
+    DownmixMapChannel { channel: u32, downmix: u32 },
+    /// Neither one stimulation duration nor one per channel
+    StimdurationCount { durations: usize, channels: u32 },
+    /// A stimulation duration (ms) that isn't between 1ms and a second
+    StimdurationRange(i64),
+    /// A channel rest (slots) longer than every cycle allows (max slots)
+    ChannelRestRelaxed { rest: u32, max: u32 },
+    /// The baseline level of the continuous mode isn't between 0 and 1
+    BaselineLevel(f64),
+    /// The float WAV output (bytes) doesn't fit the 4 GB of a WAV file
+    WavTooLarge(u64),
+    /// The marker level of the dual carrier isn't between 0 and 1
+    MarkerLevel(f64),
+    /// The marker frequency of the dual carrier (Hz) isn't above 0 and below the Nyquist frequency
+    MarkerFreq { freq: i64, nyquist: i64 },
+}
+
+impl ConfigError {
+
+    /// Returns whether no output can be generated, as opposed to a warning
+    pub fn is_error(&self) -> bool {
+        matches!(self,
+            ConfigError::PauzesAndActiveCycles |
+            ConfigError::RandomPauzeCount { .. } |
+            ConfigError::InvalidPercentage(..) |
+            ConfigError::FingerLabelCount { .. } |
+            ConfigError::ChannelDelayCount { .. } |
+            ConfigError::ChannelOffsetCount { .. } |
+            ConfigError::NegativeChannelOffset(_) |
+            ConfigError::UnknownPlaceholder(_) |
+            ConfigError::EmptyFskData |
+            ConfigError::FskSymbol { .. } |
+            ConfigError::EmptyEmphasisCurve |
+            ConfigError::EmphasisRange { .. } |
+            ConfigError::InvalidFile { .. } |
+            ConfigError::EmptySequence |
+            ConfigError::SequenceChannel { .. } |
+            ConfigError::TooManyChannels { .. } |
+            ConfigError::EnergyBudget { .. } |
+            ConfigError::UnscheduledChannels { .. } |
+            ConfigError::ChordSize { .. } |
+            ConfigError::NegativeGridAnchor(_) |
+            ConfigError::DownmixMapCount { .. } |
+            ConfigError::DownmixMapChannel { .. } |
+            ConfigError::StimdurationCount { .. } |
+            ConfigError::StimdurationRange(_) |
+            ConfigError::BaselineLevel(_) |
+            ConfigError::WavTooLarge(_) |
+            ConfigError::MarkerLevel(_) |
+            ConfigError::MarkerFreq { .. })
+    }
+
+    /// Stable identifier of the problem, for machine-readable diagnostics
+    pub fn code(&self) -> &'static str {
+        match self {
+            ConfigError::StimFreqMismatch => "stim_freq_mismatch",
+            ConfigError::OverlappingStimulation { .. } => "overlapping_stimulation",
+            ConfigError::IneffectivePauze(_) => "ineffective_pauze",
+            ConfigError::PauzesAndActiveCycles => "pauzes_and_active_cycles",
+            ConfigError::RandomPauzeCount { .. } => "random_pauze_count",
+            ConfigError::SessionFadeTooLong(_) => "session_fade_too_long",
+            ConfigError::IntercycleGapTooLarge { .. } => "intercycle_gap_too_large",
+            ConfigError::ResamplePassband { .. } => "resample_passband",
+            ConfigError::PreviewTooLong(_) => "preview_too_long",
+            ConfigError::EnvelopeTooLong(_) => "envelope_too_long",
+            ConfigError::JitterTooLarge { .. } => "jitter_too_large",
+            ConfigError::BeatTooFast(_) => "beat_too_fast",
+            ConfigError::InvalidPercentage(..) => "invalid_percentage",
+            ConfigError::FingerLabelCount { .. } => "finger_label_count",
+            ConfigError::ChannelDelayCount { .. } => "channel_delay_count",
+            ConfigError::ChannelOffsetCount { .. } => "channel_offset_count",
+            ConfigError::NegativeChannelOffset(_) => "negative_channel_offset",
+            ConfigError::ChannelOffsetOutsideSlot(_) => "channel_offset_outside_slot",
+            ConfigError::UnknownPlaceholder(_) => "unknown_placeholder",
+            ConfigError::EmptyFskData => "empty_fsk_data",
+            ConfigError::FskSymbol { .. } => "fsk_symbol",
+            ConfigError::EmptyEmphasisCurve => "empty_emphasis_curve",
+            ConfigError::EmphasisRange { .. } => "emphasis_range",
+            ConfigError::InvalidFile { .. } => "invalid_file",
+            ConfigError::EmptySequence => "empty_sequence",
+            ConfigError::SequenceChannel { .. } => "sequence_channel",
+            ConfigError::TooManyChannels { .. } => "too_many_channels",
+            ConfigError::EnergyBudget { .. } => "energy_budget",
+            ConfigError::UnscheduledChannels { .. } => "unscheduled_channels",
+            ConfigError::ChordSize { .. } => "chord_size",
+            ConfigError::NegativeGridAnchor(_) => "negative_grid_anchor",
+            ConfigError::DownmixMapCount { .. } => "downmix_map_count",
+            ConfigError::DownmixMapChannel { .. } => "downmix_map_channel",
+            ConfigError::StimdurationCount { .. } => "stimduration_count",
+            ConfigError::StimdurationRange(_) => "stimduration_range",
+            ConfigError::ChannelRestRelaxed { .. } => "channel_rest_relaxed",
+            ConfigError::BaselineLevel(_) => "baseline_level",
+            ConfigError::WavTooLarge(_) => "wav_too_large",
+            ConfigError::MarkerLevel(_) => "marker_level",
+            ConfigError::MarkerFreq { .. } => "marker_freq",
+        }
+    }
+
+    /// Command line option (long name) most related to the problem
+    pub fn parameter(&self) -> &'static str {
+        match self {
+            ConfigError::StimFreqMismatch => "stimfreq",
+            ConfigError::OverlappingStimulation { .. } => "stimduration",
+            ConfigError::IneffectivePauze(_) => "pauzes",
+            ConfigError::PauzesAndActiveCycles => "active-cycles",
+            ConfigError::RandomPauzeCount { .. } => "random-pauzes",
+            ConfigError::SessionFadeTooLong(_) => "session-fade-sec",
+            ConfigError::IntercycleGapTooLarge { .. } => "intercycle-gap",
+            ConfigError::ResamplePassband { .. } => "synth-rate",
+            ConfigError::PreviewTooLong(_) => "preview",
+            ConfigError::EnvelopeTooLong(_) => "attack-ms",
+            ConfigError::JitterTooLarge { .. } => "jitter",
+            ConfigError::BeatTooFast(_) => "beat",
+            ConfigError::InvalidPercentage(parameter, _) => parameter,
+            ConfigError::FingerLabelCount { .. } => "finger-labels",
+            ConfigError::ChannelDelayCount { .. } => "channel-delay",
+            ConfigError::ChannelOffsetCount { .. } |
+            ConfigError::NegativeChannelOffset(_) |
+            ConfigError::ChannelOffsetOutsideSlot(_) => "channel-offset",
+            ConfigError::UnknownPlaceholder(_) => "name-template",
+            ConfigError::EmptyFskData |
+            ConfigError::FskSymbol { .. } => "fsk-data",
+            ConfigError::EmptyEmphasisCurve |
+            ConfigError::EmphasisRange { .. } => "emphasis-curve",
+            ConfigError::InvalidFile { parameter, .. } => parameter,
+            ConfigError::EmptySequence |
+            ConfigError::SequenceChannel { .. } => "sequence-file",
+            ConfigError::TooManyChannels { .. } => "channels",
+            ConfigError::EnergyBudget { .. } => "max-energy",
+            ConfigError::UnscheduledChannels { .. } => "require-all-channels",
+            ConfigError::ChordSize { .. } => "chord-size",
+            ConfigError::NegativeGridAnchor(_) => "grid-anchor",
+            ConfigError::DownmixMapCount { .. } |
+            ConfigError::DownmixMapChannel { .. } => "downmix-map",
+            ConfigError::StimdurationCount { .. } |
+            ConfigError::StimdurationRange(_) => "stimduration",
+            ConfigError::ChannelRestRelaxed { .. } => "min-channel-rest",
+            ConfigError::BaselineLevel(_) => "baseline-level",
+            ConfigError::WavTooLarge(_) => "format",
+            ConfigError::MarkerLevel(_) => "marker-level",
+            ConfigError::MarkerFreq { .. } => "marker-freq",
+        }
+    }
+
+    /// Description of the problem, without its severity
+    pub fn message(&self) -> String {
+        match self {
+            ConfigError::StimFreqMismatch =>
+                "Stimulation period and frequency do not match!".to_owned(),
+            ConfigError::OverlappingStimulation { stimulation, slot } =>
+                format!("Stimulation of {}ms is longer than the slot of {}ms, it is cut off when the next stimulation starts (use --overlap to let it continue)", stimulation, slot),
+            ConfigError::IneffectivePauze(pauze) =>
+                format!("This pauze will have no effect: {}", pauze),
+            ConfigError::PauzesAndActiveCycles =>
+                "Conflicting command line options, choose either pauzes or active cycles".to_owned(),
+            ConfigError::RandomPauzeCount { count, pauzecycleperiod } =>
+                format!("Cannot pauze {} random cycles in a pauze-cycle of {} cycles", count, pauzecycleperiod),
+            ConfigError::SessionFadeTooLong(fade) =>
+                format!("Session fade in and fade out overlap: {}s", fade),
+            ConfigError::IntercycleGapTooLarge { max, cycleperiod } =>
+                format!("Intercycle gap of up to {}ms is more than 10% of the cycle period of {}ms, it changes the tempo rather than breaking up its regularity", max, cycleperiod),
+            ConfigError::ResamplePassband { freq, passband } =>
+                format!("Stimulation frequency {}Hz is above the {}Hz passband of the resampler and is attenuated", freq, passband),
+            ConfigError::PreviewTooLong(preview) =>
+                format!("Preview is longer than the output: {}s", preview),
+            ConfigError::EnvelopeTooLong(duration) =>
+                format!("Envelope is longer than the stimulation and is scaled down: {}ms", duration),
+            ConfigError::JitterTooLarge { jitter, max } =>
+                format!("Jitter of {}% can cut stimulations short, at most {}% keeps them complete (see --clamp-jitter-to-slot)", jitter, max),
+            ConfigError::BeatTooFast(beat) =>
+                format!("Beat frequency should be positive and below 10% of the stimulation frequency: {}Hz", beat),
+            ConfigError::InvalidPercentage(parameter, value) =>
+                format!("{} must be a percentage (0-100): {}", parameter, value),
+            ConfigError::FingerLabelCount { labels, channels } =>
+                format!("{} finger labels given for {} channels", labels, channels),
+            ConfigError::ChannelDelayCount { delays, channels } =>
+                format!("{} channel delays given for {} channels", delays, channels),
+            ConfigError::ChannelOffsetCount { offsets, channels } =>
+                format!("{} channel offsets given for {} channels", offsets, channels),
+            ConfigError::NegativeChannelOffset(channel) =>
+                format!("Offset of channel {} is negative, stimulations can only start later in their slot", channel),
+            ConfigError::ChannelOffsetOutsideSlot(channel) =>
+                format!("Offset of channel {} moves its stimulation past the end of the slot, it gets cut off", channel),
+            ConfigError::UnknownPlaceholder(placeholder) =>
+                format!("Unknown placeholder in name template: {{{}}}", placeholder),
+            ConfigError::EmptyFskData =>
+                "FSK data file contains no symbols".to_owned(),
+            ConfigError::FskSymbol { symbol, position, symbols } =>
+                format!("FSK data has symbol {} at position {}, only {} FSK frequencies given", symbol, position, symbols),
+            ConfigError::EmptyEmphasisCurve =>
+                "Emphasis curve contains no points".to_owned(),
+            ConfigError::EmphasisRange { freq, min, max } =>
+                format!("Stimulation frequency {}Hz is outside of the emphasis curve ({}Hz - {}Hz)", freq, min, max),
+            ConfigError::InvalidFile { file, line: Some(line), reason, .. } =>
+                format!("{} line {}: {}", file, line, reason),
+            ConfigError::InvalidFile { file, line: None, reason, .. } =>
+                format!("{}: {}", file, reason),
+            ConfigError::EmptySequence =>
+                "Sequence file contains no cycles".to_owned(),
+            ConfigError::SequenceChannel { channel, line, channels } =>
+                format!("Sequence refers to channel {} on line {}, only {} channels available", channel, line, channels),
+            ConfigError::TooManyChannels { channels, max } =>
+                format!("{} output channels requested, FLAC supports at most {}", channels, max),
+            ConfigError::EnergyBudget { energy, budget } =>
+                format!("Estimated session energy of {:.1} full scale seconds exceeds the budget of {}", energy, budget),
+            ConfigError::UnscheduledChannels { missing, cycles } =>
+                format!("Channel(s) {:?} not stimulated in the first {} cycles", missing, cycles),
+            ConfigError::ChordSize { size, channels } =>
+                format!("Chord size must be between 1 and the {} channels: {}", channels, size),
+            ConfigError::NegativeGridAnchor(anchor) =>
+                format!("Grid anchor is negative: {}ms, the first cycle can't start before the file", anchor),
+            ConfigError::DownmixMapCount { entries, channels } =>
+                format!("{} downmix map entries given for {} output channels", entries, channels),
+            ConfigError::DownmixMapChannel { channel, downmix } =>
+                format!("Downmix map sends a channel to device channel {}, the preview has {} channels", channel, downmix),
+            ConfigError::StimdurationCount { durations, channels } =>
+                format!("{} stimulation durations given for {} channels, give one or one per channel", durations, channels),
+            ConfigError::StimdurationRange(duration) =>
+                format!("Stimulation duration must be between 1 and 1000ms: {}ms", duration),
+            ConfigError::ChannelRestRelaxed { rest, max } =>
+                format!("A channel rest of {} slots can't be met with every channel in every cycle, relaxed to {}", rest, max),
+            ConfigError::BaselineLevel(level) =>
+                format!("Baseline level must be above 0 and below 1: {}", level),
+            ConfigError::WavTooLarge(bytes) =>
+                format!("The float WAV output of {} MB doesn't fit the 4 GB of a WAV file, render shorter parts", bytes / 1024 / 1024),
+            ConfigError::MarkerLevel(level) =>
+                format!("Marker level must be above 0 and below 1: {}", level),
+            ConfigError::MarkerFreq { freq, nyquist } =>
+                format!("Marker frequency must be above 0 and below the Nyquist frequency of {}Hz: {}Hz", nyquist, freq),
+        }
+    }
+
+    /// Problem as JSON object with its severity, code, parameter and message
+    pub fn to_json(&self) -> String {
+        format!("{{ \"severity\": {}, \"code\": {}, \"parameter\": {}, \"message\": {} }}",
+            manifest::string(if self.is_error() { "error" } else { "warning" }),
+            manifest::string(self.code()),
+            manifest::string(self.parameter()),
+            manifest::string(&self.message()))
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_error() {
+            write!(f, "ERROR: ")?;
+        } else {
+            write!(f, "WARNING: ")?;
+        }
+
+        write!(f, "{}", self.message())
+    }
+}
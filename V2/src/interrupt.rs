@@ -0,0 +1,33 @@
+//! Ctrl-C handling, so an interrupted render still ends in a valid FLAC file.
+//!
+//! The handler only raises a flag, the render loop polls it and stops early. The encoder is
+//! then finished as usual, leaving a playable file with everything rendered so far.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn on_sigint(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Install the Ctrl-C (SIGINT) handler
+#[cfg(unix)]
+pub fn install() {
+    let handler = on_sigint as extern "C" fn(libc::c_int);
+
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+    unsafe {
+        libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+    }
+}
+
+/// Without SIGINT Ctrl-C stops the render as before, leaving the file incomplete
+#[cfg(not(unix))]
+pub fn install() {}
+
+/// Returns whether Ctrl-C was pressed since the handler was installed
+pub fn requested() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,105 @@
+//! Inter-stimulation intervals per channel (`--render-stats-per-channel`).
+//!
+//! The render records the sample every stimulation of a channel starts at (after gaps and channel offsets),
+//! and the time between successive stimulations of a channel is counted in a histogram. The bins are slots
+//! (the cycle period over the channels): an interval falls in the bin of the nearest whole number of slots, so
+//! bin k holds the intervals from k - 1/2 to k + 1/2 slots. Random channel orders give intervals from 2 slots
+//! (a channel never repeats immediately) to twice the channels minus 1, jitter spreads them, pauzes add long
+//! ones. Clustering shows as many short intervals on a channel.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+/// Starts of the stimulations of every channel
+pub struct IntervalStats {
+    samplerate: i64,
+    slot_ms: f64,
+    starts: Vec<Vec<i64>>,
+}
+
+impl IntervalStats {
+    pub fn new(channels: u32, samplerate: i64, slot_ms: f64) -> IntervalStats {
+        IntervalStats {
+            samplerate,
+            slot_ms,
+            starts: vec![Vec::new(); channels as usize],
+        }
+    }
+
+    /// Record a stimulation of channel starting at sample
+    pub fn record(&mut self, channel: u32, sample: i64) {
+        self.starts[channel as usize].push(sample);
+    }
+
+    /// Forget the stimulations starting at or after the end of the output, they were scheduled but not rendered
+    pub fn truncate(&mut self, end: i64) {
+        for starts in self.starts.iter_mut() {
+            starts.retain(|start| *start < end);
+        }
+    }
+
+    /// Intervals in samples between the successive stimulations of every channel
+    fn intervals(&self) -> Vec<Vec<i64>> {
+        self.starts.iter().map(|starts| starts.windows(2).map(|pair| pair[1] - pair[0]).collect()).collect()
+    }
+
+    /// Interval in ms
+    fn ms(&self, interval: i64) -> f64 {
+        interval as f64 * 1000.0 / self.samplerate as f64
+    }
+
+    /// Count of the intervals of every channel by slots
+    fn histogram(&self) -> BTreeMap<i64, Vec<usize>> {
+        let mut histogram = BTreeMap::new();
+
+        for (channel, intervals) in self.intervals().iter().enumerate() {
+            for interval in intervals {
+                let slots = (self.ms(*interval) / self.slot_ms).round() as i64;
+                histogram.entry(slots).or_insert_with(|| vec![0; self.starts.len()])[channel] += 1;
+            }
+        }
+
+        histogram
+    }
+
+    /// Print the histogram and the shortest, mean and longest interval of every channel
+    pub fn report(&self, labels: &[String]) {
+        let header : Vec<String> = labels.iter().map(|label| format!("{:>8}", label)).collect();
+
+        println!("Inter-stimulation intervals per channel (rows slots of {:.1}ms, columns channels):", self.slot_ms);
+        println!("   {:>10}{}", "Slots", header.join(""));
+        for (slots, counts) in self.histogram() {
+            let row : Vec<String> = counts.iter().map(|count| format!("{:>8}", count)).collect();
+            println!("   {:>10}{}", slots, row.join(""));
+        }
+
+        let summary = |name: &str, value: &dyn Fn(&[i64]) -> Option<f64>| {
+            let row : Vec<String> = self.intervals().iter()
+                .map(|intervals| value(intervals).map_or(format!("{:>8}", "-"), |ms| format!("{:>8.1}", ms)))
+                .collect();
+            println!("   {:>10}{}", name, row.join(""));
+        };
+        summary("Min ms", &|intervals| intervals.iter().min().map(|interval| self.ms(*interval)));
+        summary("Mean ms", &|intervals| (!intervals.is_empty())
+            .then(|| self.ms(intervals.iter().sum::<i64>()) / intervals.len() as f64));
+        summary("Max ms", &|intervals| intervals.iter().max().map(|interval| self.ms(*interval)));
+    }
+
+    /// Write the histogram as CSV to path, a line `channel,slots,count` for every channel and slot count with
+    /// intervals
+    pub fn write_csv(&self, path: &str, labels: &[String]) -> io::Result<()> {
+        let histogram = self.histogram();
+        let mut csv = String::from("channel,slots,count\n");
+
+        for (channel, label) in labels.iter().enumerate() {
+            for (slots, counts) in &histogram {
+                if counts[channel] > 0 {
+                    csv.push_str(&format!("{},{},{}\n", label, slots, counts[channel]));
+                }
+            }
+        }
+
+        fs::write(path, csv)
+    }
+}
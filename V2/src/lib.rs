@@ -0,0 +1,4125 @@
+//! The F2Heal V2 generator. The f2heal-v2 binary runs `run`; for in-process analysis `render_to_vec` renders the
+//! output of an `Arguments` in memory, or `Render` streams it frame by frame. The file formats (`decode`,
+//! `schedule`) read the output back, e.g. in the integration tests.
+
+use rand::prelude::*;
+use std::collections::VecDeque;
+use std::f64::consts::{PI, TAU};
+
+use flac_bound;
+use std::fs::File;
+use std::io::Write;
+use std::str::FromStr;
+
+use clap::{CommandFactory, FromArgMatches, Parser};
+use colored::Colorize;
+
+pub use error::ConfigError;
+use rng::{GenRng, RngKind};
+use noise::{NoiseColor, NoiseGen};
+use intervals::IntervalStats;
+use progress::Progress;
+use resample::ResampleSink;
+use scale::AmplitudeScale;
+use schedule::ScheduleWriter;
+use sink::{DownmixSink, FlacSeekSink, FlacSink, PcmSink, Sink, WavF32Sink};
+
+mod audit;
+pub mod decode;
+mod error;
+mod interrupt;
+mod intervals;
+mod manifest;
+mod modes;
+mod noise;
+#[cfg(feature = "plot")]
+mod plot;
+mod preset;
+mod process;
+mod progress;
+mod resample;
+mod rng;
+mod scale;
+pub mod schedule;
+mod sink;
+mod sweep;
+mod tags;
+#[cfg(feature = "simd")]
+pub mod simd;
+mod watch;
+
+/// Maximum number of channels in a FLAC stream
+const FLAC_MAX_CHANNELS: u32 = 8;
+
+/// Placement of the envelope ramps relative to the stimulation duration
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum RampMode {
+    /// Attack and release are part of the stimulation duration
+    Inside,
+    /// Attack and release extend the stimulation
+    Outside,
+}
+
+/// Arithmetic of the slot boundaries
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum TimingModel {
+    /// Chained integer divisions, rounding towards 0
+    Legacy,
+    /// One exact rational division per boundary, rounding towards minus infinity
+    Rational,
+}
+
+/// Waveform that fills the stimulations
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum Carrier {
+    /// Sine at the stimulation frequency
+    Sine,
+    /// Noise from the seeded generator, see --noise-color
+    Noise,
+}
+
+/// Moments of the metronome click
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum MetronomeAt {
+    /// At the start of every cycle
+    Cycle,
+    /// At the start of every pauze-cycle period
+    PauzePeriod,
+}
+
+/// Granularity of the seek points of --markers
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum MarkerAt {
+    /// At the start of every slot (stimulation)
+    Slot,
+    /// At the start of every cycle, which is also where a new channel order (block) starts
+    Cycle,
+    /// At the start of every pauze-cycle period
+    PauzePeriod,
+}
+
+/// Direction to round the length of the output to whole seconds
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum RoundDuration {
+    /// Pad silence up to the next whole second
+    Up,
+    /// Cut the output at the last whole second
+    Down,
+    /// Pad or cut, whichever is shorter
+    Nearest,
+}
+
+/// Format of the output file
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum OutputFormat {
+    /// FLAC, 16 bit
+    Flac,
+    /// WAV of 32-bit IEEE floats relative to full scale, not quantized
+    WavF32,
+}
+
+#[derive(Parser, Debug, Clone)]
+#[command(author, version, about, long_about = None)]
+
+/// Create F2Heal FLAC audio output
+pub struct Arguments {
+    /// Channels or fingers per side (L/R), 
+    #[arg(short, long, default_value_t = 8)]
+    channels : u32,
+
+    /// Output file sample rate in Hz
+    #[arg(long, default_value_t = 44100)]
+    samplerate : i64,
+
+    /// Synthesize at this sample rate in Hz and resample to the output sample rate before encoding, so the
+    /// timing is rounded to samples of the synthesis rate. Resampling rings at sharp edges, use envelope ramps
+    #[arg(long, value_parser = clap::value_parser!(i64).range(1..))]
+    synth_rate : Option<i64>,
+
+    /// Frequency of finger stimulation in Hz
+    #[arg(long, default_value_t = 250)]
+    stimfreq : i64,
+
+    /// Add a second carrier at stimfreq + beat Hz, the stimulation then beats at the difference frequency.
+    /// Both carriers are mixed at half amplitude to avoid clipping
+    #[arg(long)]
+    beat: Option<i64>,
+
+    /// Waveform of the stimulations: a sine at the stimulation frequency, or noise drawn from the seeded
+    /// generator. Timing, envelope and scheduling are the same for both
+    #[arg(long, value_enum, default_value_t = Carrier::Sine)]
+    carrier: Carrier,
+
+    /// Spectrum of the noise carrier (--carrier noise)
+    #[arg(long, value_enum, default_value_t = NoiseColor::Pink)]
+    noise_color: NoiseColor,
+
+    /// File with data to encode by frequency-shift keying: symbols 0, 1, ... separated by whitespace or commas.
+    /// Every stimulation takes the next symbol and is played at that symbol's frequency in --fsk-freqs instead of
+    /// the stimulation frequency. The data starts over at its end
+    #[arg(long, requires = "fsk_freqs")]
+    fsk_data: Option<String>,
+
+    /// Stimulation frequency in Hz of every FSK symbol, comma separated
+    #[arg(long, value_delimiter = ',', requires = "fsk_data")]
+    fsk_freqs: Vec<i64>,
+
+    /// End the output after the stimulation of the last FSK symbol, instead of starting over
+    #[arg(long, default_value_t = false, requires = "fsk_data")]
+    fsk_stop: bool,
+
+    /// Alternate every channel between two stimulation frequencies in Hz on its successive stimulations (F1
+    /// first, e.g. 200:300), instead of the stimulation frequency. Every channel keeps its own alternation
+    #[arg(long, value_name = "F1:F2", value_parser = parse_alt_freqs, conflicts_with = "fsk_data")]
+    alt_freqs: Option<(i64, i64)>,
+
+    /// Vary the amplitude of every stimulation randomly by A %, drawn uniformly from [1 - A, 1 + A] and
+    /// limited to full scale
+    #[arg(long)]
+    amp_jitter: Option<i64>,
+
+    /// Scale the amplitude of every channel so every channel delivers the same energy per stimulation, when
+    /// their stimulations differ (e.g. a --stimduration per channel). The energy of a stimulation follows from
+    /// its samples (envelope, frequency, emphasis), the channels are scaled down to the one with the least energy
+    #[arg(long, default_value_t = false)]
+    energy_normalize: bool,
+
+    /// Never silence a channel: every channel plays a continuous baseline tone at --stimfreq, the stimulation
+    /// of the active channel is added on top. The baseline keeps going in pauzes and gaps
+    #[arg(long, default_value_t = false)]
+    continuous_mode: bool,
+
+    /// Amplitude of the baseline tone of --continuous-mode (above 0 and below 1, relative to full scale), the
+    /// stimulation gets the rest, so a channel stays within full scale
+    #[arg(long, default_value_t = 0.2, requires = "continuous_mode")]
+    baseline_level: f64,
+
+    /// Mix an audible marker tone into every stimulation, for bimodal (tactile and auditory) experiments: the
+    /// marker has the envelope of the stimulation, so the cue is synchronized to the burst
+    #[arg(long, default_value_t = false)]
+    dual_carrier: bool,
+
+    /// Frequency in Hz of the marker tone of --dual-carrier
+    #[arg(long, default_value_t = 1000, requires = "dual_carrier")]
+    marker_freq: i64,
+
+    /// Amplitude of the marker tone of --dual-carrier (above 0 and below 1, relative to the stimulation), the
+    /// stimulation carrier gets the rest, so their sum stays within full scale
+    #[arg(long, default_value_t = 0.1, requires = "dual_carrier")]
+    marker_level: f64,
+
+    /// Equalize the transducer frequency response with a gain per stimulation frequency. The file has one
+    /// "frequency gain" pair per line (Hz and dB, e.g. "300 3.5"), the gain is interpolated linearly in between
+    #[arg(long)]
+    emphasis_curve: Option<String>,
+
+    /// Start every stimulation at a random carrier phase instead of 0, drawn per channel every cycle, so
+    /// simultaneous stimulations (chords, overlap) don't add up to coherent peaks
+    #[arg(long, default_value_t = false)]
+    random_phase: bool,
+
+    /// Duration of the finger stimulation in ms, or one per channel (e.g. 150,120,100,100,80), comma separated
+    #[arg(long, value_delimiter = ',', default_value = "100")]
+    stimduration : Vec<i64>,
+
+    /// Attack of the stimulation envelope in ms: the amplitude rises linearly from 0 to full scale
+    #[arg(long, default_value_t = 0)]
+    attack_ms: i64,
+
+    /// Decay of the stimulation envelope in ms: the amplitude falls linearly from full scale to the sustain level
+    #[arg(long, default_value_t = 0)]
+    decay_ms: i64,
+
+    /// Sustain level of the stimulation envelope in % of full scale, held for the rest of the stimulation
+    #[arg(long, default_value_t = 100)]
+    sustain_level: i64,
+
+    /// Release of the stimulation envelope in ms: the amplitude falls linearly from the sustain level to 0 at the
+    /// end of the stimulation. Attack, decay and release are scaled down together when they exceed the stimulation
+    #[arg(long, default_value_t = 0)]
+    release_ms: i64,
+
+    /// Whether attack and release are carved from the stimulation duration (inside), or added before and after
+    /// it (outside), so the stimulation keeps its full duration after the attack
+    #[arg(long, value_enum, default_value_t = RampMode::Inside)]
+    ramp_mode: RampMode,
+
+    /// Adjust the stimulation frequency to the nearest frequency that completes a whole number of
+    /// sine periods within the stimulation duration
+    #[arg(long, default_value_t = false)]
+    snap_stimfreq: bool,
+
+    /// Support stimulations that last longer than the channel's slot in the cycle: the stimulation continues
+    /// while the next channel starts. Stimulations overlapping on the same channel are averaged.
+    /// Without overlap, a stimulation longer than the slot is cut off when the next stimulation starts
+    #[arg(long, default_value_t = false)]
+    overlap: bool,
+
+    /// Duration of one cycle (stimulation of all fingers)
+    #[arg(long, default_value_t = 888)]
+    cycleperiod : i64,
+
+    /// Interval between the starts of successive stimulations in ms. Sets the cycle period to channels * ibi
+    #[arg(long, conflicts_with = "cycleperiod")]
+    ibi : Option<i64>,
+
+    /// Duration of one cycle in samples instead of ms, for exact cycle boundaries that don't drift
+    #[arg(long, conflicts_with_all = ["cycleperiod", "ibi"])]
+    cycleperiod_samples : Option<i64>,
+
+    /// Change the cycle period linearly from START to END ms over the output (e.g. 888:666), so the tempo speeds
+    /// up or slows down during the session
+    #[arg(long, value_name = "START:END", value_parser = parse_ramp,
+        conflicts_with_all = ["cycleperiod", "ibi", "cycleperiod_samples", "total_bursts"])]
+    cycleperiod_ramp : Option<(i64, i64)>,
+
+    /// Insert a random silent gap of MIN to MAX ms (drawn every cycle from the seeded generator) before every
+    /// cycle, the rest of the schedule shifts by the gap. Breaks up the strictly periodic tempo
+    #[arg(long, value_name = "MIN:MAX", value_parser = parse_gap)]
+    intercycle_gap : Option<(i64, i64)>,
+
+    /// Arithmetic of the slot boundaries: legacy chains integer divisions, rational divides the exact fraction
+    /// of elapsed slots once (in 128 bits) and rounds down also before sample 0, which jittered starts can reach
+    #[arg(long, value_enum, default_value_t = TimingModel::Legacy, conflicts_with = "cycleperiod_ramp")]
+    timing_model: TimingModel,
+
+    /// Start the first cycle MS ms after the start of the file, the file starts with that much silence. Files
+    /// rendered with the same timing and anchor have their cycle grids aligned to the sample when started together
+    #[arg(long, value_name = "MS", allow_hyphen_values = true, conflicts_with = "trim_leading_silence")]
+    grid_anchor : Option<f64>,
+
+    /// Apply jitter J (0-100) for in blocked mode. J is % of 1/8th of cycleperiod so that, apart from first channel, 
+    /// every start is delayed over ] s0 - J * cycleperiod / 8 , s0 + J * cycleperiod / 8 [ (from a uniform distribution)
+    #[arg(short, long)]
+    jitter: Option<i64>,
+
+    /// Limit the jitter to the largest jitter that keeps every stimulation complete within its (jittered) slot,
+    /// see max_safe_jitter
+    #[arg(long, default_value_t = false)]
+    clamp_jitter_to_slot: bool,
+
+    /// Duration (in cycles) of one pauze-cycle
+    #[arg(long, default_value_t = 5)]
+    pauzecycleperiod : i64,
+
+    /// The cycles (within the pauze-cycle) with no stimulation output produced. You can use this option more than once.
+    #[arg(short, long)]
+    pauzes : Vec<i64>,
+
+    /// The only cycles (within the pauze-cycle) with stimulation output produced, all others are pauzed. The inverse
+    /// of --pauzes. You can use this option more than once.
+    #[arg(long)]
+    active_cycles : Vec<i64>,
+
+    /// Pauze this number of randomly chosen cycles in every pauze-cycle, instead of fixed cycles
+    #[arg(long, conflicts_with_all = ["pauzes", "active_cycles"])]
+    random_pauzes: Option<i64>,
+
+    /// Duration in sec of output
+    #[arg(short,long, required_unless_present_any = ["total_bursts", "cycles", "compare", "process", "list_modes", "describe_mode", "watch", "version_full", "self_test", "rng_audit", "list_presets", "char_sweep"])]
+    secondsoutput: Option<i64>,
+
+    /// Stop the output after this number of stimulations (instead of a duration)
+    #[arg(long, conflicts_with = "secondsoutput")]
+    total_bursts: Option<i64>,
+
+    /// Render exactly this number of whole cycles (instead of a duration): the output ends where the next cycle
+    /// would start, so no cycle is cut off
+    #[arg(long, value_parser = clap::value_parser!(i64).range(1..),
+        conflicts_with_all = ["secondsoutput", "total_bursts", "cycleperiod_ramp", "intercycle_gap"])]
+    cycles: Option<i64>,
+
+    /// Round the length of an output ended by --cycles or --total-bursts to whole seconds, by padding silence after
+    /// the end (up) or cutting the output (down). The output is at least 1 second
+    #[arg(long, value_enum, conflicts_with_all = ["secondsoutput", "synth_rate", "trim_leading_silence"])]
+    round_duration: Option<RoundDuration>,
+
+    /// Fade the whole output in over the first M seconds and out over the last M seconds, with a raised cosine.
+    /// Without a duration (--total-bursts, --cycles) only the fade in is applied
+    #[arg(long)]
+    session_fade_sec: Option<i64>,
+
+    /// Scripted staircase: step the amplitude through these levels (% of full scale, comma separated), each
+    /// held for --staircase-cycles cycles, starting over after the last level
+    #[arg(long, value_delimiter = ',')]
+    staircase: Vec<i64>,
+
+    /// Number of cycles every staircase level is held
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(i64).range(1..), requires = "staircase")]
+    staircase_cycles: i64,
+
+    /// Curve from the session fade and staircase levels to the amplitude: linear, log (linear in dB over 40dB)
+    /// or power:EXP (the level to the power EXP)
+    #[arg(long, value_name = "SCALE", default_value = "linear", value_parser = scale::parse)]
+    amplitude_scale: AmplitudeScale,
+
+    /// Render only the first seconds of output for a quick check, the filename gets a PREVIEW marker
+    #[arg(long)]
+    preview: Option<i64>,
+
+    /// Random seed (default from timer)
+    #[arg(long)]
+    randomseed: Option<i64>,
+
+    /// Random seed from a passphrase (e.g. monday-protocol-A), easier to record than a number. The phrase always
+    /// gives the same seed, which is used as --randomseed and shown in the filename and manifest
+    #[arg(long, value_name = "PHRASE", conflicts_with = "randomseed")]
+    seed_phrase: Option<String>,
+
+    /// Shuffle with the Fisher-Yates of this crate instead of the one of the rand crate, so the channel orders
+    /// of a seed don't change with a rand upgrade. The algorithm version is recorded in the manifest
+    #[arg(long, default_value_t = false)]
+    deterministic_shuffle: bool,
+
+    /// Disable randomization of channels in blocked mode, and thus plays channels in order 1->2->3->4. The
+    /// jitter then follows a fixed pattern instead of random draws, so the timing doesn't depend on the seed
+    #[arg(long, default_value_t = false)]
+    norandom: bool,
+
+    /// Reverse the channel order on every other block (a full pass over the channels): a block plays the order of
+    /// the block before it backwards, so adjacency effects balance out. With randomization the other blocks get a
+    /// new random order. The reversed block starts with the channel that ended the previous one
+    #[arg(long, default_value_t = false, conflicts_with = "sequence_file")]
+    alternate_direction: bool,
+
+    /// Rest every channel at least N slots (stimulations of other channels) between two of its stimulations,
+    /// instead of only never stimulating a channel twice in a row (N = 1). Every position of a new random order
+    /// draws from the channels that rested enough. Every channel is stimulated every cycle, so more than
+    /// channels - 1 is relaxed to channels - 1 (with a warning)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with_all = ["norandom", "channel_seeding", "alternate_direction", "sequence_file", "chord_size"])]
+    min_channel_rest: Option<u32>,
+
+    /// Replay the stimulation sequence from file instead of generating a channel order. Each line lists the
+    /// channel(s) active for one cycle, comma separated (e.g. "0" or "0,2"). The sequence loops when exhausted.
+    #[arg(long)]
+    sequence_file: Option<String>,
+
+    /// Stimulate K randomly chosen channels at once in every cycle, instead of a single one. The channels of a
+    /// chord are scaled by 1/K, so the chord as a whole has the amplitude of a single stimulation
+    #[arg(long, conflicts_with = "sequence_file")]
+    chord_size: Option<u32>,
+
+    /// Names of the channels (e.g. thumb,index,middle,ring,little), one per channel, comma separated.
+    /// Defaults to Ch0..ChN
+    #[arg(long, value_delimiter = ',')]
+    finger_labels: Vec<String>,
+
+    /// Fixed output delay per channel in ms (e.g. 0,1.5,-0.5,0), one per channel, comma separated, to compensate
+    /// for transducer latencies. Negative delays are realized by delaying all other channels (and the trigger)
+    #[arg(long, value_delimiter = ',', allow_hyphen_values = true)]
+    channel_delay: Vec<f64>,
+
+    /// Fixed offset per channel in ms (e.g. 0,5,10,0) of the stimulation start within the channel's slot, one per
+    /// channel, comma separated. Unlike --channel-delay this moves the stimulation itself (and the trigger)
+    #[arg(long, value_delimiter = ',')]
+    channel_offset: Vec<f64>,
+
+    /// Add a trigger channel (after the stimulation channels) with a square pulse at the start of every stimulation,
+    /// e.g. for EEG alignment
+    #[arg(long, default_value_t = false)]
+    trigger_channel: bool,
+
+    /// Width of the trigger pulse in ms
+    #[arg(long, default_value_t = 10)]
+    trigger_width: i64,
+
+    /// Add a metronome channel (after the stimulation and trigger channels) with a short audible click at the
+    /// start of every cycle (default) or every pauze-cycle period, for the patient to anticipate or for alignment
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "cycle")]
+    metronome: Option<MetronomeAt>,
+
+    /// Length of the metronome click in ms
+    #[arg(long, default_value_t = 5)]
+    metronome_click_ms: i64,
+
+    /// Frequency of the metronome click in Hz
+    #[arg(long, default_value_t = 1000)]
+    metronome_freq: i64,
+
+    /// Also write the output as raw PCM (16 bit signed little endian, interleaved) for auditioning, e.g. to a
+    /// FIFO played with `aplay -f S16_LE -c <channels> -r <samplerate>`. Only this stream gets the preview gain
+    /// and octave, the output file keeps the true parameters
+    #[arg(long)]
+    play_pcm: Option<String>,
+
+    /// Gain in dB applied to the auditioning stream only
+    #[arg(long, default_value_t = 0.0)]
+    preview_gain: f64,
+
+    /// Shift the carrier of the auditioning stream up by this many octaves, to make the pattern audible
+    #[arg(long, default_value_t = 0)]
+    preview_octave: u32,
+
+    /// Sum the channels of the auditioning stream into N device channels, e.g. 2 to preview a multichannel
+    /// output on stereo hardware. By default channel c goes to device channel c % N (2: even channels left, odd
+    /// right). The output file keeps all channels
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), requires = "play_pcm")]
+    downmix: Option<u32>,
+
+    /// Device channel of every output channel (trigger and metronome included) for --downmix, comma separated
+    /// (e.g. 0,0,1,1)
+    #[arg(long, value_delimiter = ',', requires = "downmix")]
+    downmix_map: Vec<u32>,
+
+    /// Template for the output filename. Placeholders: {default} (all parameters, the default naming scheme),
+    /// {mode}, {stimfreq}, {stimduration}, {cycleperiod}, {channels}, {samplerate}, {duration}, {seed} and {date}
+    #[arg(long, default_value = "output/{default}.flac")]
+    name_template: String,
+
+    /// Treat all warnings about the supplied arguments as errors
+    #[arg(long, default_value_t = false)]
+    strict: bool,
+
+    /// Write a reproducibility manifest (JSON) next to the output file
+    #[arg(long, default_value_t = false)]
+    manifest: bool,
+
+    /// Report the peak level (dBFS) and the number of samples at full scale after rendering. With --strict,
+    /// samples at full scale end the program with an error (after writing the output)
+    #[arg(long, default_value_t = false)]
+    headroom_report: bool,
+
+    /// Report a histogram of the intervals between the stimulations of every channel after rendering, in slots,
+    /// with the shortest, mean and longest interval. With CSV the histogram is written to that file as well
+    #[arg(long, value_name = "CSV")]
+    render_stats_per_channel: Option<Option<String>>,
+
+    /// Write the progress of the render to stderr as JSON lines (frames_done, frames_total, percent and
+    /// eta_seconds) for a wrapping program, at most every 500ms
+    #[arg(long, default_value_t = false)]
+    progress_json: bool,
+
+    /// Compute every sample with the scalar path, also when the simd feature is built and the CPU supports it,
+    /// e.g. to check that both give the same output
+    #[arg(long, default_value_t = false)]
+    force_scalar: bool,
+
+    /// Free disk space in MB that must remain after writing the estimated output, checked before rendering
+    #[arg(long, default_value_t = 100)]
+    min_free_mb: u64,
+
+    /// Refuse to generate a session whose estimated stimulation energy exceeds this budget, in full scale
+    /// seconds (the sum over all channels of the squared samples relative to full scale, divided by the
+    /// sample rate). A full scale sine for 1 second on one channel is 0.5
+    #[arg(long)]
+    max_energy: Option<f64>,
+
+    /// Before rendering, run the schedule for this number of cycles and refuse to generate the output when a
+    /// channel is not stimulated in them
+    #[arg(long, value_name = "CYCLES")]
+    require_all_channels: Option<i64>,
+
+    /// Check the invariants of the schedule at every sample (the sample and cycle start never go back, the
+    /// current cycle and its channel are in range) and panic with the sample and state when one breaks. For
+    /// development, it slows down the render
+    #[arg(long, default_value_t = false)]
+    debug_invariants: bool,
+
+    /// Don't write the silence before the first stimulation (e.g. when the output starts with a pauze), the
+    /// file starts at the first non-zero sample. The schedule is not shifted
+    #[arg(long, default_value_t = false)]
+    trim_leading_silence: bool,
+
+    /// Output format: flac (16 bit) or wav-f32, the samples as 32-bit floats relative to full scale before the
+    /// conversion to 16 bit, for analysis without quantization. A .flac extension of the filename becomes .wav
+    #[arg(long, value_enum, default_value_t = OutputFormat::Flac,
+        conflicts_with_all = ["markers", "synth_rate", "channel_delay", "trim_leading_silence"])]
+    format: OutputFormat,
+
+    /// Add a FLAC seek table with a seek point at the start of every cycle (default), slot or pauze-cycle
+    /// period, to jump between stimulation segments in an editor. The schedule is run once before rendering
+    /// to find the points
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "cycle", conflicts_with = "trim_leading_silence")]
+    markers: Option<MarkerAt>,
+
+    /// Write the samples of a single stimulation (carrier, envelope and emphasis at --stimfreq) to this CSV file
+    /// instead of generating output, to inspect or share the exact stimulus
+    #[arg(long, value_name = "PATH")]
+    dump_burst: Option<String>,
+
+    /// Write the parameters as Vorbis comments (F2HEAL_<NAME>=<value>) into FILE, an existing FLAC output of
+    /// them, instead of generating output. The audio is not re-encoded, earlier f2heal comments are replaced
+    #[arg(long, value_name = "FILE")]
+    tag: Option<String>,
+
+    /// Write which channels are stimulated at every sample, and at what level, to this binary file (the format is
+    /// described in src/schedule.rs), as ground truth for the analysis of recordings
+    #[arg(long, value_name = "PATH", conflicts_with = "trim_leading_silence")]
+    dense_schedule: Option<String>,
+
+    /// Record only every Nth sample in the dense schedule
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..), requires = "dense_schedule")]
+    dense_schedule_downsample: u32,
+
+    /// Number of frames collected before they are passed to the FLAC encoder at once
+    #[arg(long, default_value_t = 4096, value_parser = clap::value_parser!(u32).range(1..))]
+    buffer_frames: u32,
+
+    /// Seed used when no random seed is supplied
+    #[arg(skip = rand::random::<u64>())]
+    entropy_seed: u64,
+
+    /// Draw the random decisions of each channel from its own random stream (derived from the seed and the
+    /// channel index), so the randomization of a channel does not change when channels are added or removed
+    #[arg(long, default_value_t = false)]
+    channel_seeding: bool,
+
+    /// Random number generator: chacha8, chacha20 (cryptographic), xoshiro (Xoshiro256++) or pcg (PCG32)
+    #[arg(long, value_enum, default_value_t = RngKind::Chacha8)]
+    rng: RngKind,
+
+    /// Discard the first draws of every random stream after seeding, for the same sequences as tools that warm up
+    /// their generator. Changes the randomization, so it is part of the output parameters
+    #[arg(long, default_value_t = 0)]
+    rng_warmup: u64,
+
+    /// Derive the seed from the random seed and the parameters of the output, so every file of a parameter sweep
+    /// gets its own randomization while the whole sweep is reproducible from one --randomseed. The derived seed is
+    /// the 64-bit FNV-1a hash of the configuration (without the preview and naming options)
+    #[arg(long, default_value_t = false)]
+    seed_per_file: bool,
+
+    /// Write a PNG with the waveform of every channel for the first cycles, for a quick visual check
+    #[cfg(feature = "plot")]
+    #[arg(long)]
+    plot: Option<String>,
+
+    /// Compare two FLAC files sample by sample instead of generating output. Exits with an error when they differ
+    #[arg(long, num_args = 2, value_names = ["A", "B"])]
+    compare: Vec<String>,
+
+    /// Transform an existing FLAC file instead of generating output, with --gain, --trim and --remap (applied
+    /// in the order trim, remap, gain). The result is written next to the input with a PROCESSED marker
+    #[arg(long)]
+    process: Option<String>,
+
+    /// List the stimulation modes with their parameters instead of generating output
+    #[arg(long, default_value_t = false)]
+    list_modes: bool,
+
+    /// Print the version with the git commit, the enabled features and the versions of everything that affects
+    /// the output for a seed (random generators, shuffles), instead of generating output
+    #[arg(long, default_value_t = false)]
+    version_full: bool,
+
+    /// Check that the verbose diagnostics (-v -v -v) don't change the schedule (run a fixed jittered configuration
+    /// with and without them and compare the active channels at every sample) and that the slot boundaries of both
+    /// timing models don't drift over a million samples, instead of generating output
+    #[arg(long, default_value_t = false)]
+    self_test: bool,
+
+    /// Draw PATTERNS channel orders with the scheduler (and the random options given) and test with chi-square
+    /// whether every channel is equally likely at every position, also after avoiding an immediate repeat, instead
+    /// of generating output. Exits with 1 when a test finds a bias
+    #[arg(long, value_name = "PATTERNS", value_parser = clap::value_parser!(u64).range(1..))]
+    rng_audit: Option<u64>,
+
+    /// Only check the arguments, print the problems found as JSON and exit with 0 (no problems), 1 (only
+    /// warnings) or 2 (errors)
+    #[arg(long, default_value_t = false)]
+    validate_only: bool,
+
+    /// Describe a stimulation mode (see --list-modes) with an example instead of generating output
+    #[arg(long, value_name = "NAME")]
+    describe_mode: Option<String>,
+
+    /// Load the options of a named preset (see --list-presets), built-in or a NAME.toml in the presets directory.
+    /// Options on the command line replace those of the preset
+    #[arg(long, value_name = "NAME")]
+    preset: Option<String>,
+
+    /// List the built-in and user presets with their options instead of generating output
+    #[arg(long, default_value_t = false)]
+    list_presets: bool,
+
+    /// Save the options on the command line (with those of --preset) as user preset NAME in the presets directory
+    /// after checking them, instead of generating output. The duration is needed for the checks and saved too
+    #[arg(long, value_name = "NAME")]
+    save_preset: Option<String>,
+
+    /// Render with the command line options in FILE (whitespace separated, # starts a comment, no quoting) every
+    /// time it is saved, interrupting a render still running. FILE isn't TOML and is polled for changes, see the
+    /// README. Pair it with --preview for quick iterations
+    #[arg(long, value_name = "FILE")]
+    watch: Option<String>,
+
+    /// Gain in dB for --process, the samples are clipped to full scale
+    #[arg(long, requires = "process", allow_hyphen_values = true)]
+    gain: Option<f64>,
+
+    /// Part of the file to keep for --process, as start:end in seconds
+    #[arg(long, requires = "process", value_parser = process::parse_trim)]
+    trim: Option<(f64, f64)>,
+
+    /// Channels of the input for every output channel for --process, comma separated (e.g. 3,2,1,0 reverses
+    /// 4 channels). Channels can be repeated or left out
+    #[arg(long, requires = "process", value_delimiter = ',')]
+    remap: Vec<u32>,
+
+    /// Play every channel in turn at each of these frequencies in Hz (comma separated, e.g. 100,200,300) for
+    /// --char-hold ms, to characterize the transducers, instead of the stimulation schedule. Every tone gets a seek
+    /// point and a comment labeling its channel and frequency
+    #[arg(long, value_name = "FREQS", alias = "channel-test-sequence", conflicts_with_all = ["process", "format", "synth_rate"])]
+    char_sweep: Option<String>,
+
+    /// Duration in ms of every tone of --char-sweep
+    #[arg(long, default_value_t = 500, requires = "char_sweep")]
+    char_hold: i64,
+
+    /// Output verbosity. You can use this option more than once.
+    #[clap(short, long, action = clap::ArgAction::Count)]
+    verbosity: u8,
+
+}
+
+impl Arguments {
+
+    /// Resolve --seed-phrase to the random seed
+    fn apply_seed_phrase(&mut self) {
+        if let Some(phrase) = &self.seed_phrase {
+            self.randomseed = Some(phrase_seed(phrase));
+        }
+    }
+
+    /// Snap the stimulation frequency so the stimulation sine ends on the end of the stimulation period. Invalid
+    /// durations are left to verify_argvalues
+    fn apply_snap_stimfreq(&mut self) {
+        if !self.stimdurations_valid() {
+            return;
+        }
+
+        let stimfreq_frame = self.stimfreq_frame();
+        let snapped = ((self.stimfreq as f64 / stimfreq_frame as f64).round() as i64).max(1) * stimfreq_frame;
+
+        if snapped != self.stimfreq {
+            println!("Stimulation frequency snapped from {}Hz to {}Hz", self.stimfreq, snapped);
+            self.stimfreq = snapped;
+        }
+    }
+
+    /// Check the arguments as the binary does before generating output, returns the errors (not the warnings)
+    /// found. Done by `Render::new` and `render_to_vec`
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let errors : Vec<ConfigError> = self.verify_argvalues().into_iter().filter(|problem| problem.is_error()).collect();
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// Verify the supplied arguments make sense for generating output, returns all problems found
+    fn verify_argvalues(&self) -> Vec<ConfigError> {
+        let mut problems = Vec::new();
+
+        // Do the stimulation frequency en period match, otherwise said, does the stimulation sine
+        // end on period end
+        if let Some(duration) = self.stimduration.iter().find(|duration| !(1..=1000).contains(*duration)) {
+            problems.push(ConfigError::StimdurationRange(*duration));
+        } else if self.stimfreq % self.stimfreq_frame() != 0 {
+            problems.push(ConfigError::StimFreqMismatch);
+        }
+
+        // Is there a duration for every channel
+        if self.stimduration.len() != 1 && self.stimduration.len() != self.channels as usize {
+            problems.push(ConfigError::StimdurationCount { durations: self.stimduration.len(), channels: self.channels });
+        }
+
+        if self.continuous_mode && !(self.baseline_level > 0.0 && self.baseline_level < 1.0) {
+            problems.push(ConfigError::BaselineLevel(self.baseline_level));
+        }
+
+        if self.dual_carrier {
+            if !(self.marker_level > 0.0 && self.marker_level < 1.0) {
+                problems.push(ConfigError::MarkerLevel(self.marker_level));
+            }
+            if self.marker_freq <= 0 || self.marker_freq * 2 >= self.samplerate {
+                problems.push(ConfigError::MarkerFreq { freq: self.marker_freq, nyquist: self.samplerate / 2 });
+            }
+        }
+
+        // The sizes in a WAV header are 32 bit
+        if self.format == OutputFormat::WavF32 && self.estimated_bytes() > u32::MAX as u64 - 1024 {
+            problems.push(ConfigError::WavTooLarge(self.estimated_bytes()));
+        }
+
+        // Without overlap a stimulation ends when the next one starts, every channel's stimulation must fit its slot
+        if self.longest_burst_duration() * self.channels as i64 > self.cycle_period() && !self.overlap {
+            problems.push(ConfigError::OverlappingStimulation {
+                stimulation: self.longest_burst_duration(),
+                slot: self.cycle_period() / self.channels as i64,
+            });
+        }
+
+        // Do the fade in and fade out fit in the output
+        if let (Some(fade), Some(secondsoutput)) = (self.session_fade_sec, self.secondsoutput) {
+            if 2 * fade > secondsoutput {
+                problems.push(ConfigError::SessionFadeTooLong(fade));
+            }
+        }
+
+        // A gap is meant as a small irregularity of the tempo
+        if let Some((_, max)) = self.intercycle_gap {
+            if max * 10 > self.cycle_period() {
+                problems.push(ConfigError::IntercycleGapTooLarge { max, cycleperiod: self.cycle_period() });
+            }
+        }
+
+        // The resampler only passes frequencies well below the Nyquist frequency of both rates
+        if let Some(synth_rate) = self.synth_rate {
+            let passband = resample::PASSBAND * synth_rate.min(self.samplerate) as f64 / 2.0;
+
+            let marker = self.marker().map(|(freq, _)| freq);
+            for freq in self.stimulation_freqs().iter().flat_map(|freq| self.carrier_freqs(*freq)).chain(marker) {
+                if freq as f64 > passband {
+                    problems.push(ConfigError::ResamplePassband { freq, passband: passband as i64 });
+                }
+            }
+        }
+
+        // A preview only makes sense as an excerpt of the full output
+        if let Some(preview) = self.preview {
+            if self.secondsoutput.is_some_and(|secondsoutput| preview > secondsoutput) {
+                problems.push(ConfigError::PreviewTooLong(preview));
+            }
+        }
+
+        if let Some(amp_jitter) = self.amp_jitter {
+            if !(0..=100).contains(&amp_jitter) {
+                problems.push(ConfigError::InvalidPercentage("amp-jitter", amp_jitter));
+            }
+        }
+
+        for level in &self.staircase {
+            if !(0..=100).contains(level) {
+                problems.push(ConfigError::InvalidPercentage("staircase", *level));
+            }
+        }
+
+        // Every channel is stimulated every cycle, at most channels - 1 others fit between two stimulations
+        if let Some(rest) = self.min_channel_rest {
+            if rest > self.channels - 1 {
+                problems.push(ConfigError::ChannelRestRelaxed { rest, max: self.channels - 1 });
+            }
+        }
+
+        // Does the jitter keep the stimulations within their slots. Above 100 % a delayed and a prestarted start
+        // can cross, which loses a stimulation
+        if let Some(jitter) = self.jitter {
+            if !(0..=100).contains(&jitter) {
+                problems.push(ConfigError::InvalidPercentage("jitter", jitter));
+            } else if jitter > self.max_safe_jitter() && !self.clamp_jitter_to_slot {
+                problems.push(ConfigError::JitterTooLarge { jitter, max: self.max_safe_jitter() });
+            }
+        }
+
+        if !(0..=100).contains(&self.sustain_level) {
+            problems.push(ConfigError::InvalidPercentage("sustain-level", self.sustain_level));
+        }
+
+        // Does the envelope fit in the stimulation, otherwise it gets scaled down
+        let envelope_duration = self.carved_envelope();
+        if envelope_duration > self.stimduration.iter().copied().min().unwrap_or(0) {
+            problems.push(ConfigError::EnvelopeTooLong(envelope_duration));
+        }
+
+        // A beat is only felt as such when it is slow compared to the carrier
+        if let Some(beat) = self.beat {
+            if beat <= 0 || beat * 10 > self.stimfreq {
+                problems.push(ConfigError::BeatTooFast(beat));
+            }
+        }
+
+        // Are the selected pauzes within the pauze period
+        for pauze in self.pauzes.iter() {
+            if pauze >= &self.pauzecycleperiod {
+                problems.push(ConfigError::IneffectivePauze(*pauze));
+            }
+        }
+
+        // Pauzes and active cycles are each other's inverse, only one of them can be used
+        if !self.pauzes.is_empty() && !self.active_cycles.is_empty() {
+            problems.push(ConfigError::PauzesAndActiveCycles);
+        }
+
+        if let Some(count) = self.random_pauzes {
+            if count < 0 || count > self.pauzecycleperiod {
+                problems.push(ConfigError::RandomPauzeCount { count, pauzecycleperiod: self.pauzecycleperiod });
+            }
+        }
+
+        // Does the name template only use known placeholders
+        let placeholders = self.placeholders();
+        for placeholder in self.name_template.split('{').skip(1).filter_map(|part| part.split_once('}')).map(|(name, _)| name) {
+            if !placeholders.iter().any(|(name, _)| *name == placeholder) {
+                problems.push(ConfigError::UnknownPlaceholder(placeholder.to_owned()));
+            }
+        }
+
+        // Can the output format hold all channels
+        if self.output_channels() > FLAC_MAX_CHANNELS {
+            problems.push(ConfigError::TooManyChannels { channels: self.output_channels(), max: FLAC_MAX_CHANNELS });
+        }
+
+        // Does the session stay within the energy budget
+        if let Some(budget) = self.max_energy {
+            let energy = self.estimated_energy();
+            if energy > budget {
+                problems.push(ConfigError::EnergyBudget { energy, budget });
+            }
+        }
+
+        // Does every channel get stimulated, a misconfiguration can leave channels silent
+        if let Some(cycles) = self.require_all_channels {
+            let missing = unscheduled_channels(self, cycles);
+            if !missing.is_empty() {
+                problems.push(ConfigError::UnscheduledChannels { missing, cycles });
+            }
+        }
+
+        // Does the first cycle start within the file
+        if let Some(anchor) = self.grid_anchor {
+            if anchor < 0.0 {
+                problems.push(ConfigError::NegativeGridAnchor(anchor));
+            }
+        }
+
+        // Does the chord fit in the channels
+        if let Some(chord_size) = self.chord_size {
+            if chord_size == 0 || chord_size > self.channels {
+                problems.push(ConfigError::ChordSize { size: chord_size, channels: self.channels });
+            }
+        }
+
+        // Is there a label for every channel
+        if !self.finger_labels.is_empty() && self.finger_labels.len() != self.channels as usize {
+            problems.push(ConfigError::FingerLabelCount { labels: self.finger_labels.len(), channels: self.channels });
+        }
+
+        // Is there a delay for every channel
+        if !self.channel_delay.is_empty() && self.channel_delay.len() != self.channels as usize {
+            problems.push(ConfigError::ChannelDelayCount { delays: self.channel_delay.len(), channels: self.channels });
+        }
+
+        // Does the downmix map every output channel to a device channel
+        if let Some(downmix) = self.downmix {
+            if !self.downmix_map.is_empty() && self.downmix_map.len() != self.output_channels() as usize {
+                problems.push(ConfigError::DownmixMapCount { entries: self.downmix_map.len(), channels: self.output_channels() });
+            }
+            if let Some(channel) = self.downmix_map.iter().find(|channel| **channel >= downmix) {
+                problems.push(ConfigError::DownmixMapChannel { channel: *channel, downmix });
+            }
+        }
+
+        // Is there an offset for every channel, and does it keep the stimulation within its slot
+        if !self.channel_offset.is_empty() {
+            if self.channel_offset.len() != self.channels as usize {
+                problems.push(ConfigError::ChannelOffsetCount { offsets: self.channel_offset.len(), channels: self.channels });
+            }
+
+            let slot = self.cycle_period() as f64 / self.channels as f64;
+            for (channel, offset) in self.channel_offset.iter().enumerate() {
+                if *offset < 0.0 {
+                    problems.push(ConfigError::NegativeChannelOffset(channel as u32));
+                } else if offset + self.burst_duration(channel as u32) as f64 > slot {
+                    problems.push(ConfigError::ChannelOffsetOutsideSlot(channel as u32));
+                }
+            }
+        }
+
+        // Does the FSK data only use symbols with a frequency
+        if self.fsk_data.is_some() {
+            match self.read_fsk_data() {
+                Ok(data) if data.is_empty() => problems.push(ConfigError::EmptyFskData),
+                Ok(data) => {
+                    if let Some((position, symbol)) = data.iter().enumerate().find(|(_, symbol)| **symbol >= self.fsk_freqs.len()) {
+                        problems.push(ConfigError::FskSymbol { symbol: *symbol, position: position + 1, symbols: self.fsk_freqs.len() });
+                    }
+                }
+                Err(problem) => problems.push(problem),
+            }
+        }
+
+        // Does the emphasis curve cover all stimulation frequencies
+        if self.emphasis_curve.is_some() {
+            match self.read_emphasis_curve() {
+                Ok(curve) => match (curve.first(), curve.last()) {
+                    (Some((min, _)), Some((max, _))) => {
+                        for freq in self.stimulation_freqs() {
+                            if (freq as f64) < *min || (freq as f64) > *max {
+                                problems.push(ConfigError::EmphasisRange { freq, min: *min, max: *max });
+                            }
+                        }
+                    }
+                    _ => problems.push(ConfigError::EmptyEmphasisCurve),
+                },
+                Err(problem) => problems.push(problem),
+            }
+        }
+
+        // Does the sequence file only refer to existing channels
+        if self.sequence_file.is_some() {
+            match self.read_sequence() {
+                Ok(sequence) if sequence.is_empty() => problems.push(ConfigError::EmptySequence),
+                Ok(sequence) => {
+                    for (line, step) in sequence.iter() {
+                        for channel in step.iter() {
+                            if *channel >= self.channels {
+                                problems.push(ConfigError::SequenceChannel { channel: *channel, line: *line, channels: self.channels });
+                            }
+                        }
+                    }
+                }
+                Err(problem) => problems.push(problem),
+            }
+        }
+
+        problems
+    }
+
+    /// Read the stimulation sequence file, one cycle per line with the active channels. Every step has the number
+    /// of its line in the file (from 1), blank lines are skipped
+    fn read_sequence(&self) -> Result<Vec<(usize, Vec<u32>)>, ConfigError> {
+        let fname = self.sequence_file.as_ref().unwrap();
+        let invalid = |line, reason| ConfigError::InvalidFile { parameter: "sequence-file", file: fname.clone(), line, reason };
+        let content = std::fs::read_to_string(fname).map_err(|e| invalid(None, format!("cannot read: {}", e)))?;
+
+        content.lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(number, line)| {
+                let step = line.split(',')
+                    .map(|channel| channel.trim().parse::<u32>()
+                        .map_err(|_| invalid(Some(number), format!("invalid channel: {}", line))))
+                    .collect::<Result<_, _>>()?;
+                Ok((number, step))
+            })
+            .collect()
+    }
+
+    /// Read the FSK data file, the symbols in order
+    fn read_fsk_data(&self) -> Result<Vec<usize>, ConfigError> {
+        let fname = self.fsk_data.as_ref().unwrap();
+        let invalid = |line, reason| ConfigError::InvalidFile { parameter: "fsk-data", file: fname.clone(), line, reason };
+        let content = std::fs::read_to_string(fname).map_err(|e| invalid(None, format!("cannot read: {}", e)))?;
+
+        content.lines()
+            .enumerate()
+            .flat_map(|(i, line)| line.split(|c: char| c.is_whitespace() || c == ',').map(move |symbol| (i + 1, symbol)))
+            .filter(|(_, symbol)| !symbol.is_empty())
+            .map(|(number, symbol)| symbol.parse::<usize>()
+                .map_err(|_| invalid(Some(number), format!("invalid symbol: {}", symbol))))
+            .collect()
+    }
+
+    /// Read the emphasis curve file, (frequency, gain in dB) sorted by frequency
+    fn read_emphasis_curve(&self) -> Result<Vec<(f64, f64)>, ConfigError> {
+        let fname = self.emphasis_curve.as_ref().unwrap();
+        let invalid = |line, reason| ConfigError::InvalidFile { parameter: "emphasis-curve", file: fname.clone(), line, reason };
+        let content = std::fs::read_to_string(fname).map_err(|e| invalid(None, format!("cannot read: {}", e)))?;
+
+        let mut curve = content.lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(number, line)| {
+                let values : Vec<f64> = line.split(|c: char| c.is_whitespace() || c == ',')
+                    .filter(|value| !value.is_empty())
+                    .map(|value| value.parse::<f64>()
+                        .map_err(|_| invalid(Some(number), format!("invalid value: {}", line))))
+                    .collect::<Result<_, _>>()?;
+
+                match values[..] {
+                    [freq, gain] => Ok((freq, gain)),
+                    _ => Err(invalid(Some(number), format!("expected frequency and gain: {}", line))),
+                }
+            })
+            .collect::<Result<Vec<(f64, f64)>, _>>()?;
+        curve.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        Ok(curve)
+    }
+
+    /// Returns the stimulation frequencies used
+    fn stimulation_freqs(&self) -> Vec<i64> {
+        if self.fsk_data.is_some() {
+            self.fsk_freqs.clone()
+        } else if let Some((f1, f2)) = self.alt_freqs {
+            vec![f1, f2]
+        } else {
+            vec![self.stimfreq]
+        }
+    }
+
+    /// Returns the display name of a channel
+    fn channel_label(&self, channel: u32) -> String {
+        match self.finger_labels.get(channel as usize) {
+            Some(label) => label.clone(),
+            None => format!("Ch{}", channel),
+        }
+    }
+
+    /// Returns the display names of a list of channels
+    fn channel_labels(&self, channels: &[u32]) -> Vec<String> {
+        channels.iter().map(|channel| self.channel_label(*channel)).collect()
+    }
+
+    fn display_config(&self) {
+        println!("Generating Blocked/Interleaved {} output for:", match self.format {
+            OutputFormat::Flac => "FLAC",
+            OutputFormat::WavF32 => "32-bit float WAV",
+        });
+        if let Some(preset) = &self.preset {
+            println!("   Preset                  : {}", preset);
+        }
+        println!("   Channels                : {}", self.channels);
+        println!("   Channel labels          : {}", self.channel_labels(&(0..self.channels).collect::<Vec<u32>>()).join(", "));
+        if self.trigger_channel {
+            println!("   Trigger channel         : {} ({}ms pulse)", self.channels, self.trigger_width);
+        }
+        if let Some(channel) = self.metronome_channel() {
+            println!("   Metronome channel       : {} ({}ms click of {}Hz every {})", channel, self.metronome_click_ms,
+                self.metronome_freq, if self.metronome == Some(MetronomeAt::Cycle) { "cycle" } else { "pauze-cycle period" });
+        }
+        println!("   Sample Rate             : {}Hz", self.samplerate);
+        if let Some(synth_rate) = self.synth_rate {
+            println!("   Synthesis Rate          : {}Hz (resampled)", synth_rate);
+        }
+        match (self.secondsoutput, self.cycles) {
+            (Some(secondsoutput), _) => println!("   Duration                : {}s", secondsoutput),
+            (None, Some(cycles)) => println!("   Duration                : {} cycles ({:.3}s)", cycles,
+                self.render_samples().unwrap() as f64 / self.samplerate as f64),
+            (None, None) => println!("   Duration                : {} stimulations", self.total_bursts.unwrap()),
+        }
+        if let Some(round) = self.round_duration {
+            println!("   Duration rounded        : {} to whole seconds", format!("{:?}", round).to_lowercase());
+        }
+        if let Some(preview) = self.preview {
+            println!("   Preview                 : {}s", preview);
+        }
+        if let Some(fade) = self.session_fade_sec {
+            println!("   Session fade in/out     : {}s", fade);
+        }
+        if !self.staircase.is_empty() {
+            println!("   Staircase               : {:?}% every {} cycles", self.staircase, self.staircase_cycles);
+        }
+        if self.amplitude_scale != AmplitudeScale::Linear {
+            println!("   Amplitude scale         : {}", self.amplitude_scale.name());
+        }
+        println!("");
+        println!("   Stimulation details:");
+        println!("     Stimulation Frequency : {}Hz", self.stimfreq);
+        if let Some(fsk_data) = &self.fsk_data {
+            println!("     FSK Frequencies       : {:?}Hz from {}{}", self.fsk_freqs, fsk_data, if self.fsk_stop { ", once" } else { "" });
+        }
+        if let Some((f1, f2)) = self.alt_freqs {
+            println!("     Alternating between   : {}Hz and {}Hz", f1, f2);
+        }
+        if let Some(beat) = self.beat {
+            println!("     Beat Frequency        : {}Hz", beat);
+        }
+        if self.carrier == Carrier::Noise {
+            println!("     Carrier               : {} noise", format!("{:?}", self.noise_color).to_lowercase());
+        }
+        if let Some(amp_jitter) = self.amp_jitter {
+            println!("     Amplitude Jitter      : {}%", amp_jitter);
+        }
+        if self.energy_normalize {
+            println!("     Energy per channel    : normalized");
+        }
+        if self.continuous_mode {
+            println!("     Baseline level        : {}", self.baseline_level);
+        }
+        if let Some((freq, level)) = self.marker() {
+            println!("     Marker tone           : {}Hz at level {}", freq, level);
+        }
+        if self.random_phase {
+            println!("     Random carrier phase");
+        }
+        if let Some(emphasis_curve) = &self.emphasis_curve {
+            println!("     Emphasis curve        : {}", emphasis_curve);
+        }
+        println!("     Stimulation Duration  : {}ms", self.stimduration_name());
+        if self.has_envelope() {
+            println!("     Envelope (ADSR)       : {}ms/{}ms/{}%/{}ms", self.attack_ms, self.decay_ms, self.sustain_level, self.release_ms);
+            if self.ramp_mode == RampMode::Outside {
+                println!("     Stimulation + ramps   : {}ms", self.longest_burst_duration());
+            }
+        }
+        if let Some(ibi) = self.ibi {
+            println!("     Stimulation Interval  : {}ms", ibi);
+        }
+        match (self.cycleperiod_samples, self.cycleperiod_ramp) {
+            (Some(samples), _) => println!("     Cycle Period          : {} samples", samples),
+            (None, Some((start, end))) => println!("     Cycle Period          : {}ms to {}ms", start, end),
+            (None, None) => println!("     Cycle Period          : {}ms", self.cycle_period()),
+        }
+        if let Some((min, max)) = self.intercycle_gap {
+            println!("     Intercycle gap        : {}ms - {}ms", min, max);
+        }
+        if self.timing_model == TimingModel::Rational {
+            println!("     Timing model          : rational");
+        }
+        if let Some(anchor) = self.grid_anchor {
+            println!("     Grid anchor           : {}ms ({} samples)", anchor, self.anchor_samples());
+        }
+        if self.clamp_jitter_to_slot {
+            println!("     Jitter clamped to     : {}%", self.max_safe_jitter());
+        }
+        if self.overlap {
+            println!("     Overlapping stimulation allowed");
+        }
+        if let Some(chord_size) = self.chord_size {
+            println!("     Chord size            : {}", chord_size);
+        }
+        if !self.channel_delay.is_empty() {
+            println!("     Channel delays        : {:?}ms", self.channel_delay);
+        }
+        if !self.channel_offset.is_empty() {
+            println!("     Channel offsets       : {:?}ms", self.channel_offset);
+        }
+        println!("");
+
+        if !self.active_cycles.is_empty() {
+            println!("   Pauze cycle period      : {}", self.pauzecycleperiod);
+            println!("   Active on cycles        : {:?}", self.active_cycles);
+        } else if let Some(count) = self.random_pauzes {
+            println!("   Pauze cycle period      : {}", self.pauzecycleperiod);
+            println!("   Random pauzes           : {}", count);
+        } else if self.pauzes.is_empty() {
+            println!("   Without pauzes");
+        } else {
+            println!("   Pauze cycle period      : {}", self.pauzecycleperiod);
+            println!("   Pauze on cycles         : {:?}", self.pauzes);
+        }
+        println!("");
+        if let Some(fname) = &self.sequence_file {
+            println!("   Sequence file           : {}", fname);
+            println!();
+        }
+        if self.randomseed.is_none() {
+            println!("   Randomized seed");
+        } else {
+            println!("   Random seed             : {}", self.randomseed.unwrap());
+        }
+        if let Some(phrase) = &self.seed_phrase {
+            println!("   Seed phrase             : {}", phrase);
+        }
+        if self.seed_per_file {
+            println!("   Derived seed            : {}", self.seed());
+        }
+        if self.channel_seeding {
+            println!("   Random stream per channel");
+        }
+        if self.alternate_direction {
+            println!("   Direction alternates per block");
+        }
+        if let Some(rest) = self.channel_rest() {
+            println!("   Minimum channel rest    : {} slots", rest);
+        }
+        if self.rng != RngKind::Chacha8 {
+            println!("   Random generator        : {:?}", self.rng);
+        }
+        if self.rng_warmup > 0 {
+            println!("   Random warm-up          : {} draws", self.rng_warmup);
+        }
+        println!("   Sample path             : {}", self.sample_path().1);
+    }
+
+    /// Number of samples to render, the preview length if requested. None when the output is only
+    /// limited by the number of stimulations
+    fn render_samples(&self) -> Option<i64> {
+        match (self.preview.or(self.secondsoutput), self.cycles) {
+            (None, Some(cycles)) => Some(self.anchor_samples() + self.cycle_start(cycles)),
+            (seconds, _) => seconds.map(|seconds| seconds * self.samplerate),
+        }
+    }
+
+    /// Returns whether the stimulations are shaped by an envelope
+    fn has_envelope(&self) -> bool {
+        self.attack_ms != 0 || self.decay_ms != 0 || self.sustain_level != 100 || self.release_ms != 0
+    }
+
+    /// Duration in ms of the envelope parts that take their time from the stimulation duration
+    fn carved_envelope(&self) -> i64 {
+        match self.ramp_mode {
+            RampMode::Inside => self.attack_ms + self.decay_ms + self.release_ms,
+            RampMode::Outside => self.decay_ms,
+        }
+    }
+
+    /// Duration in ms of the stimulation of channel, a single duration applies to every channel
+    fn stimduration(&self, channel: u32) -> i64 {
+        self.stimduration.get(channel as usize).copied().unwrap_or(self.stimduration[0])
+    }
+
+    /// Stimulation durations for the names, joined by _ when they differ per channel
+    fn stimduration_name(&self) -> String {
+        let durations : Vec<String> = self.stimduration.iter().map(|duration| duration.to_string()).collect();
+        durations.join("_")
+    }
+
+    /// Whether every stimulation duration is between 1ms and a second, which stimfreq_frame needs
+    fn stimdurations_valid(&self) -> bool {
+        self.stimduration.iter().all(|duration| (1..=1000).contains(duration))
+    }
+
+    /// Stimulation frequencies that end on the end of every stimulation are a multiple of this (Hz): f * D / 1000
+    /// is a whole number of periods for every duration D when f is a multiple of 1000 / gcd(1000, D)
+    fn stimfreq_frame(&self) -> i64 {
+        fn gcd(a: i64, b: i64) -> i64 { if b == 0 { a } else { gcd(b, a % b) } }
+
+        self.stimduration.iter().map(|duration| 1000 / gcd(1000, *duration)).fold(1, |lcm, frame| lcm / gcd(lcm, frame) * frame)
+    }
+
+    /// Duration in ms of one stimulation of channel, including the ramps added around it
+    fn burst_duration(&self, channel: u32) -> i64 {
+        match self.ramp_mode {
+            RampMode::Inside => self.stimduration(channel),
+            RampMode::Outside => self.attack_ms + self.stimduration(channel) + self.release_ms,
+        }
+    }
+
+    /// Duration in ms of the longest stimulation of the channels
+    fn longest_burst_duration(&self) -> i64 {
+        (0..self.channels).map(|channel| self.burst_duration(channel)).max().unwrap_or(0)
+    }
+
+    /// Samples of one stimulation of channel, including the ramps
+    fn burst_samples(&self, channel: u32) -> i64 {
+        self.burst_duration(channel) * self.samplerate / 1000
+    }
+
+    /// Attack, decay and release of the envelope of channel in samples, the parts carved from the stimulation are
+    /// scaled down to fit it
+    fn envelope_samples(&self, channel: u32) -> (f64, f64, f64) {
+        let envelope_duration = self.carved_envelope();
+        let stimduration = self.stimduration(channel);
+        let scale = if envelope_duration > stimduration {
+            stimduration as f64 / envelope_duration as f64
+        } else {
+            1.0
+        };
+
+        let samples = |ms: i64| ms as f64 * scale * self.samplerate as f64 / 1000.0;
+        let ramp_samples = |ms: i64| match self.ramp_mode {
+            RampMode::Inside => samples(ms),
+            RampMode::Outside => ms as f64 * self.samplerate as f64 / 1000.0,
+        };
+
+        (ramp_samples(self.attack_ms), samples(self.decay_ms), ramp_samples(self.release_ms))
+    }
+
+    /// Offset in samples of the stimulation start of channel within its slot
+    fn offset_samples(&self, channel: u32) -> i64 {
+        let offset = self.channel_offset.get(channel as usize).copied().unwrap_or(0.0);
+
+        (offset * self.samplerate as f64 / 1000.0).round() as i64
+    }
+
+    /// Silent samples before the first cycle (--grid-anchor)
+    fn anchor_samples(&self) -> i64 {
+        (self.grid_anchor.unwrap_or(0.0).max(0.0) * self.samplerate as f64 / 1000.0).round() as i64
+    }
+
+    /// Estimated size of the output in bytes, assuming a compression ratio of 2 for FLAC
+    fn estimated_bytes(&self) -> u64 {
+        const FLAC_RATIO: u64 = 2;
+
+        match self.format {
+            OutputFormat::Flac => self.estimated_samples() as u64 * self.output_channels() as u64 * 2 / FLAC_RATIO,
+            OutputFormat::WavF32 => self.estimated_samples() as u64 * self.output_channels() as u64 * 4,
+        }
+    }
+
+    /// Frequency and amplitude of the marker tone (--dual-carrier), None without
+    fn marker(&self) -> Option<(i64, f64)> {
+        self.dual_carrier.then_some((self.marker_freq, self.marker_level))
+    }
+
+    /// Amplitude of the baseline tone (--continuous-mode), None without
+    fn baseline(&self) -> Option<f64> {
+        self.continuous_mode.then_some(self.baseline_level)
+    }
+
+    /// Gain of a single channel, a chord shares the amplitude of one stimulation over its channels
+    fn chord_gain(&self) -> f64 {
+        1.0 / self.chord_size.unwrap_or(1) as f64
+    }
+
+    /// Delay in samples of every output channel, shifted so the smallest delay is 0. The trigger channel
+    /// has no delay of its own.
+    fn delay_samples(&self) -> Vec<usize> {
+        let mut delays : Vec<f64> = (0..self.output_channels() as usize)
+            .map(|channel| self.channel_delay.get(channel).copied().unwrap_or(0.0))
+            .collect();
+        let min_delay = delays.iter().copied().fold(0.0, f64::min);
+
+        for delay in delays.iter_mut() {
+            *delay = ((*delay - min_delay) * self.samplerate as f64 / 1000.0).round();
+        }
+
+        delays.iter().map(|delay| *delay as usize).collect()
+    }
+
+    /// Length in samples of an output of samples rounded to whole seconds (--round-duration), at least 1 second
+    fn rounded_samples(&self, samples: i64) -> i64 {
+        let seconds = match self.round_duration {
+            Some(RoundDuration::Up) => (samples + self.samplerate - 1) / self.samplerate,
+            Some(RoundDuration::Down) => samples / self.samplerate,
+            Some(RoundDuration::Nearest) => (samples + self.samplerate / 2) / self.samplerate,
+            None => return samples,
+        };
+
+        seconds.max(1) * self.samplerate
+    }
+
+    /// Estimated number of samples in the output
+    fn estimated_samples(&self) -> i64 {
+        match (self.render_samples(), self.total_bursts) {
+            (Some(samples), _) => samples,
+            (None, Some(total_bursts)) => self.cycles_to_samples(total_bursts) / self.channels as i64,
+            (None, None) => 0,
+        }
+    }
+
+    /// Analytic estimate of the stimulation energy of the whole session in full scale seconds, see --max-energy.
+    /// Every stimulation counts as a sine at its full amplitude over the stimulation including its ramps, the
+    /// envelope, amplitude jitter and emphasis (which mostly lower the energy) are left out
+    fn estimated_energy(&self) -> f64 {
+        let stimulations = match (self.secondsoutput, self.total_bursts, self.cycles) {
+            (Some(secondsoutput), _, _) => {
+                let cycles = (secondsoutput * 1000) as f64 / self.cycle_period() as f64;
+                cycles * self.active_fraction() * self.channels as f64
+            }
+            (None, Some(total_bursts), _) => total_bursts as f64,
+            (None, None, Some(cycles)) => cycles as f64 * self.active_fraction() * self.channels as f64,
+            (None, None, None) => 0.0,
+        };
+
+        // a chord of K channels at 1/K each
+        let chord_size = self.chord_size.unwrap_or(1) as f64;
+        let chord = chord_size * self.chord_gain().powi(2);
+
+        let staircase = if self.staircase.is_empty() {
+            1.0
+        } else {
+            self.staircase.iter().map(|level| self.amplitude_scale.gain(*level as f64 / 100.0).powi(2)).sum::<f64>() / self.staircase.len() as f64
+        };
+
+        // channels take turns, so the average stimulation counts, normalized every channel has the energy of the
+        // shortest
+        let burst_duration = if self.energy_normalize {
+            (0..self.channels).map(|channel| self.burst_duration(channel)).min().unwrap_or(0) as f64
+        } else {
+            (0..self.channels).map(|channel| self.burst_duration(channel)).sum::<i64>() as f64 / self.channels as f64
+        };
+
+        let energy = stimulations * burst_duration / 1000.0 * 0.5 * chord * staircase;
+
+        // the stimulation carrier at 1 - level and the marker at level, sines of different frequencies
+        let energy = match self.marker() {
+            Some((_, level)) => energy * ((1.0 - level).powi(2) + level.powi(2)),
+            None => energy,
+        };
+
+        // the stimulation at 1 - level, the baseline on every channel all the time (their sum is left out)
+        match self.baseline() {
+            Some(level) => {
+                let seconds = self.estimated_samples() as f64 / self.samplerate as f64;
+                energy * (1.0 - level).powi(2) + seconds * self.channels as f64 * 0.5 * (level * self.chord_gain()).powi(2)
+            }
+            None => energy,
+        }
+    }
+
+    /// Fraction of the cycles of a pauze-cycle that are not pauzed
+    fn active_fraction(&self) -> f64 {
+        let cycles = 0..self.pauzecycleperiod;
+        let active = match self.random_pauzes {
+            Some(count) => self.pauzecycleperiod - count,
+            None if !self.active_cycles.is_empty() => cycles.filter(|cycle| self.active_cycles.contains(cycle)).count() as i64,
+            None => cycles.filter(|cycle| !self.pauzes.contains(cycle)).count() as i64,
+        };
+
+        active.max(0) as f64 / self.pauzecycleperiod as f64
+    }
+
+    /// Name of the shuffle algorithm of the channel orders, with its version
+    fn shuffle_algorithm(&self) -> String {
+        if self.deterministic_shuffle {
+            format!("fisher-yates-v{}", rng::SHUFFLE_VERSION)
+        } else {
+            "rand-0.8".to_owned()
+        }
+    }
+
+    /// Returns the seed for the random generator, the supplied one or one drawn from entropy
+    fn seed(&self) -> u64 {
+        let seed = match self.randomseed {
+            Some(seed) => seed as u64,
+            None => self.entropy_seed,
+        };
+
+        if self.seed_per_file {
+            self.derive_seed(seed)
+        } else {
+            seed
+        }
+    }
+
+    /// Seed derived from the master seed and the configuration that shapes the output
+    fn derive_seed(&self, seed: u64) -> u64 {
+        const NOT_OUTPUT: [&str; 16] = ["preview", "play_pcm", "preview_gain", "preview_octave", "downmix", "downmix_map", "name_template",
+            "randomseed", "max_energy", "require_all_channels", "debug_invariants", "dense_schedule", "dense_schedule_downsample", "preset",
+            "seed_phrase", "format"];
+
+        let mut key = seed.to_string();
+        for (name, value) in self.config_fields().iter().filter(|(name, _)| !NOT_OUTPUT.contains(name)) {
+            key.push_str(&format!(";{}={}", name, value));
+        }
+
+        manifest::fnv1a64(key.as_bytes())
+    }
+
+    /// Minimum rest (in slots) of a channel between two of its stimulations, relaxed to what every cycle allows
+    fn channel_rest(&self) -> Option<u32> {
+        self.min_channel_rest.map(|rest| rest.min(self.channels.saturating_sub(1)))
+    }
+
+    /// Largest jitter (in %) for which no stimulation gets cut short. A start moves at most J % of half a slot,
+    /// so two successive starts come closer by at most J % of the slot, which must leave room for the stimulation
+    fn max_safe_jitter(&self) -> i64 {
+        let slot = self.cycles_to_samples(1) / self.channels as i64;
+        let stimulation = self.longest_burst_duration() * self.samplerate / 1000;
+
+        (100 * (slot - stimulation) / slot).max(0)
+    }
+
+    /// Jitter in % used for the schedule, clamped to the safe jitter if requested
+    fn effective_jitter(&self) -> Option<i64> {
+        if self.clamp_jitter_to_slot {
+            self.jitter.map(|jitter| jitter.min(self.max_safe_jitter()))
+        } else {
+            self.jitter
+        }
+    }
+
+    /// Returns the duration of one cycle in ms, derived from the inter-stimulation interval if given. A cycle
+    /// period in samples is rounded to ms, a ramped cycle period gives its shortest cycle
+    fn cycle_period(&self) -> i64 {
+        match (self.cycleperiod_samples, self.cycleperiod_ramp, self.ibi) {
+            (Some(samples), _, _) => (samples as f64 * 1000.0 / self.samplerate as f64).round() as i64,
+            (None, Some((start, end)), _) => start.min(end),
+            (None, None, Some(ibi)) => ibi * self.channels as i64,
+            (None, None, None) => self.cycleperiod,
+        }
+    }
+
+    /// Returns the number of samples in a number of cycles
+    fn cycles_to_samples(&self, cycles: i64) -> i64 {
+        match self.cycleperiod_samples {
+            Some(samples) => cycles * samples,
+            None => cycles * self.cycle_period() * self.samplerate / 1000,
+        }
+    }
+
+    /// Returns the first sample (of the schedule) of cycle, where the slot arithmetic of the scheduler enters it
+    fn cycle_start(&self, cycle: i64) -> i64 {
+        let slots = cycle * self.channels as i64;
+        let mut sample = self.cycles_to_samples(cycle).max(0);
+
+        while sample > 0 && self.elapsed_slots(sample - 1, self.channels as i64) >= slots {
+            sample -= 1;
+        }
+        while self.elapsed_slots(sample, self.channels as i64) < slots {
+            sample += 1;
+        }
+
+        sample
+    }
+
+    /// Returns the number of whole 1/slots_per_cycle parts of a cycle elapsed at sample
+    fn elapsed_slots(&self, sample: i64, slots_per_cycle: i64) -> i64 {
+        match (self.cycleperiod_samples, self.cycleperiod_ramp) {
+            (Some(samples), _) if self.timing_model == TimingModel::Rational => (sample * slots_per_cycle).div_euclid(samples),
+            (Some(samples), _) => sample * slots_per_cycle / samples,
+            (None, Some((start, end))) if start != end => {
+                // the cycles elapsed are the integral of 1 / period over time, with the period linear in time
+                let t = sample as f64 * 1000.0 / self.samplerate as f64;
+                let slope = (end - start) as f64 / (self.secondsoutput.unwrap() * 1000) as f64;
+                let cycles = ((start as f64 + slope * t) / start as f64).ln() / slope;
+
+                (cycles * slots_per_cycle as f64).floor() as i64
+            }
+            _ if self.timing_model == TimingModel::Rational => {
+                let elapsed = sample as i128 * 1_000 * slots_per_cycle as i128;
+                elapsed.div_euclid(self.samplerate as i128 * self.cycle_period() as i128) as i64
+            }
+            _ => sample * 1_000 * slots_per_cycle / self.samplerate / self.cycle_period(),
+        }
+    }
+
+    /// Returns the number of channels in the output file
+    fn output_channels(&self) -> u32 {
+        self.channels + self.trigger_channel as u32 + self.metronome.is_some() as u32
+    }
+
+    /// Returns the index of the metronome channel, if any: the last channel in the file
+    fn metronome_channel(&self) -> Option<u32> {
+        self.metronome.map(|_| self.channels + self.trigger_channel as u32)
+    }
+
+    /// Returns the arguments the schedule is synthesized with, at the synthesis rate when resampling. The seed
+    /// is resolved first, so a per file seed still follows from the output configuration
+    fn synth_args(&self) -> Arguments {
+        match self.synth_rate {
+            Some(synth_rate) => Arguments { samplerate: synth_rate, randomseed: Some(self.seed() as i64), seed_per_file: false, ..self.clone() },
+            None => self.clone(),
+        }
+    }
+
+    /// Device channel of every output channel for the downmix of the auditioning stream, if any
+    fn downmix_map(&self) -> Option<Vec<u32>> {
+        let downmix = self.downmix?;
+
+        if self.downmix_map.is_empty() {
+            Some((0..self.output_channels()).map(|channel| channel % downmix).collect())
+        } else {
+            Some(self.downmix_map.clone())
+        }
+    }
+
+    /// Returns the arguments for the auditioning stream: same schedule, carrier shifted by the preview octaves
+    fn audition_args(&self) -> Arguments {
+        let mut audition = self.clone();
+
+        audition.stimfreq <<= self.preview_octave;
+        audition.beat = self.beat.map(|beat| beat << self.preview_octave);
+        audition.fsk_freqs = self.fsk_freqs.iter().map(|freq| freq << self.preview_octave).collect();
+        audition.alt_freqs = self.alt_freqs.map(|(f1, f2)| (f1 << self.preview_octave, f2 << self.preview_octave));
+        audition.verbosity = 0;
+
+        audition
+    }
+
+    /// Returns whether the sines are computed in batches (the simd feature on a CPU with its instructions, unless
+    /// --force-scalar), with the reason for display
+    fn sample_path(&self) -> (bool, String) {
+        if !cfg!(feature = "simd") {
+            return (false, "scalar (built without the simd feature)".to_owned());
+        }
+        if self.force_scalar {
+            return (false, "scalar (forced)".to_owned());
+        }
+
+        #[cfg(feature = "simd")]
+        if let Some(instructions) = simd::detect() {
+            return (true, format!("batched ({})", instructions));
+        }
+
+        (false, "scalar (CPU without vector instructions)".to_owned())
+    }
+
+    /// Returns the carrier frequencies mixed into a stimulation at stimfreq
+    fn carrier_freqs(&self, stimfreq: i64) -> Vec<i64> {
+        match self.beat {
+            Some(beat) => vec![stimfreq, stimfreq + beat],
+            None => vec![stimfreq],
+        }
+    }
+
+    /// Resolved configuration as JSON object
+    fn to_json(&self) -> String {
+        let items : Vec<String> = self.config_fields().iter()
+            .map(|(name, value)| format!("\"{}\": {}", name, value))
+            .collect();
+
+        format!("{{ {} }}", items.join(", "))
+    }
+
+    /// Resolved configuration, as JSON values by name
+    fn config_fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("channels", self.channels.to_string()),
+            ("samplerate", self.samplerate.to_string()),
+            ("synth_rate", manifest::option(&self.synth_rate)),
+            ("stimfreq", self.stimfreq.to_string()),
+            ("beat", manifest::option(&self.beat)),
+            ("carrier", manifest::string(&format!("{:?}", self.carrier).to_lowercase())),
+            ("noise_color", manifest::string(&format!("{:?}", self.noise_color).to_lowercase())),
+            ("fsk_data", manifest::option_string(&self.fsk_data)),
+            ("fsk_freqs", manifest::numbers(&self.fsk_freqs)),
+            ("fsk_stop", self.fsk_stop.to_string()),
+            ("alt_freqs", self.alt_freqs.map_or("null".to_owned(), |(f1, f2)| manifest::numbers(&[f1, f2]))),
+            ("amp_jitter", manifest::option(&self.amp_jitter)),
+            ("energy_normalize", self.energy_normalize.to_string()),
+            ("baseline_level", manifest::option(&self.baseline())),
+            ("marker_freq", manifest::option(&self.marker().map(|(freq, _)| freq))),
+            ("marker_level", manifest::option(&self.marker().map(|(_, level)| level))),
+            ("random_phase", self.random_phase.to_string()),
+            ("emphasis_curve", manifest::option_string(&self.emphasis_curve)),
+            // a single duration stays a number, so the derived seeds don't change
+            ("stimduration", if self.stimduration.len() == 1 { self.stimduration[0].to_string() } else { manifest::numbers(&self.stimduration) }),
+            ("attack_ms", self.attack_ms.to_string()),
+            ("decay_ms", self.decay_ms.to_string()),
+            ("sustain_level", self.sustain_level.to_string()),
+            ("release_ms", self.release_ms.to_string()),
+            ("ramp_mode", manifest::string(&format!("{:?}", self.ramp_mode).to_lowercase())),
+            ("snap_stimfreq", self.snap_stimfreq.to_string()),
+            ("overlap", self.overlap.to_string()),
+            ("cycleperiod", self.cycle_period().to_string()),
+            ("cycleperiod_samples", manifest::option(&self.cycleperiod_samples)),
+            ("cycleperiod_ramp", self.cycleperiod_ramp.map_or("null".to_owned(), |(start, end)| manifest::numbers(&[start, end]))),
+            ("intercycle_gap", self.intercycle_gap.map_or("null".to_owned(), |(min, max)| manifest::numbers(&[min, max]))),
+            ("grid_anchor", manifest::option(&self.grid_anchor)),
+            ("timing_model", manifest::string(&format!("{:?}", self.timing_model).to_lowercase())),
+            ("ibi", manifest::option(&self.ibi)),
+            ("jitter", manifest::option(&self.jitter)),
+            ("clamp_jitter_to_slot", self.clamp_jitter_to_slot.to_string()),
+            ("pauzecycleperiod", self.pauzecycleperiod.to_string()),
+            ("pauzes", manifest::numbers(&self.pauzes)),
+            ("active_cycles", manifest::numbers(&self.active_cycles)),
+            ("random_pauzes", manifest::option(&self.random_pauzes)),
+            ("secondsoutput", manifest::option(&self.secondsoutput)),
+            ("total_bursts", manifest::option(&self.total_bursts)),
+            ("cycles", manifest::option(&self.cycles)),
+            ("round_duration", manifest::option_string(&self.round_duration.map(|round| format!("{:?}", round).to_lowercase()))),
+            ("session_fade_sec", manifest::option(&self.session_fade_sec)),
+            ("max_energy", manifest::option(&self.max_energy)),
+            ("require_all_channels", manifest::option(&self.require_all_channels)),
+            ("debug_invariants", self.debug_invariants.to_string()),
+            ("staircase", manifest::numbers(&self.staircase)),
+            ("staircase_cycles", self.staircase_cycles.to_string()),
+            ("amplitude_scale", manifest::string(&self.amplitude_scale.name())),
+            ("preset", manifest::option_string(&self.preset)),
+            ("preview", manifest::option(&self.preview)),
+            ("trim_leading_silence", self.trim_leading_silence.to_string()),
+            ("format", manifest::string(&format!("{:?}", self.format).to_lowercase())),
+            ("markers", self.markers.map_or("null".to_owned(), |at| manifest::string(&format!("{:?}", at).to_lowercase()))),
+            ("dense_schedule", manifest::option_string(&self.dense_schedule)),
+            ("dense_schedule_downsample", self.dense_schedule_downsample.to_string()),
+            ("randomseed", manifest::option(&self.randomseed)),
+            ("seed_phrase", manifest::option_string(&self.seed_phrase)),
+            ("deterministic_shuffle", self.deterministic_shuffle.to_string()),
+            ("norandom", self.norandom.to_string()),
+            ("alternate_direction", self.alternate_direction.to_string()),
+            ("min_channel_rest", manifest::option(&self.min_channel_rest)),
+            ("channel_seeding", self.channel_seeding.to_string()),
+            ("rng", manifest::string(&format!("{:?}", self.rng).to_lowercase())),
+            ("rng_warmup", self.rng_warmup.to_string()),
+            ("seed_per_file", self.seed_per_file.to_string()),
+            ("sequence_file", manifest::option_string(&self.sequence_file)),
+            ("chord_size", manifest::option(&self.chord_size)),
+            ("finger_labels", manifest::strings(&self.finger_labels)),
+            ("channel_delay", manifest::numbers(&self.channel_delay)),
+            ("channel_offset", manifest::numbers(&self.channel_offset)),
+            ("trigger_channel", self.trigger_channel.to_string()),
+            ("trigger_width", self.trigger_width.to_string()),
+            ("metronome", self.metronome.map_or("null".to_owned(), |at| manifest::string(&format!("{:?}", at).to_lowercase()))),
+            ("metronome_click_ms", self.metronome_click_ms.to_string()),
+            ("metronome_freq", self.metronome_freq.to_string()),
+            ("play_pcm", manifest::option_string(&self.play_pcm)),
+            ("preview_gain", self.preview_gain.to_string()),
+            ("preview_octave", self.preview_octave.to_string()),
+            ("downmix", manifest::option(&self.downmix)),
+            ("downmix_map", manifest::numbers(&self.downmix_map)),
+            ("name_template", manifest::string(&self.name_template)),
+        ]
+    }
+
+    /// Set filename from the name template, a preview gets a PREVIEW marker before the extension
+    fn construct_fname(&self) -> String {
+        let mut result = self.name_template.clone();
+
+        for (placeholder, value) in self.placeholders() {
+            result = result.replace(&format!("{{{}}}", placeholder), &value);
+        }
+
+        if self.format == OutputFormat::WavF32 {
+            if let Some(stem) = result.strip_suffix(".flac") {
+                result = format!("{}.wav", stem);
+            }
+        }
+
+        if let Some(preview) = self.preview {
+            let marker = format!("-PREVIEW{}s", preview);
+            let basename_start = result.rfind('/').map(|i| i + 1).unwrap_or(0);
+
+            match result[basename_start..].rfind('.') {
+                Some(i) => result.insert_str(basename_start + i, &marker),
+                None => result.push_str(&marker),
+            }
+        }
+
+        result
+    }
+
+    /// Duration of the output for in the filename
+    fn duration_name(&self) -> String {
+        let duration = match (self.secondsoutput, self.cycles) {
+            (Some(secondsoutput), _) => format!("{}s", secondsoutput),
+            (None, Some(cycles)) => format!("{}CYCLES", cycles),
+            (None, None) => format!("{}STIMS", self.total_bursts.unwrap()),
+        };
+
+        match self.round_duration {
+            Some(round) => format!("{}-ROUND{}", duration, format!("{:?}", round).to_uppercase()),
+            None => duration,
+        }
+    }
+
+    /// Values for the placeholders of the name template
+    fn placeholders(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("default", self.default_name()),
+            ("mode", "Interleaved".to_owned()),
+            ("stimfreq", self.stimfreq.to_string()),
+            ("stimduration", self.stimduration_name()),
+            ("cycleperiod", self.cycle_period().to_string()),
+            ("channels", self.channels.to_string()),
+            ("samplerate", self.samplerate.to_string()),
+            ("duration", self.duration_name()),
+            ("seed", self.seed().to_string()),
+            ("date", today()),
+        ]
+    }
+
+    /// Default name with all parameters included
+    fn default_name(&self) -> String {
+        let mut result: String = match self.carrier {
+            Carrier::Sine => "Sine-Interleaved--".to_owned(),
+            Carrier::Noise => format!("{:?}Noise-Interleaved--", self.noise_color),
+        };
+
+        result.push_str(&self.stimfreq.to_string());    result.push_str("SFREQ-");
+        if let Some(beat) = self.beat {
+            result.push_str(&beat.to_string());         result.push_str("BEAT-");
+        }
+        if !self.fsk_freqs.is_empty() {
+            let freqs : Vec<String> = self.fsk_freqs.iter().map(|freq| freq.to_string()).collect();
+            result.push_str(&freqs.join("_"));
+            result.push_str("FSK-");
+        }
+        if let Some((f1, f2)) = self.alt_freqs {
+            result.push_str(&format!("{}_{}ALT-", f1, f2));
+        }
+        if let Some(amp_jitter) = self.amp_jitter {
+            result.push_str(&amp_jitter.to_string());   result.push_str("AJIT-");
+        }
+        if self.energy_normalize {
+            result.push_str("ENORM-");
+        }
+        if let Some(level) = self.baseline() {
+            result.push_str(&format!("CONT{}-", level));
+        }
+        if let Some((freq, level)) = self.marker() {
+            result.push_str(&format!("DUAL{}x{}-", freq, level));
+        }
+        if self.random_phase {
+            result.push_str("RPH-");
+        }
+        if self.emphasis_curve.is_some() {
+            result.push_str("EMPH-");
+        }
+        result.push_str(&self.stimduration_name());  result.push_str("SPER-");
+        if self.has_envelope() {
+            result.push_str(&format!("{}_{}_{}_{}ADSR-", self.attack_ms, self.decay_ms, self.sustain_level, self.release_ms));
+            if self.ramp_mode == RampMode::Outside {
+                result.push_str("RAMPOUT-");
+            }
+        }
+        match (self.cycleperiod_samples, self.cycleperiod_ramp, self.ibi) {
+            (Some(samples), _, _) => { result.push_str(&samples.to_string()); result.push_str("CPERSMP-"); }
+            (None, Some((start, end)), _) => result.push_str(&format!("{}_{}CPERRAMP-", start, end)),
+            (None, None, Some(ibi)) => { result.push_str(&ibi.to_string()); result.push_str("IBI-"); }
+            (None, None, None) => { result.push_str(&self.cycleperiod.to_string()); result.push_str("CPER-"); }
+        }
+        if let Some((min, max)) = self.intercycle_gap {
+            result.push_str(&format!("{}_{}GAP-", min, max));
+        }
+        if let Some(anchor) = self.grid_anchor {
+            result.push_str(&anchor.to_string());   result.push_str("ANCH-");
+        }
+        if self.timing_model == TimingModel::Rational {
+            result.push_str("RAT-");
+        }
+        if self.overlap {
+            result.push_str("OVL-");
+        }
+        if let Some(chord_size) = self.chord_size {
+            result.push_str(&chord_size.to_string());   result.push_str("CHORD-");
+        }
+
+        if !self.pauzes.is_empty() {
+            let mut first : bool = true;
+
+            for pauze in self.pauzes.iter() {
+                if first {
+                    first = false;
+                } else {
+                    result.push_str("_");
+                }
+
+                result.push_str(&pauze.to_string()); 
+            }
+            result.push_str("P");
+            result.push_str(&self.pauzecycleperiod.to_string());
+            result.push_str("--");
+        }
+
+        if !self.active_cycles.is_empty() {
+            let cycles : Vec<String> = self.active_cycles.iter().map(|cycle| cycle.to_string()).collect();
+            result.push_str(&cycles.join("_"));
+            result.push('A');
+            result.push_str(&self.pauzecycleperiod.to_string());
+            result.push_str("--");
+        }
+
+        if let Some(count) = self.random_pauzes {
+            result.push_str(&count.to_string());
+            result.push_str("RP");
+            result.push_str(&self.pauzecycleperiod.to_string());
+            result.push_str("--");
+        }
+
+        if !self.randomseed.is_none() {
+            result.push_str(&self.randomseed.unwrap().to_string());
+            result.push_str("RSEED--");
+        }
+
+        if self.seed_per_file {
+            result.push_str(&self.seed().to_string());
+            result.push_str("DSEED--");
+        }
+
+        if self.channel_seeding {
+            result.push_str("CSEED--");
+        }
+
+        if self.alternate_direction {
+            result.push_str("ALTDIR--");
+        }
+
+        if let Some(rest) = self.channel_rest() {
+            result.push_str(&rest.to_string());
+            result.push_str("REST--");
+        }
+
+        if self.rng != RngKind::Chacha8 {
+            result.push_str(&format!("{:?}", self.rng).to_uppercase());
+            result.push_str("--");
+        }
+
+        if self.rng_warmup > 0 {
+            result.push_str(&self.rng_warmup.to_string());
+            result.push_str("WARM--");
+        }
+
+        if let Some(fname) = &self.sequence_file {
+            let stem = std::path::Path::new(fname).file_stem().unwrap().to_string_lossy();
+            result.push_str(&stem);
+            result.push_str("SEQ--");
+        }
+
+        if !self.channel_delay.is_empty() {
+            result.push_str("CDLY-");
+        }
+        if !self.channel_offset.is_empty() {
+            let offsets : Vec<String> = self.channel_offset.iter().map(|offset| offset.to_string()).collect();
+            result.push_str(&offsets.join("_"));
+            result.push_str("COFS-");
+        }
+        result.push_str(&self.channels.to_string());      result.push_str("out-");
+        if self.trigger_channel {
+            result.push_str("TRIG-");
+        }
+        match self.metronome {
+            Some(MetronomeAt::Cycle) => result.push_str("METR-"),
+            Some(MetronomeAt::PauzePeriod) => result.push_str("METRPP-"),
+            None => {}
+        }
+        result.push_str(&self.samplerate.to_string());    result.push_str("Hz-");
+        if let Some(synth_rate) = self.synth_rate {
+            result.push_str(&synth_rate.to_string());     result.push_str("SYNTH-");
+        }
+        result.push_str(&self.duration_name());
+        if let Some(fade) = self.session_fade_sec {
+            result.push('-');
+            result.push_str(&fade.to_string());
+            result.push_str("SFADE");
+        }
+        if !self.staircase.is_empty() {
+            let levels : Vec<String> = self.staircase.iter().map(|level| level.to_string()).collect();
+            result.push('-');
+            result.push_str(&levels.join("_"));
+            result.push_str("STAIR");
+            result.push_str(&self.staircase_cycles.to_string());
+        }
+        match self.amplitude_scale {
+            AmplitudeScale::Linear => {},
+            AmplitudeScale::Log => result.push_str("-LOGSCALE"),
+            AmplitudeScale::Power(exp) => {
+                result.push_str("-POW");
+                result.push_str(&exp.to_string());
+                result.push_str("SCALE");
+            },
+        }
+        if self.trim_leading_silence {
+            result.push_str("-LTRIM");
+        }
+
+        result
+    }
+}
+
+/// Writer of the verbose diagnostics of the generator (-v -v and up), standard output unless discarded
+struct Diagnostics(Box<dyn Write>);
+
+impl Diagnostics {
+    fn stdout() -> Diagnostics {
+        Diagnostics(Box::new(std::io::stdout()))
+    }
+
+    /// Diagnostics that are written nowhere, to run the verbose schedule without its output
+    fn discarded() -> Diagnostics {
+        Diagnostics(Box::new(std::io::sink()))
+    }
+
+    /// Write a line, like println! ignoring a failed write
+    fn line(&mut self, line: std::fmt::Arguments) {
+        let _ = writeln!(self.0, "{}", line);
+    }
+}
+
+impl std::fmt::Debug for Diagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("Diagnostics")
+    }
+}
+
+#[derive(Debug)]
+struct SampleGenerator {
+    rng: GenRng,
+    channel_rngs: Vec<GenRng>,
+    sample: i64,
+    cycle: i64,
+    cyclestart: i64,
+    channelorder : Vec<u32>,
+    jdelay: Vec<i64>,
+    amplitude: Vec<f64>,
+    /// Gain of every channel for equal energy per stimulation (--energy-normalize), 1.0 without
+    channel_gains: Vec<f64>,
+    phase: Vec<f64>,
+    sequence: Vec<Vec<u32>>,
+    seqstep: usize,
+    chords: Vec<Vec<u32>>,
+    /// Channel orders generated so far, the odd ones are reversed with --alternate-direction
+    blocks: u64,
+    /// Slot (counted over all blocks) of the last stimulation of every channel in the channel orders
+    last_slots: Vec<i64>,
+    bursts: Vec<Vec<(i64, i64, f64)>>,
+    fsk: Vec<usize>,
+    stimfreq: i64,
+    alternations: Vec<i64>,
+    noise: Vec<NoiseGen>,
+    emphasis: Vec<(f64, f64)>,
+    stimulations: i64,
+    pauzes: Vec<i64>,
+    pauzeperiod: i64,
+    pauzed: bool,
+    clickstart: Option<i64>,
+    diagnostics: Diagnostics,
+    /// Sines computed in batches, see `Arguments::sample_path`
+    #[cfg(feature = "simd")]
+    batched: bool,
+}
+
+/// Transition in the generated output, reported by `SampleGenerator::next_sample`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Event {
+    /// A new cycle (stimulation slot) starts, with its index
+    CycleStart(i64),
+    /// A new channel order was generated
+    NewPattern,
+    /// The output goes silent for a pauze
+    PauzeStart,
+    /// The pauze ends
+    PauzeEnd,
+}
+
+impl SampleGenerator {
+
+    /// Constructor from cmdline args, writing the verbose diagnostics to diagnostics. Fails on an invalid input
+    /// file
+    fn new(args: &Arguments, diagnostics: Diagnostics) -> Result<SampleGenerator, ConfigError> {
+        let mut rng = GenRng::new(args.rng, args.seed(), 0);
+        rng.warm_up(args.rng_warmup);
+
+        // independent stream per channel, so a channel's draws don't depend on the number of channels
+        let mut channel_rngs = Vec::new();
+        if args.channel_seeding {
+            for channel in 0..args.channels {
+                let mut channel_rng = GenRng::new(args.rng, args.seed(), channel as u64 + 1);
+                channel_rng.warm_up(args.rng_warmup);
+                channel_rngs.push(channel_rng);
+            }
+        }
+        
+        let channelorder : Vec<u32> = (0..args.channels).collect();
+        
+        let jdelay = vec![0;args.channels as usize];
+
+        let mut fsk = Vec::new();
+        if args.fsk_data.is_some() {
+            fsk = args.read_fsk_data()?;
+        }
+
+        let mut emphasis = Vec::new();
+        if args.emphasis_curve.is_some() {
+            emphasis = args.read_emphasis_curve()?;
+        }
+
+        let mut sequence = Vec::new();
+        if args.sequence_file.is_some() {
+            sequence = args.read_sequence()?.into_iter().map(|(_, step)| step).collect();
+        }
+
+        let mut noise = Vec::new();
+        if args.carrier == Carrier::Noise {
+            noise = (0..args.channels).map(|channel| NoiseGen::new(args.rng, args.seed(), channel, args.noise_color)).collect();
+        }
+
+        let mut sg = SampleGenerator {
+            rng, 
+            channel_rngs,
+            sample: 0, 
+            cycle: 0, 
+            cyclestart: 0,
+            channelorder,
+            jdelay,
+            amplitude: vec![1.0; args.channels as usize],
+            channel_gains: vec![1.0; args.channels as usize],
+            phase: vec![0.0; args.channels as usize],
+            sequence,
+            seqstep: 0,
+            chords: Vec::new(),
+            blocks: 0,
+            last_slots: vec![i64::MIN / 2; args.channels as usize],
+            bursts: vec![Vec::new(); args.channels as usize],
+            fsk,
+            stimfreq: args.stimfreq,
+            alternations: vec![0; args.channels as usize],
+            noise,
+            emphasis,
+            stimulations: 0,
+            pauzes: Vec::new(),
+            pauzeperiod: 0,
+            pauzed: false,
+            clickstart: args.metronome.map(|_| 0),
+            diagnostics,
+            #[cfg(feature = "simd")]
+            batched: args.sample_path().0,
+        };
+
+        if args.energy_normalize {
+            sg.channel_gains = sg.energy_gains(args);
+            sg.amplitude = sg.channel_gains.clone();
+        }
+
+        Ok(sg)
+    }
+
+    /// Energy of one stimulation of channel at full amplitude, at the stimulation frequency (a noise carrier counts
+    /// by its envelope)
+    fn burst_energy(&self, args: &Arguments, channel: u32) -> f64 {
+        let gain = self.emphasis_gain(args.stimfreq);
+
+        (0..args.burst_samples(channel))
+            .map(|rel_sample| match args.carrier {
+                Carrier::Sine => burst_sample(args, channel, rel_sample, args.stimfreq, 0.0, gain),
+                Carrier::Noise => envelope(args, channel, rel_sample) * gain,
+            })
+            .map(|sample| sample * sample)
+            .sum::<f64>() / args.samplerate as f64
+    }
+
+    /// Gain of every channel that gives it the energy per stimulation of the channel with the least, so no channel
+    /// gets louder
+    fn energy_gains(&self, args: &Arguments) -> Vec<f64> {
+        let energies : Vec<f64> = (0..args.channels).map(|channel| self.burst_energy(args, channel)).collect();
+        let least = energies.iter().copied().fold(f64::INFINITY, f64::min);
+
+        energies.iter().map(|energy| if *energy > 0.0 { (least / energy).sqrt() } else { 1.0 }).collect()
+    }
+
+    /// Generates new random pattern for each hand 
+    fn gen_channelorder(&mut self, args: &Arguments) {
+        let mut channelorder : Vec<u32> = (0..args.channels).collect();
+        
+        if !self.sequence.is_empty() {
+            // the sequence file dictates the active channels
+        } else if args.alternate_direction && self.blocks % 2 == 1 {
+            // the previous block backwards
+            channelorder = self.channelorder.iter().rev().copied().collect();
+        } else if args.norandom {
+            // play channels in order
+        } else if args.channel_seeding {
+            // order by a random key per channel, each from the channel's own stream
+            let keys : Vec<u64> = self.channel_rngs.iter_mut().map(|rng| rng.gen()).collect();
+            channelorder.sort_by_key(|channel| keys[*channel as usize]);
+
+            // avoid triggering same channel twice, without drawing again
+            if channelorder[0] == *self.channelorder.last().unwrap() && channelorder.len() > 1 {
+                channelorder.swap(0, 1);
+            }
+        } else if let Some(rest) = args.channel_rest() {
+            channelorder = self.rested_channelorder(args, rest);
+        } else {
+            // avoid triggering same channel twice 
+            loop {
+                self.shuffle(args, &mut channelorder);
+
+                if channelorder[0] != *self.channelorder.last().unwrap() {
+                    break;
+                }
+            }
+        }
+        let first_slot = self.blocks as i64 * args.channels as i64;
+        for (position, channel) in channelorder.iter().enumerate() {
+            self.last_slots[*channel as usize] = first_slot + position as i64;
+        }
+        self.channelorder = channelorder;
+        self.blocks += 1;
+
+        if let Some(chord_size) = args.chord_size {
+            self.gen_chords(args, chord_size);
+        }
+    
+        if !args.jitter.is_none() {
+            // 2 * => ] s0 - J * cycleperiod / 8 , s0 + J * cycleperiod / 8 [
+            //let jitter_max_samples = 2 * args.jitter.unwrap() * args.cycleperiod * args.samplerate / 1000 / 8 / 100;
+            let jitter_max_samples = (args.cycles_to_samples(2 * args.effective_jitter().unwrap()) / (2 * args.channels as i64) / 100).max(1);
+            
+            // no jitter on first channel
+            for c in 1..args.channels as usize {
+                if args.norandom {
+                    // fixed pattern: the channels spread evenly over the jitter range
+                    self.jdelay[c] = c as i64 * jitter_max_samples / args.channels as i64 - jitter_max_samples / 2;
+                } else {
+                    let rng = if args.channel_seeding { &mut self.channel_rngs[c] } else { &mut self.rng };
+                    self.jdelay[c] = rng.gen_range(0..jitter_max_samples) - jitter_max_samples / 2;
+                }
+            }
+        }
+
+        if let Some(amp_jitter) = args.amp_jitter {
+            // [1 - A, 1 + A], but never above full scale
+            let spread = amp_jitter as f64 / 100.0;
+
+            for c in 0..args.channels as usize {
+                let rng = if args.channel_seeding { &mut self.channel_rngs[c] } else { &mut self.rng };
+                self.amplitude[c] = rng.gen_range(1.0 - spread..=1.0 + spread).min(1.0) * self.channel_gains[c];
+            }
+
+            if args.verbosity > 1 {
+                self.diagnostics.line(format_args!(" * New Amplitudes: {:?}", self.amplitude));
+            }
+        }
+
+        if args.random_phase {
+            for c in 0..args.channels as usize {
+                let rng = if args.channel_seeding { &mut self.channel_rngs[c] } else { &mut self.rng };
+                self.phase[c] = rng.gen_range(0.0..TAU);
+            }
+        }
+         
+        if args.verbosity > 1 {
+            if args.jitter.is_none() {
+                self.diagnostics.line(format_args!(" * New Channel Order: {:?}", args.channel_labels(&self.channelorder)));
+            } else {
+                self.diagnostics.line(format_args!(" * New Channel Order: {:?} - Jitter in samples: {:?}", 
+                    args.channel_labels(&self.channelorder), 
+                    self.jdelay));
+            }
+        }
+
+    }
+
+    /// Random channel order in which every channel rests at least rest slots after its last stimulation: every
+    /// position draws from the channels that rested enough. Only the channels at the end of the previous order
+    /// have to wait, with a rest below the channels one of them is always free
+    fn rested_channelorder(&mut self, args: &Arguments, rest: u32) -> Vec<u32> {
+        let first_slot = self.blocks as i64 * args.channels as i64;
+        let mut channels : Vec<u32> = (0..args.channels).collect();
+        let mut channelorder = Vec::with_capacity(channels.len());
+
+        for position in 0..args.channels as i64 {
+            let rested : Vec<usize> = (0..channels.len())
+                .filter(|i| first_slot + position - self.last_slots[channels[*i] as usize] > rest as i64)
+                .collect();
+            let i = rested[self.rng.gen_range(0..rested.len())];
+            channelorder.push(channels.remove(i));
+        }
+
+        channelorder
+    }
+
+    /// Shuffle channels with the main generator, by the selected algorithm
+    fn shuffle(&mut self, args: &Arguments, channels: &mut [u32]) {
+        if args.deterministic_shuffle {
+            rng::shuffle(channels, &mut self.rng);
+        } else {
+            channels.shuffle(&mut self.rng);
+        }
+    }
+
+    /// Generates a chord of channels for each cycle (chord mode)
+    fn gen_chords(&mut self, args: &Arguments, chord_size: u32) {
+        let mut chords : Vec<Vec<u32>> = Vec::new();
+
+        for cycle in 0..args.channels {
+            let mut chord : Vec<u32>;
+
+            if args.norandom {
+                // consecutive channels, shifting one channel per cycle
+                chord = (cycle..cycle + chord_size).map(|channel| channel % args.channels).collect();
+            } else {
+                let previous = chords.last().or(self.chords.last()).cloned();
+                let mut channels : Vec<u32> = (0..args.channels).collect();
+
+                // avoid triggering the same chord twice, unless it is the only one
+                loop {
+                    self.shuffle(args, &mut channels);
+                    chord = channels[..chord_size as usize].to_vec();
+                    chord.sort();
+
+                    if chord_size == args.channels || previous.as_ref() != Some(&chord) {
+                        break;
+                    }
+                }
+            }
+
+            chords.push(chord);
+        }
+
+        self.chords = chords;
+
+        if args.verbosity > 1 {
+            let labels : Vec<Vec<String>> = self.chords.iter().map(|chord| args.channel_labels(chord)).collect();
+            self.diagnostics.line(format_args!(" * New Chords: {:?}", labels));
+        }
+    }
+
+    /// Choose the randomly pauzed cycles of the current pauze-cycle
+    fn gen_pauzes(&mut self, args: &Arguments, count: i64) {
+        let mut pauzes : Vec<i64> = rand::seq::index::sample(&mut self.rng, args.pauzecycleperiod as usize, count as usize)
+            .iter()
+            .map(|cycle| cycle as i64)
+            .collect();
+        pauzes.sort();
+
+        self.pauzes = pauzes;
+
+        if args.verbosity > 1 {
+            self.diagnostics.line(format_args!(" * New Pauzes: {:?}", self.pauzes));
+        }
+    }
+
+    /// Advance to the next sample, returns the transitions at the new sample
+    fn next_sample(&mut self, args: &Arguments) -> Vec<Event> {
+        let mut events = Vec::new();
+        let (prev_sample, prev_cyclestart) = (self.sample, self.cyclestart);
+
+        self.sample += 1;
+
+        if let Some(count) = args.random_pauzes {
+            let pauzeperiod = args.elapsed_slots(self.sample, 1) / args.pauzecycleperiod;
+
+            if pauzeperiod != self.pauzeperiod {
+                self.pauzeperiod = pauzeperiod;
+                self.gen_pauzes(args, count);
+            }
+        }
+        
+        if self.curr_cycle(args) < self.cycle {
+            // we went back to cycle 0:
+            //   - regen random pattern
+            self.gen_channelorder(&args);
+            events.push(Event::NewPattern);
+        }
+        
+        let cycle_changed = self.curr_cycle(args) != self.cycle;
+
+        if cycle_changed {
+            self.cyclestart = self.sample;
+            self.seqstep += 1;
+            events.push(Event::CycleStart(self.curr_cycle(args)));
+        }
+        
+        self.cycle = self.curr_cycle(args);
+
+        if cycle_changed && self.cycle == 0 && self.at_metronome_beat(args) {
+            self.clickstart = Some(self.sample);
+        }
+
+        let pauzed = self.in_pauze(args);
+        if pauzed != self.pauzed {
+            self.pauzed = pauzed;
+            events.push(if pauzed { Event::PauzeStart } else { Event::PauzeEnd });
+        }
+
+        if cycle_changed {
+            self.count_stimulation(args);
+        }
+
+        if args.overlap && cycle_changed {
+            self.start_bursts(args);
+        }
+
+        if args.debug_invariants {
+            self.check_invariants(args, prev_sample, prev_cyclestart);
+        }
+
+        events
+    }
+
+    /// Panic with the sample and state when the schedule breaks an invariant (--debug-invariants)
+    fn check_invariants(&self, args: &Arguments, prev_sample: i64, prev_cyclestart: i64) {
+        let broken = if self.sample <= prev_sample {
+            Some(format!("sample went from {} to {}", prev_sample, self.sample))
+        } else if self.cyclestart < prev_cyclestart {
+            Some(format!("cycle start went back from {} to {}", prev_cyclestart, self.cyclestart))
+        } else if !(0..i64::from(args.channels)).contains(&self.cycle) {
+            Some(format!("cycle {} out of range 0..{}", self.cycle, args.channels))
+        } else if self.channelorder.len() != args.channels as usize || self.channelorder.iter().any(|channel| *channel >= args.channels) {
+            Some(format!("channel order {:?} out of range 0..{}", self.channelorder, args.channels))
+        } else if self.chords.get(self.cycle as usize).is_some_and(|chord| chord.iter().any(|channel| *channel >= args.channels)) {
+            Some(format!("chord {:?} out of range 0..{}", self.chords[self.cycle as usize], args.channels))
+        } else {
+            None
+        };
+
+        if let Some(invariant) = broken {
+            panic!("!!!ERROR: Invariant broken at sample {}: {} (cycle {}, cycle start {}, channel order {:?}, jitter delays {:?})",
+                self.sample, invariant, self.cycle, self.cyclestart, self.channelorder, self.jdelay);
+        }
+    }
+
+    /// Draw the silent gap in samples before the cycle starting now, 0 without --intercycle-gap
+    fn intercycle_gap(&mut self, args: &Arguments) -> i64 {
+        let Some((min, max)) = args.intercycle_gap else {
+            return 0;
+        };
+
+        let gap = self.rng.gen_range(min * args.samplerate / 1000..=max * args.samplerate / 1000);
+
+        if args.verbosity > 1 {
+            self.diagnostics.line(format_args!(" * Gap of {} samples before the cycle at {}", gap, self.sample));
+        }
+
+        gap
+    }
+
+    /// Report a transition of the generator
+    fn report(&mut self, args: &Arguments, event: Event) {
+        if args.verbosity > 2 {
+            match event {
+                Event::CycleStart(cycle) => self.diagnostics.line(format_args!(" Cycle #{} at {}", cycle, self.sample)),
+                Event::NewPattern => self.diagnostics.line(format_args!(" New pattern at {}", self.sample)),
+                Event::PauzeStart => self.diagnostics.line(format_args!(" Pauze from {}", self.sample)),
+                Event::PauzeEnd => self.diagnostics.line(format_args!(" Pauze until {}", self.sample)),
+            }
+        }
+    }
+
+    /// Count the stimulation starting at the current cycle, unless pauzed. With FSK the stimulation takes the
+    /// frequency of the next symbol
+    fn count_stimulation(&mut self, args: &Arguments) {
+        if !self.in_pauze(args) {
+            self.stimulations += 1;
+
+            if !self.fsk.is_empty() {
+                let symbol = self.fsk[(self.stimulations - 1) as usize % self.fsk.len()];
+                self.stimfreq = args.fsk_freqs[symbol];
+            }
+
+            if args.alt_freqs.is_some() {
+                let active : Vec<u32> = (0..args.channels).filter(|channel| self.is_active(*channel)).collect();
+
+                for channel in active {
+                    self.alternations[channel as usize] += 1;
+
+                    if args.verbosity > 2 {
+                        let freq = self.channel_freq(args, channel);
+                        self.diagnostics.line(format_args!(" Channel {} at {}Hz", args.channel_label(channel), freq));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the stimulation frequency of channel: with --alt-freqs F1 on its odd and F2 on its even
+    /// stimulations, else the current stimulation frequency
+    fn channel_freq(&self, args: &Arguments, channel: u32) -> i64 {
+        match args.alt_freqs {
+            Some((f1, f2)) => if self.alternations[channel as usize] % 2 == 1 { f1 } else { f2 },
+            None => self.stimfreq,
+        }
+    }
+
+    /// Returns the number of stimulations after which the output ends, if limited
+    fn stimulation_limit(&self, args: &Arguments) -> Option<i64> {
+        let fsk_limit = if args.fsk_stop { Some(self.fsk.len() as i64) } else { None };
+
+        [args.total_bursts, fsk_limit].into_iter().flatten().min()
+    }
+
+    /// Returns whether the stimulation of the current cycle has ended
+    fn stimulation_done(&self, args: &Arguments) -> bool {
+        let cycle_active_time = (0..args.channels)
+            .filter(|channel| self.is_active(*channel))
+            .map(|channel| args.burst_samples(channel))
+            .max()
+            .unwrap_or(args.burst_samples(0));
+
+        self.sample - self.cyclestart > cycle_active_time + self.onset_offset(args)
+    }
+
+    /// Offset in samples of the first stimulation start within the current slot
+    fn onset_offset(&self, args: &Arguments) -> i64 {
+        if args.channel_offset.is_empty() {
+            return 0;
+        }
+
+        (0..args.channels)
+            .filter(|channel| self.is_active(*channel))
+            .map(|channel| args.offset_samples(channel))
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Start a stimulation on the channels active in the current cycle, and forget the finished
+    /// ones (overlap mode)
+    fn start_bursts(&mut self, args: &Arguments) {
+        for channel in 0..args.channels {
+            let cycle_active_time = args.burst_samples(channel);
+            let sample = self.sample;
+            let bursts = &mut self.bursts[channel as usize];
+
+            bursts.retain(|(start, _, _)| sample - start <= cycle_active_time);
+
+            if self.is_active(channel) {
+                let stimfreq = self.channel_freq(args, channel);
+                self.bursts[channel as usize].push((self.sample + args.offset_samples(channel), stimfreq, self.phase[channel as usize]));
+            }
+        }
+    }
+
+    /// Returns the slot without jitter, the slot at the (jittered) start of the next channel and the slot at the
+    /// (jittered) start of the current channel, the latter two only when there is such a channel
+    fn jittered_slots(&self, args: &Arguments) -> (i64, Option<i64>, Option<i64>) {
+        let channels = i64::from(args.channels);
+        let nojit_channel = args.elapsed_slots(self.sample, channels) % channels;
+
+        let next_channel = (nojit_channel < channels - 1)
+            .then(|| args.elapsed_slots(self.sample - self.jdelay[(nojit_channel + 1) as usize], channels) % channels);
+        let this_channel = (nojit_channel > 0)
+            .then(|| args.elapsed_slots(self.sample - self.jdelay[nojit_channel as usize], channels) % channels);
+
+        (nojit_channel, next_channel, this_channel)
+    }
+
+    /// Returns the current cycle (in range 0..args.channels)
+    fn curr_cycle(&mut self, args: &Arguments) -> i64{
+        if args.jitter.is_none() && args.verbosity <= 2 {
+            return args.elapsed_slots(self.sample, i64::from(args.channels)) % i64::from(args.channels);
+        }
+
+        let (nojit_channel, next_channel, this_channel) = self.jittered_slots(args);
+
+        if args.verbosity > 2 {
+            self.diagnostics.line(format_args!("CC Sample:{} nojit:{} jit1:{} jit2:{}",
+                self.sample, nojit_channel, next_channel.unwrap_or(-1), this_channel.unwrap_or(-1)));
+        }
+
+        if args.jitter.is_none() {
+            return nojit_channel;
+        }
+
+        // do we need to prestart next channel?
+        if let Some(jit_channel) = next_channel {
+            if self.jdelay[(nojit_channel+1) as usize] < 0 && jit_channel > nojit_channel {
+                return jit_channel;
+            }
+        }
+
+        // do we need to delay next channel?
+        if let Some(jit_channel) = this_channel {
+            if self.jdelay[nojit_channel as usize] > 0 && jit_channel < nojit_channel {
+                return jit_channel;
+            }
+        }
+
+        nojit_channel
+    }
+
+    /// Returns current sample for channel
+    fn sample(&mut self, args: &Arguments, channel: u32) -> f64 {
+        let rel_samples = self.rel_samples(args, channel);
+
+        if rel_samples.is_empty() {
+            return 0.0;
+        }
+
+        let sample = if args.carrier == Carrier::Noise {
+            let noise = self.noise[channel as usize].next_sample();
+            let gain : f64 = rel_samples.iter()
+                .map(|(rel_sample, stimfreq, _)| envelope(args, channel, *rel_sample) * self.emphasis_gain(*stimfreq))
+                .sum();
+
+            noise * gain / rel_samples.len() as f64
+        } else {
+            let carriers = args.carrier_freqs(args.stimfreq).len();
+            let sum : f64 = rel_samples.iter()
+                .map(|(rel_sample, stimfreq, start_phase)| burst_sample(args, channel, *rel_sample, *stimfreq, *start_phase, self.emphasis_gain(*stimfreq)))
+                .sum();
+
+            sum / (carriers * rel_samples.len()) as f64
+        };
+
+        with_marker(args, channel, &rel_samples, sample)
+    }
+
+    /// Returns the envelope of every channel at the current sample (emphasis included, carrier left out), None for
+    /// a channel without stimulation
+    fn envelopes(&mut self, args: &Arguments) -> Vec<Option<f64>> {
+        (0..args.channels)
+            .map(|channel| {
+                let rel_samples = self.rel_samples(args, channel);
+
+                if rel_samples.is_empty() {
+                    return None;
+                }
+
+                let sum : f64 = rel_samples.iter()
+                    .map(|(rel_sample, stimfreq, _)| envelope(args, channel, *rel_sample) * self.emphasis_gain(*stimfreq))
+                    .sum();
+
+                Some(sum / rel_samples.len() as f64)
+            })
+            .collect()
+    }
+
+    /// Returns the gain of the emphasis curve at freq, interpolated linearly in dB
+    fn emphasis_gain(&self, freq: i64) -> f64 {
+        if self.emphasis.is_empty() {
+            return 1.0;
+        }
+
+        let freq = freq as f64;
+        let upper = self.emphasis.iter().position(|(f, _)| *f >= freq).unwrap_or(self.emphasis.len() - 1);
+        let (f1, g1) = self.emphasis[upper];
+        let (f0, g0) = self.emphasis[upper.saturating_sub(1)];
+
+        let db = if f1 > f0 && freq > f0 { g0 + (g1 - g0) * (freq - f0) / (f1 - f0) } else { g1 };
+
+        10f64.powf(db / 20.0)
+    }
+
+    /// Returns the sample index relative to the start of each ongoing stimulation of channel, with the
+    /// stimulation's frequency and start phase, empty when the channel is silent. Only in overlap mode a channel
+    /// can have more than one.
+    fn rel_samples(&mut self, args: &Arguments, channel: u32) -> Vec<(i64, i64, f64)> {
+        let cycle_active_time = args.burst_samples(channel);
+
+        if args.overlap {
+            return self.bursts[channel as usize].iter()
+                .map(|(start, stimfreq, start_phase)| (self.sample - start, *stimfreq, *start_phase))
+                .filter(|(rel_sample, _, _)| (0..=cycle_active_time).contains(rel_sample))
+                .collect();
+        }
+
+        // only the channel of the current slot is active, so a stimulation longer than the slot is cut
+        // off when the next slot starts
+        if !self.is_active(channel) {
+            return Vec::new();
+        }
+
+        let rel_sample = self.sample - self.cyclestart - args.offset_samples(channel);
+
+        if !(0..=cycle_active_time).contains(&rel_sample) {
+            return Vec::new();
+        }
+
+        vec![(rel_sample, self.channel_freq(args, channel), self.phase[channel as usize])]
+    }
+
+    /// Returns current sample for all channels
+    fn frame(&mut self, args: &Arguments) -> Vec<f64> {
+        // only the sines are batched
+        #[cfg(feature = "simd")]
+        if self.batched && args.carrier != Carrier::Noise {
+            return self.frame_batched(args);
+        }
+
+        (0..args.channels).map(|channel| self.sample(args, channel)).collect()
+    }
+
+    /// Returns current sample for all channels, the active channels are computed in batches
+    #[cfg(feature = "simd")]
+    fn frame_batched(&mut self, args: &Arguments) -> Vec<f64> {
+
+        let mut frame = vec![0.0; args.channels as usize];
+
+        let mut bursts = vec![0; args.channels as usize];
+        let mut active : Vec<(usize, i64, i64, f64)> = Vec::new();
+
+        for channel in 0..args.channels {
+            for (rel_sample, stimfreq, start_phase) in self.rel_samples(args, channel) {
+                bursts[channel as usize] += 1;
+                active.push((channel as usize, rel_sample, stimfreq, start_phase));
+            }
+        }
+
+        let carriers = args.carrier_freqs(args.stimfreq).len();
+
+        for batch in active.chunks(simd::LANES) {
+            for carrier in 0..carriers {
+                let mut phases = [0.0; simd::LANES];
+                for (lane, (_, rel_sample, stimfreq, start_phase)) in batch.iter().enumerate() {
+                    phases[lane] = phase(args, *rel_sample, args.carrier_freqs(*stimfreq)[carrier]) + start_phase;
+                }
+
+                let sines = simd::sin(phases);
+                for (lane, (channel, rel_sample, stimfreq, _)) in batch.iter().enumerate() {
+                    frame[*channel] += envelope(args, *channel as u32, *rel_sample) * self.emphasis_gain(*stimfreq) * sines[lane];
+                }
+            }
+
+        }
+
+        for (channel, sample) in frame.iter_mut().enumerate() {
+            if bursts[channel] > 0 {
+                *sample /= (carriers * bursts[channel]) as f64;
+            }
+        }
+
+        if args.marker().is_some() {
+            for channel in 0..args.channels {
+                let rel_samples = self.rel_samples(args, channel);
+                frame[channel as usize] = with_marker(args, channel, &rel_samples, frame[channel as usize]);
+            }
+        }
+
+        frame
+    }
+
+    /// Returns whether channel is stimulated in the current cycle
+    fn is_active(&self, channel: u32) -> bool {
+        if !self.chords.is_empty() {
+            self.chords[self.cycle as usize].contains(&channel)
+        } else if self.sequence.is_empty() {
+            channel == self.channelorder[self.cycle as usize]
+        } else {
+            self.sequence[self.seqstep % self.sequence.len()].contains(&channel)
+        }
+    }
+
+    /// Returns whether the trigger pulse for the current stimulation is high
+    fn in_trigger(&self, args: &Arguments) -> bool {
+        let trigger_width = args.trigger_width * args.samplerate / 1000;
+
+        let rel_sample = self.sample - self.cyclestart - self.onset_offset(args);
+
+        (0..trigger_width).contains(&rel_sample)
+    }
+
+    /// Returns whether the current cycle gets a metronome click
+    fn at_metronome_beat(&self, args: &Arguments) -> bool {
+        match args.metronome {
+            Some(MetronomeAt::Cycle) => true,
+            Some(MetronomeAt::PauzePeriod) =>
+                args.elapsed_slots(self.sample, 1) % args.pauzecycleperiod == 0,
+            None => false,
+        }
+    }
+
+    /// Returns the metronome click at the current sample, a sine burst decaying linearly to 0
+    fn metronome_click(&self, args: &Arguments) -> f64 {
+        let clickstart = match self.clickstart {
+            Some(clickstart) => clickstart,
+            None => return 0.0,
+        };
+        let click_samples = args.metronome_click_ms * args.samplerate / 1000;
+        let rel_sample = self.sample - clickstart;
+
+        if !(0..click_samples).contains(&rel_sample) {
+            return 0.0;
+        }
+
+        let decay = 1.0 - rel_sample as f64 / click_samples as f64;
+        decay * (2.0 * PI * args.metronome_freq as f64 * rel_sample as f64 / args.samplerate as f64).sin()
+    }
+
+    /// Returns whether channel is currently pauzed
+    fn in_pauze(&self, args: &Arguments) -> bool {
+        let curr_paucycle = args.elapsed_slots(self.sample, 1) % args.pauzecycleperiod;
+
+        if args.random_pauzes.is_some() {
+            self.pauzes.contains(&curr_paucycle)
+        } else if args.active_cycles.is_empty() {
+            args.pauzes.contains(&curr_paucycle)
+        } else {
+            !args.active_cycles.contains(&curr_paucycle)
+        }
+    }
+
+}
+
+/// Returns the current (UTC) date as YYYYMMDD
+fn today() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+
+    // civil date from days since 1970-01-01 (proleptic Gregorian calendar)
+    let z = secs.div_euclid(86_400) + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}{:02}{:02}", year, month, day)
+}
+
+/// Returns the free disk space in bytes, available to the user, on the filesystem of path
+fn free_disk_bytes(path: &std::path::Path) -> Option<u64> {
+    fs2::available_space(path).ok()
+}
+
+/// Returns the sine argument at rel_sample for a carrier of freq Hz
+fn phase(args: &Arguments, rel_sample: i64, freq: i64) -> f64 {
+    let arg = rel_sample * freq * 2;
+    arg as f64 * PI / args.samplerate as f64
+}
+
+/// Returns a single stimulation at rel_sample: the envelope times gain times the sum of the carriers of stimfreq,
+/// starting at start_phase
+fn burst_sample(args: &Arguments, channel: u32, rel_sample: i64, stimfreq: i64, start_phase: f64, gain: f64) -> f64 {
+    envelope(args, channel, rel_sample) * gain * args.carrier_freqs(stimfreq).iter().map(|freq| (phase(args, rel_sample, *freq) + start_phase).sin()).sum::<f64>()
+}
+
+/// Returns the sample of the stimulations of channel at rel_samples with the marker tone mixed in (--dual-carrier):
+/// the stimulation at 1 - level, the marker at level with the envelope of every stimulation, starting at phase 0
+fn with_marker(args: &Arguments, channel: u32, rel_samples: &[(i64, i64, f64)], sample: f64) -> f64 {
+    match args.marker() {
+        Some((freq, level)) if !rel_samples.is_empty() => {
+            let marker : f64 = rel_samples.iter()
+                .map(|(rel_sample, _, _)| envelope(args, channel, *rel_sample) * phase(args, *rel_sample, freq).sin())
+                .sum();
+
+            (1.0 - level) * sample + level * marker / rel_samples.len() as f64
+        }
+        _ => sample,
+    }
+}
+
+/// Returns the envelope gain at rel_sample of a stimulation of channel
+fn envelope(args: &Arguments, channel: u32, rel_sample: i64) -> f64 {
+    if !args.has_envelope() {
+        return 1.0;
+    }
+
+    let (attack, decay, release) = args.envelope_samples(channel);
+    let sustain = args.sustain_level as f64 / 100.0;
+    let duration = args.burst_samples(channel) as f64;
+    let t = rel_sample as f64;
+
+    if t < attack {
+        t / attack
+    } else if t < attack + decay {
+        1.0 - (1.0 - sustain) * (t - attack) / decay
+    } else if t < duration - release {
+        sustain
+    } else {
+        (sustain * (duration - t) / release).max(0.0)
+    }
+}
+
+/// Parse a pair A:B of values, what names them (e.g. "min:max") in the errors
+fn parse_pair<T: FromStr>(pair: &str, what: &str) -> Result<(T, T), String> {
+    let (a, b) = pair.split_once(':').ok_or(format!("expected {}", what))?;
+    let (name_a, name_b) = what.split_once(':').unwrap_or((what, what));
+    let a : T = a.trim().parse().map_err(|_| format!("invalid {}: {}", name_a, a))?;
+    let b : T = b.trim().parse().map_err(|_| format!("invalid {}: {}", name_b, b))?;
+
+    Ok((a, b))
+}
+
+/// Parse an intercycle gap MIN:MAX in ms
+fn parse_gap(gap: &str) -> Result<(i64, i64), String> {
+    let (min, max) = parse_pair(gap, "min:max")?;
+
+    if min < 0 || max < min {
+        return Err(format!("gaps must be positive with min <= max: {}", gap));
+    }
+
+    Ok((min, max))
+}
+
+/// Parse alternating frequencies F1:F2 in Hz
+fn parse_alt_freqs(freqs: &str) -> Result<(i64, i64), String> {
+    let (f1, f2) = parse_pair(freqs, "f1:f2")?;
+
+    if f1 <= 0 || f2 <= 0 {
+        return Err(format!("frequencies must be positive: {}", freqs));
+    }
+
+    Ok((f1, f2))
+}
+
+/// Parse a cycle period ramp START:END in ms
+fn parse_ramp(ramp: &str) -> Result<(i64, i64), String> {
+    let (start, end) = parse_pair(ramp, "start:end")?;
+
+    if start <= 0 || end <= 0 {
+        return Err(format!("cycle periods must be positive: {}", ramp));
+    }
+
+    Ok((start, end))
+}
+
+/// Returns the gain of the session fade in and fade out at sample, on the amplitude scale
+fn session_fade(args: &Arguments, sample: i64) -> f64 {
+    let fade = match args.session_fade_sec {
+        Some(fade) if fade > 0 => (fade * args.samplerate) as f64,
+        _ => return 1.0,
+    };
+
+    // the fade out is relative to the full output, also in a preview
+    let to_end = args.secondsoutput.map(|secondsoutput| (secondsoutput * args.samplerate - 1 - sample) as f64);
+    let from_edge = (sample as f64).min(to_end.unwrap_or(f64::MAX));
+
+    if from_edge >= fade {
+        1.0
+    } else {
+        args.amplitude_scale.gain((1.0 - (PI * from_edge.max(0.0) / fade).cos()) / 2.0)
+    }
+}
+
+/// Returns the gain of the staircase level held at sample, on the amplitude scale
+fn staircase_gain(args: &Arguments, sample: i64) -> f64 {
+    if args.staircase.is_empty() {
+        return 1.0;
+    }
+
+    let step = args.elapsed_slots(sample, 1) / args.staircase_cycles;
+
+    args.amplitude_scale.gain(args.staircase[step as usize % args.staircase.len()] as f64 / 100.0)
+}
+
+/// Iterator over the interleaved output frames of a run, one frame (all channels) per sample
+pub struct Render {
+    args: Arguments,
+    sg: SampleGenerator,
+    samples_to_go: Option<i64>,
+    samples_done: i64,
+    delay_lines: Vec<VecDeque<i32>>,
+    peak: f64,
+    clipped: i64,
+    non_finite: Option<(i64, usize)>,
+    /// Silent samples left before the schedule continues (grid anchor, intercycle gap)
+    gap: i64,
+    /// Level of every stimulation channel at the last rendered sample relative to full scale, None when silent.
+    /// Only kept with --dense-schedule
+    levels: Vec<Option<f64>>,
+    /// Intervals between the stimulations of every channel, only kept with --render-stats-per-channel
+    intervals: Option<IntervalStats>,
+    /// Every output channel of the last rendered sample relative to full scale, before the conversion to
+    /// integers. Only kept with --format wav-f32
+    floats: Vec<f64>,
+    /// End of the schedule when the output is padded with silence after it (--round-duration)
+    padded_from: Option<i64>,
+}
+
+impl Render {
+
+    /// Start a new render from cmdline args, after checking them as the binary does. Returns the errors found
+    /// instead when they can't be rendered
+    pub fn new(args: &Arguments) -> Result<Render, Vec<ConfigError>> {
+        args.validate()?;
+
+        Render::with_diagnostics(args, Diagnostics::stdout()).map_err(|problem| vec![problem])
+    }
+
+    /// Start a new render from cmdline args that verify_argvalues accepted, an input file that became invalid
+    /// since then panics
+    fn verified(args: &Arguments) -> Render {
+        Render::with_diagnostics(args, Diagnostics::stdout()).unwrap_or_else(|problem| panic!("!!!{}", problem))
+    }
+
+    /// Start a new render writing the verbose diagnostics of the generator to diagnostics, fails on an invalid
+    /// input file
+    fn with_diagnostics(args: &Arguments, diagnostics: Diagnostics) -> Result<Render, ConfigError> {
+        let mut sg = SampleGenerator::new(args, diagnostics)?;
+        sg.gen_channelorder(args);
+
+        if let Some(count) = args.random_pauzes {
+            sg.gen_pauzes(args, count);
+        }
+
+        // count before starting the bursts, as next_sample does, so the first burst gets its frequency
+        sg.count_stimulation(args);
+
+        if args.overlap {
+            sg.start_bursts(args);
+        }
+
+        sg.pauzed = sg.in_pauze(args);
+
+        let mut render = Render {
+            args: args.clone(),
+            sg,
+            samples_to_go: args.render_samples(),
+            samples_done: 0,
+            delay_lines: args.delay_samples().iter().map(|delay| VecDeque::from(vec![0; *delay])).collect(),
+            peak: 0.0,
+            clipped: 0,
+            non_finite: None,
+            gap: args.anchor_samples(),
+            levels: vec![None; args.channels as usize],
+            floats: vec![0.0; if args.format == OutputFormat::WavF32 { args.output_channels() as usize } else { 0 }],
+            intervals: args.render_stats_per_channel.is_some()
+                .then(|| IntervalStats::new(args.channels, args.samplerate, args.cycle_period() as f64 / args.channels as f64)),
+            padded_from: None,
+        };
+        render.record_starts();
+
+        Ok(render)
+    }
+
+    /// Record the start of the stimulations of the cycle that starts with the next sample (after a gap), unless
+    /// pauzed, with --render-stats-per-channel
+    fn record_starts(&mut self) {
+        if let Some(intervals) = &mut self.intervals {
+            if !self.sg.in_pauze(&self.args) {
+                for channel in (0..self.args.channels).filter(|channel| self.sg.is_active(*channel)) {
+                    intervals.record(channel, self.samples_done + self.gap + self.args.offset_samples(channel));
+                }
+            }
+        }
+    }
+
+    /// Length in samples of the render when run to its end
+    fn length(args: &Arguments) -> i64 {
+        args.render_samples().unwrap_or_else(|| Render::verified(&Arguments { verbosity: 0, ..args.clone() }).count() as i64)
+    }
+
+    /// End the render after samples instead of where it ends by itself at end, with silence after the end
+    fn round_to(&mut self, end: i64, samples: i64) {
+        self.samples_to_go = Some(samples);
+        if samples > end {
+            self.padded_from = Some(end);
+        }
+    }
+
+    /// Start the auditioning render, same schedule as the output but with the preview transforms
+    fn new_audition(args: &Arguments) -> Render {
+        Render::verified(&args.audition_args())
+    }
+
+    /// Returns the baseline level and the baseline tone of the current frame (--continuous-mode), a sine at
+    /// --stimfreq counted from the start of the output, so it is continuous over gaps and pauzes
+    fn baseline_sample(&self) -> Option<(f64, f64)> {
+        self.args.baseline().map(|level| (level, phase(&self.args, self.samples_done - 1, self.args.stimfreq).sin()))
+    }
+
+    /// Set the stimulation channels of a frame without stimulation to the baseline tone (--continuous-mode)
+    fn baseline(&mut self, frame: &mut [i32]) -> Option<()> {
+        if let Some((level, baseline)) = self.baseline_sample() {
+            let amplitude = i16::MAX as f64 * self.args.chord_gain();
+            let fade = session_fade(&self.args, self.sg.sample) * staircase_gain(&self.args, self.sg.sample);
+
+            for (channel, sample) in frame.iter_mut().take(self.args.channels as usize).enumerate() {
+                *sample = self.output_sample(channel, level * baseline * amplitude * fade)?;
+                self.set_float(channel, level * baseline * self.args.chord_gain() * fade);
+            }
+        }
+
+        Some(())
+    }
+
+    /// Keep the value of channel relative to full scale for a float output
+    fn set_float(&mut self, channel: usize, value: f64) {
+        if let Some(float) = self.floats.get_mut(channel) {
+            *float = value;
+        }
+    }
+
+    /// Apply the fixed output delay per channel to a frame
+    fn delayed(&mut self, mut frame: Vec<i32>) -> Vec<i32> {
+        if !self.args.channel_delay.is_empty() {
+            for (sample, delay_line) in frame.iter_mut().zip(self.delay_lines.iter_mut()) {
+                delay_line.push_back(*sample);
+                *sample = delay_line.pop_front().unwrap();
+            }
+        }
+
+        frame
+    }
+
+    /// Convert a float sample of channel to the output, a NaN or infinite sample ends the render
+    fn output_sample(&mut self, channel: usize, value: f64) -> Option<i32> {
+        if !value.is_finite() {
+            self.non_finite = Some((self.sg.sample, channel));
+            return None;
+        }
+
+        Some(value as i32)
+    }
+}
+
+impl Iterator for Render {
+    type Item = Vec<i32>;
+
+    fn next(&mut self) -> Option<Vec<i32>> {
+        if self.samples_to_go == Some(self.samples_done) {
+            return None;
+        }
+
+        let padding = self.padded_from.is_some_and(|end| self.samples_done >= end);
+
+        if let Some(limit) = self.sg.stimulation_limit(&self.args).filter(|_| !padding) {
+            if self.sg.stimulations > limit
+                || (self.sg.stimulations == limit && self.sg.stimulation_done(&self.args)) {
+                return None;
+            }
+        }
+        self.samples_done += 1;
+
+        let mut next_sample = vec![0; self.args.output_channels() as usize];
+        self.floats.fill(0.0);
+
+        if self.args.dense_schedule.is_some() {
+            self.levels = vec![None; self.args.channels as usize];
+        }
+
+        if padding {
+            return Some(self.delayed(next_sample));
+        }
+
+        // the schedule waits during the grid anchor and an intercycle gap
+        if self.gap > 0 {
+            self.gap -= 1;
+            self.baseline(&mut next_sample)?;
+            return Some(self.delayed(next_sample));
+        }
+
+        // the metronome keeps going during pauzes
+        if let Some(channel) = self.args.metronome_channel() {
+            next_sample[channel as usize] = self.output_sample(channel as usize, i16::MAX as f64 * self.sg.metronome_click(&self.args))?;
+            self.set_float(channel as usize, self.sg.metronome_click(&self.args));
+        }
+
+        if !self.sg.in_pauze(&self.args) {
+            if self.args.trigger_channel && self.sg.in_trigger(&self.args) {
+                next_sample[self.args.channels as usize] = i16::MAX as i32;
+                self.set_float(self.args.channels as usize, 1.0);
+            }
+
+            let amplitude = i16::MAX as f64 * self.args.chord_gain();
+            let fade = session_fade(&self.args, self.sg.sample) * staircase_gain(&self.args, self.sg.sample);
+
+            let baseline = self.baseline_sample();
+
+            if self.args.dense_schedule.is_some() {
+                let gain = self.args.chord_gain() * fade * baseline.map_or(1.0, |(level, _)| 1.0 - level);
+                self.levels = self.sg.envelopes(&self.args).iter().zip(self.sg.amplitude.iter())
+                    .map(|(envelope, amplitude)| envelope.map(|envelope| envelope * amplitude * gain))
+                    .collect();
+            }
+
+            for (channel, sample) in self.sg.frame(&self.args).iter().enumerate() {
+                let sample = match baseline {
+                    Some((level, baseline)) => level * baseline + (1.0 - level) * sample * self.sg.amplitude[channel],
+                    None => sample * self.sg.amplitude[channel],
+                };
+                next_sample[channel] = self.output_sample(channel, sample * amplitude * fade)?;
+
+                // headroom, relative to full scale
+                let level = sample * self.args.chord_gain() * fade;
+                self.set_float(channel, level);
+                let level = level.abs();
+                self.peak = self.peak.max(level);
+                if level >= 1.0 {
+                    self.clipped += 1;
+                }
+            }
+        } else {
+            self.baseline(&mut next_sample)?;
+        }
+
+        let mut cycle_started = false;
+        for event in self.sg.next_sample(&self.args) {
+            self.sg.report(&self.args, event);
+
+            if event == Event::CycleStart(0) {
+                self.gap = self.sg.intercycle_gap(&self.args);
+            }
+            cycle_started |= matches!(event, Event::CycleStart(_));
+        }
+
+        if cycle_started {
+            self.record_starts();
+        }
+
+        Some(self.delayed(next_sample))
+    }
+}
+
+/// Seed of a passphrase (--seed-phrase): the 64-bit FNV-1a hash of its UTF-8 bytes, shifted right by one bit to
+/// be a positive --randomseed. The seeds of recorded phrases depend on it, so it must never change
+fn phrase_seed(phrase: &str) -> i64 {
+    (manifest::fnv1a64(phrase.as_bytes()) >> 1) as i64
+}
+
+/// Render the complete output in memory as interleaved samples, for in-process analysis.
+/// Use `Render` directly to stream large outputs frame by frame. Returns the errors in args instead when they
+/// can't be rendered
+pub fn render_to_vec(args: &Arguments) -> Result<Vec<i32>, Vec<ConfigError>> {
+    Ok(Render::new(args)?.flatten().collect())
+}
+
+/// Pass frame, the last one of render, to sink: before the conversion to integers if the sink takes floats
+fn write_frame(sink: &mut dyn Sink, render: &Render, frame: &[i32]) -> std::io::Result<()> {
+    if sink.takes_floats() {
+        sink.write_float_frame(&render.floats)
+    } else {
+        sink.write_frame(frame)
+    }
+}
+
+/// Write a single stimulation at --stimfreq (phase 0, envelope and emphasis included) as CSV to path, one line
+/// per sample with the amplitude relative to full scale, without rendering. Returns the number of samples
+fn dump_burst(args: &Arguments, path: &str) -> std::io::Result<i64> {
+    let mut sg = Render::verified(args).sg;
+    let samples = args.burst_samples(0) + 1;
+    let carriers = args.carrier_freqs(args.stimfreq).len() as f64;
+    let gain = sg.emphasis_gain(args.stimfreq);
+
+    let mut csv = String::from("sample,amplitude\n");
+    for rel_sample in 0..samples {
+        let amplitude = match args.carrier {
+            Carrier::Sine => burst_sample(args, 0, rel_sample, args.stimfreq, 0.0, gain) / carriers,
+            Carrier::Noise => sg.noise[0].next_sample() * envelope(args, 0, rel_sample) * gain,
+        };
+        let amplitude = with_marker(args, 0, &[(rel_sample, args.stimfreq, 0.0)], amplitude);
+        csv.push_str(&format!("{},{}\n", rel_sample, amplitude));
+    }
+
+    std::fs::write(path, csv)?;
+
+    Ok(samples)
+}
+
+/// Render the characterization sweep (--char-sweep) to the output file, with a seek point at every tone and the
+/// tones labeled in the comments
+fn char_sweep(args: &Arguments, sweep: &sweep::Sweep) {
+    // the placeholders of the schedule don't apply to the sweep
+    let placeholders = [("default", sweep.name()), ("channels", args.channels.to_string()), ("samplerate", args.samplerate.to_string()),
+        ("date", today())];
+    let fname = placeholders.iter()
+        .fold(args.name_template.clone(), |fname, (placeholder, value)| fname.replace(&format!("{{{}}}", placeholder), value));
+    let labels = args.channel_labels(&(0..args.channels).collect::<Vec<u32>>());
+
+    println!("Writing characterization sweep to: {}", fname);
+    println!("Sweep: {} tones ({} channels, {} frequencies) of {}ms, total duration {:.3}s",
+        sweep.segments.len(), args.channels, sweep.segments.len() / args.channels as usize, args.char_hold,
+        sweep.samples() as f64 / args.samplerate as f64);
+    if args.verbosity > 0 {
+        for segment in sweep.segments.iter() {
+            println!("   {:>9.3}s {} at {}Hz", segment.start as f64 / args.samplerate as f64, labels[segment.channel as usize], segment.freq);
+        }
+    }
+
+    if let Some(dir) = std::path::Path::new(&fname).parent() {
+        std::fs::create_dir_all(dir).unwrap();
+    }
+
+    let seek_points : Vec<u64> = sweep.segments.iter().map(|segment| segment.start as u64).collect();
+    let written = FlacSeekSink::create(&fname, args.channels, args.samplerate as u32, sweep.samples() as u64, args.buffer_frames, &seek_points)
+        .and_then(|sink| {
+            let mut sink : Box<dyn Sink> = Box::new(sink);
+            for sample in 0..sweep.samples() {
+                let frame : Vec<i32> = sweep.frame(sample).iter().map(|value| (i16::MAX as f64 * value) as i32).collect();
+                sink.write_frame(&frame)?;
+            }
+            sink.finish()
+        });
+    if let Err(e) = written {
+        println!("\n{}", format!("ERROR: Cannot write {}: {}", fname, e).red().bold());
+        std::process::exit(1);
+    }
+
+    // the audio is complete without the labels, the seek points still mark the tones
+    if let Err(e) = tags::write_comments(&fname, &sweep.comments(&labels)) {
+        println!("\n{}", format!("WARNING: Cannot label the sweep tones: {}", e).red().bold());
+    }
+
+    println!("Written {} samples to: {}", sweep.samples(), fname);
+}
+
+/// Returns the channels that are not stimulated in the first cycles of the schedule. Only the schedule is run,
+/// without generating the samples. None are returned for an invalid input file, which is reported by itself
+fn unscheduled_channels(args: &Arguments, cycles: i64) -> Vec<u32> {
+    let args = Arguments { verbosity: 0, ..args.clone() };
+    let Ok(render) = Render::with_diagnostics(&args, Diagnostics::discarded()) else {
+        return Vec::new();
+    };
+    let mut sg = render.sg;
+    let mut scheduled = vec![false; args.channels as usize];
+    let samples = args.cycles_to_samples(cycles);
+
+    let mut cycle_start = true;
+    while sg.sample < samples {
+        if cycle_start && !sg.in_pauze(&args) {
+            for channel in 0..args.channels {
+                scheduled[channel as usize] |= sg.is_active(channel);
+            }
+        }
+
+        cycle_start = sg.next_sample(&args).iter().any(|event| matches!(event, Event::CycleStart(_)));
+    }
+
+    (0..args.channels).filter(|channel| !scheduled[*channel as usize]).collect()
+}
+
+/// Arguments of the self-test: jittered, short and at a low rate
+const SELF_TEST_ARGS: [&str; 11] = ["f2heal-v2", "-s", "2", "--samplerate", "8000", "--channels", "4", "--jitter", "25",
+    "--randomseed", "1"];
+
+/// Returns the active channels (a bit per channel) at every sample of the schedule, without generating the samples.
+/// The verbose diagnostics are discarded
+fn active_sequence(args: &Arguments) -> Vec<u32> {
+    let mut sg = Render::with_diagnostics(args, Diagnostics::discarded()).unwrap_or_else(|problem| panic!("!!!{}", problem)).sg;
+    let samples = args.estimated_samples();
+    let mut sequence = Vec::new();
+
+    while sg.sample < samples {
+        sequence.push((0..args.channels).filter(|channel| sg.is_active(*channel)).map(|channel| 1 << channel).sum());
+        sg.next_sample(args);
+    }
+
+    sequence
+}
+
+/// Run the schedule of a fixed jittered configuration with and without the verbose diagnostics (-v -v -v) and
+/// check that they activate the same channels at every sample, returns whether they do
+fn verbose_test() -> bool {
+    let args = Arguments::parse_from(SELF_TEST_ARGS);
+    let verbose = Arguments { verbosity: 3, ..args.clone() };
+
+    println!("Self-test: {}", SELF_TEST_ARGS[1..].join(" "));
+
+    let quiet_sequence = active_sequence(&args);
+    let verbose_sequence = active_sequence(&verbose);
+
+    match quiet_sequence.iter().zip(verbose_sequence.iter()).position(|(a, b)| a != b) {
+        None if quiet_sequence.len() == verbose_sequence.len() => {
+            println!("   Active channels         : identical for {} samples", quiet_sequence.len());
+            true
+        }
+        None => {
+            println!("   Length differs          : {} vs {} samples", quiet_sequence.len(), verbose_sequence.len());
+            false
+        }
+        Some(i) => {
+            println!("   First difference        : sample {}, channels {:#06b} vs {:#06b} with -v -v -v", i, quiet_sequence[i], verbose_sequence[i]);
+            false
+        }
+    }
+}
+
+/// Samples over which the slot boundaries of the timing models are checked
+const DRIFT_TEST_SAMPLES: i64 = 1_000_000;
+
+/// Timing of the drift test, a cycle period in ms that isn't a whole number of samples per slot
+const DRIFT_TEST_ARGS: [&str; 9] = ["f2heal-v2", "-s", "23", "--samplerate", "44100", "--channels", "7", "--cycleperiod", "667"];
+
+/// Check for both timing models that every slot boundary over a million samples falls on the exact sample, the
+/// first sample at or after k slots of the cycle period, so there is no cumulative drift. Returns whether it does
+fn drift_test() -> bool {
+    let mut passed = true;
+
+    for model in [TimingModel::Legacy, TimingModel::Rational] {
+        let args = Arguments { timing_model: model, ..Arguments::parse_from(DRIFT_TEST_ARGS) };
+        let slots = i64::from(args.channels);
+        let (numerator, denominator) = ((args.samplerate * args.cycleperiod) as i128, (1_000 * slots) as i128);
+
+        let mut boundaries = 0;
+        let mut drift = None;
+        for sample in 1..DRIFT_TEST_SAMPLES {
+            let elapsed = args.elapsed_slots(sample, slots);
+            if elapsed == boundaries {
+                continue;
+            }
+
+            boundaries += 1;
+            // the exact boundary is ceil(boundaries * numerator / denominator)
+            let exact = (boundaries as i128 * numerator + denominator - 1) / denominator;
+            if elapsed != boundaries || exact != sample as i128 {
+                drift = Some((boundaries, sample, exact));
+                break;
+            }
+        }
+
+        match drift {
+            None => println!("   Timing {:<16} : {} slot boundaries on the exact sample", format!("{:?}", model).to_lowercase(), boundaries),
+            Some((boundary, sample, exact)) => {
+                println!("   Timing {:<16} : slot boundary {} at sample {}, exact {}", format!("{:?}", model).to_lowercase(), boundary, sample, exact);
+                passed = false;
+            }
+        }
+    }
+
+    passed
+}
+
+/// Run the self-tests, returns whether all pass
+fn self_test() -> bool {
+    let verbose = verbose_test();
+    let drift = drift_test();
+
+    verbose && drift
+}
+
+/// Returns the samples where a segment of the schedule starts (the first sample included), at the granularity
+/// of the markers. Only the schedule is run, without generating the samples
+fn marker_samples(args: &Arguments, at: MarkerAt) -> Vec<u64> {
+    let args = Arguments { verbosity: 0, ..args.clone() };
+    let mut sg = Render::verified(&args).sg;
+    let mut markers = vec![0];
+    let samples = args.estimated_samples();
+
+    // output samples of the grid anchor and the intercycle gaps so far, the first cycle starts after the anchor
+    let mut gaps = args.anchor_samples();
+    if gaps > 0 && gaps < samples {
+        markers.push(gaps as u64);
+    }
+
+    while sg.sample + gaps + 1 < samples {
+        for event in sg.next_sample(&args) {
+            if event == Event::CycleStart(0) {
+                gaps += sg.intercycle_gap(&args);
+            }
+
+            let marker = match (event, at) {
+                (Event::CycleStart(_), MarkerAt::Slot) => true,
+                (Event::CycleStart(0), MarkerAt::Cycle) => true,
+                (Event::CycleStart(0), MarkerAt::PauzePeriod) => args.elapsed_slots(sg.sample, 1) % args.pauzecycleperiod == 0,
+                _ => false,
+            };
+
+            if marker && sg.sample + gaps < samples {
+                markers.push((sg.sample + gaps) as u64);
+            }
+        }
+    }
+
+    markers
+}
+
+/// Cargo features of this build
+const FEATURES: [(&str, bool); 2] = [("simd", cfg!(feature = "simd")), ("plot", cfg!(feature = "plot"))];
+
+/// Print the version with the build information, to tell apart builds that generate different output
+fn print_version_full() {
+    let features : Vec<&str> = FEATURES.iter().filter(|(_, enabled)| *enabled).map(|(name, _)| *name).collect();
+    let rngs : Vec<String> = <RngKind as clap::ValueEnum>::value_variants().iter().map(|kind| format!("{:?}", kind).to_lowercase()).collect();
+
+    println!("f2heal-v2 {}", env!("CARGO_PKG_VERSION"));
+    println!("   Commit                  : {}", env!("F2HEAL_GIT_COMMIT"));
+    println!("   Features                : {}", if features.is_empty() { "none".to_owned() } else { features.join(", ") });
+    println!("   Random generators       : {} (default {})", rngs.join(", "), format!("{:?}", RngKind::Chacha8).to_lowercase());
+    println!("   rand / rand_chacha      : {} / {}", env!("F2HEAL_RAND_VERSION"), env!("F2HEAL_RAND_CHACHA_VERSION"));
+    println!("   Shuffles                : rand-0.8 (default), fisher-yates-v{} (--deterministic-shuffle)", rng::SHUFFLE_VERSION);
+}
+
+/// Compare two FLAC files sample by sample and report the differences, returns whether they are identical
+fn compare_files(fname_a: &str, fname_b: &str) -> bool {
+    let read = |fname: &str| decode::decode(fname).unwrap_or_else(|e| panic!("!!!ERROR: {}", e));
+    let (a, b) = (read(fname_a), read(fname_b));
+
+    println!("Comparing {} and {}:", fname_a, fname_b);
+
+    if a.channels != b.channels || a.samplerate != b.samplerate {
+        println!("   Format differs          : {} channels at {}Hz vs {} channels at {}Hz",
+            a.channels, a.samplerate, b.channels, b.samplerate);
+        return false;
+    }
+
+    let first_difference = a.samples.iter().zip(b.samples.iter()).position(|(x, y)| x != y);
+    let max_difference = a.samples.iter().zip(b.samples.iter()).map(|(x, y)| (x - y).abs()).max().unwrap_or(0);
+
+    if first_difference.is_none() && a.samples.len() == b.samples.len() {
+        println!("   Sample-identical        : {} samples of {} channels", a.frames(), a.channels);
+        return true;
+    }
+
+    if a.frames() != b.frames() {
+        println!("   Length differs          : {} vs {} samples", a.frames(), b.frames());
+    }
+    if let Some(i) = first_difference {
+        println!("   First difference        : sample {} on channel {}", i / a.channels as usize, i % a.channels as usize);
+    }
+    println!("   Max absolute difference : {}", max_difference);
+
+    false
+}
+
+/// Run the f2heal-v2 command line
+pub fn run() {
+    let matches = match preset::expand(std::env::args_os().collect()) {
+        Ok(command_line) => Arguments::command().get_matches_from(command_line),
+        Err(e) => {
+            println!("\n{}", format!("ERROR: {}", e).red().bold());
+            std::process::exit(1);
+        }
+    };
+    let mut args = Arguments::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    args.apply_seed_phrase();
+
+    if args.version_full {
+        print_version_full();
+        return;
+    }
+
+    if args.self_test {
+        std::process::exit(if self_test() { 0 } else { 1 });
+    }
+
+    if let Some(patterns) = args.rng_audit {
+        std::process::exit(if audit::rng_audit(&args, patterns) { 0 } else { 1 });
+    }
+
+    if args.list_modes {
+        modes::list_modes();
+        return;
+    }
+
+    if args.list_presets {
+        preset::list_presets();
+        return;
+    }
+
+    if let Some(name) = &args.describe_mode {
+        match modes::find(name) {
+            Some(mode) => modes::describe_mode(mode),
+            None => {
+                let names : Vec<&str> = modes::MODES.iter().map(|mode| mode.name).collect();
+                println!("\n{}", format!("ERROR: Unknown mode {}, available: {}", name, names.join(", ")).red().bold());
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(path) = &args.watch {
+        watch::watch(path);
+    }
+
+    if !args.compare.is_empty() {
+        let identical = compare_files(&args.compare[0], &args.compare[1]);
+        std::process::exit(if identical { 0 } else { 1 });
+    }
+
+    if let Some(fname_in) = &args.process {
+        let fname_out = process::processed_fname(fname_in);
+        let transforms = process::Transforms { gain: args.gain, trim: args.trim, remap: args.remap.clone() };
+
+        match process::process(fname_in, &fname_out, &transforms) {
+            Ok(samples) => println!("Written {} samples to: {}", samples, fname_out),
+            Err(e) => {
+                println!("\n{}", format!("ERROR: {}", e).red().bold());
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(freqs) = &args.char_sweep {
+        let freqs = sweep::parse_freqs(freqs, args.samplerate).unwrap_or_else(|e| {
+            println!("\n{}", format!("ERROR: {}", e).red().bold());
+            std::process::exit(1);
+        });
+        if args.char_hold <= 0 {
+            println!("\n{}", "ERROR: The hold of the sweep tones (--char-hold) must be positive".red().bold());
+            std::process::exit(1);
+        }
+
+        char_sweep(&args, &sweep::Sweep::new(args.channels, &freqs, args.char_hold, args.samplerate));
+        return;
+    }
+
+    if args.snap_stimfreq {
+        args.apply_snap_stimfreq();
+    }
+
+    // validate-only writes nothing but the JSON to stdout
+    if args.verbosity > 0 && !args.validate_only {
+        args.display_config();
+    }
+
+    let problems = args.verify_argvalues();
+
+    if args.validate_only {
+        let status = if problems.iter().any(|problem| problem.is_error()) {
+            "error"
+        } else if !problems.is_empty() {
+            "warning"
+        } else {
+            "ok"
+        };
+        let issues : Vec<String> = problems.iter().map(|problem| format!("\n    {}", problem.to_json())).collect();
+        let issues = if issues.is_empty() { "[]".to_owned() } else { format!("[{}\n  ]", issues.join(",")) };
+
+        println!("{{\n  \"status\": {},\n  \"issues\": {}\n}}", manifest::string(status), issues);
+
+        std::process::exit(match status { "error" => 2, "warning" => 1, _ => 0 });
+    }
+
+    for problem in problems.iter() {
+        println!("\n{}", problem.to_string().red().bold());
+    }
+
+    if problems.iter().any(|problem| problem.is_error() || args.strict) {
+        println!("\n{}",
+            format!("ERROR: Not generating output, {} problem(s) with the supplied arguments", problems.len()).red().bold());
+        std::process::exit(1);
+    }
+
+    if let Some(name) = &args.save_preset {
+        match preset::save(name, &matches) {
+            Ok(path) => println!("Saved preset {} to: {}", name, path.display()),
+            Err(e) => {
+                println!("\n{}", format!("ERROR: {}", e).red().bold());
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(path) = &args.dump_burst {
+        match dump_burst(&args, path) {
+            Ok(samples) => println!("Written a stimulation of {} samples to: {}", samples, path),
+            Err(e) => {
+                println!("\n{}", format!("ERROR: Cannot write {}: {}", path, e).red().bold());
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(path) = &args.tag {
+        match tags::write_tags(&args, path) {
+            Ok(replaced) => {
+                if replaced > 0 {
+                    println!("\n{}", format!("WARNING: Replaced {} existing f2heal comment(s) in {}", replaced, path).red().bold());
+                }
+                println!("Written the parameters as comments to: {}", path);
+            }
+            Err(e) => {
+                println!("\n{}", format!("ERROR: Cannot tag {}", e).red().bold());
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let fname = args.construct_fname();
+
+    println!("Writing output to: {}", fname);
+
+    // the length before rounding, counted by a run of the schedule when only the stimulations are limited
+    let unrounded = args.round_duration.map(|_| Render::length(&args));
+    let samples_to_go = unrounded.map_or(args.estimated_samples(), |samples| args.rounded_samples(samples));
+  
+    if let Some(dir) = std::path::Path::new(&fname).parent() {
+        std::fs::create_dir_all(dir).unwrap();
+    }
+
+    // Don't start a render that will run out of disk space
+    let dir = std::path::Path::new(&fname).parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(std::path::Path::new("."));
+    if let Some(free) = free_disk_bytes(dir) {
+        let needed = args.estimated_bytes().saturating_add(args.min_free_mb.saturating_mul(1024 * 1024));
+
+        if free < needed {
+            println!("\n{}",
+                format!("ERROR: Not enough free disk space, {}MB needed (estimated output + margin), {}MB available",
+                    needed / 1024 / 1024, free / 1024 / 1024).red().bold());
+            std::process::exit(1);
+        }
+    }
+
+    // the schedule runs at the synthesis rate, the sink gets the output rate
+    let synth_args = args.synth_args();
+
+    let mut flac_outfile : File;
+    let mut flac_outwrap : flac_bound::WriteWrapper;
+    let mut sink : Box<dyn Sink> = match args.markers {
+        _ if args.format == OutputFormat::WavF32 =>
+            Box::new(WavF32Sink::create(&fname, args.output_channels(), args.samplerate as u32).unwrap()),
+        Some(at) => {
+            let markers : Vec<u64> = marker_samples(&synth_args, at).iter()
+                .map(|sample| (sample * args.samplerate as u64 + synth_args.samplerate as u64 / 2) / synth_args.samplerate as u64)
+                .collect();
+            println!("Seek points: {} ({:?})", markers.len(), at);
+
+            Box::new(FlacSeekSink::create(&fname, args.output_channels(), args.samplerate as u32, samples_to_go as u64,
+                args.buffer_frames, &markers).unwrap())
+        }
+        None => {
+            flac_outfile = File::create(&fname).unwrap();
+            flac_outwrap = flac_bound::WriteWrapper(&mut flac_outfile);
+            let flac_encoder = flac_bound::FlacEncoder::new().unwrap()
+                .channels(args.output_channels())
+                .bits_per_sample(16)
+                .sample_rate(args.samplerate as u32)
+                .total_samples_estimate(samples_to_go as u64)
+                .compression_level(8)
+                .init_write(&mut flac_outwrap)
+                .unwrap();
+
+            Box::new(FlacSink::new(flac_encoder, args.output_channels(), args.buffer_frames))
+        }
+    };
+
+    if let Some(synth_rate) = args.synth_rate {
+        sink = Box::new(ResampleSink::new(sink, args.output_channels(), synth_rate as u32, args.samplerate as u32));
+    }
+
+    let mut audition = args.play_pcm.as_ref().map(|path| {
+        println!("Auditioning stream to: {}", path);
+        let mut sink : Box<dyn Sink> = Box::new(PcmSink::create(path, 10f64.powf(args.preview_gain / 20.0)).unwrap());
+        if let (Some(downmix), Some(map)) = (args.downmix, args.downmix_map()) {
+            println!("Downmixed to {} channels: {:?}", downmix, map);
+            sink = Box::new(DownmixSink::new(sink, &map, downmix));
+        }
+        let mut render = Render::new_audition(&synth_args);
+        if let Some(unrounded) = unrounded {
+            render.round_to(unrounded, samples_to_go);
+        }
+        (render, sink)
+    });
+
+    let mut render = Render::verified(&synth_args);
+    if let Some(unrounded) = unrounded {
+        render.round_to(unrounded, samples_to_go);
+    }
+
+    if args.energy_normalize {
+        let gains : Vec<String> = args.channel_labels(&(0..args.channels).collect::<Vec<u32>>()).iter()
+            .zip(&render.sg.channel_gains)
+            .map(|(label, gain)| format!("{} {:.3}", label, gain))
+            .collect();
+        println!("Channel gains for equal energy per stimulation: {}", gains.join(", "));
+    }
+
+    let mut dense_schedule = args.dense_schedule.as_ref().map(|path| {
+        println!("Dense schedule to: {}", path);
+        ScheduleWriter::create(path, args.channels, synth_args.samplerate as u32, args.dense_schedule_downsample).unwrap()
+    });
+
+    interrupt::install();
+
+    // the frames of the render, at the synthesis rate
+    let mut progress = args.progress_json.then(|| Progress::start(match unrounded {
+        Some(_) => samples_to_go,
+        None => synth_args.estimated_samples(),
+    }));
+
+    let mut leading_silence = args.trim_leading_silence;
+    let mut trimmed = 0;
+
+    while let Some(next_sample) = render.next() {
+        if leading_silence && next_sample.iter().all(|sample| *sample == 0) {
+            trimmed += 1;
+        } else {
+            leading_silence = false;
+            write_frame(sink.as_mut(), &render, &next_sample).unwrap();
+        }
+
+        if let Some(schedule) = dense_schedule.as_mut() {
+            schedule.write(&render.levels).unwrap();
+        }
+
+        if let Some((render, out)) = audition.as_mut() {
+            out.write_frame(&render.next().unwrap()).unwrap();
+        }
+
+        if let Some(progress) = progress.as_mut() {
+            progress.update(render.samples_done);
+        }
+
+        if interrupt::requested() {
+            println!("\n{}",
+                format!("Interrupted, output ends after {:.3}s", render.samples_done as f64 / synth_args.samplerate as f64).red().bold());
+            break;
+        }
+    }
+
+    if let Some(progress) = progress {
+        progress.finish(render.samples_done);
+    }
+
+    if args.trim_leading_silence {
+        println!("Trimmed leading silence: {} samples ({:.3}s)", trimmed, trimmed as f64 / synth_args.samplerate as f64);
+    }
+
+    if let Some((_, out)) = audition {
+        out.finish().unwrap();
+    }
+
+    if let Some(schedule) = dense_schedule {
+        schedule.finish().unwrap();
+    }
+
+    // never leave corrupt audio behind
+    if let Some((sample, channel)) = render.non_finite {
+        println!("\n{}",
+            format!("ERROR: Invalid (NaN or infinite) sample on channel {} at sample {}, output removed", channel, sample).red().bold());
+        let _ = sink.finish();
+        let _ = std::fs::remove_file(&fname);
+        std::process::exit(1);
+    }
+
+    if args.total_bursts.is_some() {
+        println!("Rendered {} stimulations in {:.3}s",
+            render.sg.stimulations, render.samples_done as f64 / synth_args.samplerate as f64);
+    }
+
+    if let (Some(cycles), None) = (args.cycles, args.preview) {
+        println!("Rendered {} cycles in {:.3}s", cycles, render.samples_done as f64 / synth_args.samplerate as f64);
+    }
+
+    if let Some(unrounded) = unrounded {
+        let seconds = |samples: i64| samples as f64 / args.samplerate as f64;
+        if render.samples_done > unrounded {
+            println!("Duration rounded up from {:.3}s to {:.3}s: {} samples of silence added", seconds(unrounded),
+                seconds(render.samples_done), render.samples_done - unrounded);
+        } else if render.samples_done < unrounded {
+            println!("Duration rounded down from {:.3}s to {:.3}s: {} samples cut", seconds(unrounded),
+                seconds(render.samples_done), unrounded - render.samples_done);
+        }
+    }
+
+    assert!(sink.finish().is_ok(), "!!!ERROR: Failed to finish FLAC output");
+
+    if args.manifest {
+        let manifest = manifest::write_manifest(&args, &fname).unwrap();
+        println!("Manifest written to: {}", manifest);
+    }
+
+    #[cfg(feature = "plot")]
+    if let Some(path) = &args.plot {
+        plot::write_png(&args, path).unwrap();
+        println!("Waveform overview written to: {}", path);
+    }
+
+    if let (Some(csv), Some(intervals)) = (&args.render_stats_per_channel, &mut render.intervals) {
+        let labels = args.channel_labels(&(0..args.channels).collect::<Vec<u32>>());
+        intervals.truncate(render.samples_done);
+        intervals.report(&labels);
+
+        if let Some(path) = csv {
+            intervals.write_csv(path, &labels).unwrap();
+            println!("Interval histogram written to: {}", path);
+        }
+    }
+
+    if args.headroom_report {
+        println!("Headroom report:");
+        println!("   Peak level              : {:.2}dBFS", 20.0 * render.peak.log10());
+        println!("   Samples at full scale   : {}", render.clipped);
+
+        if render.clipped > 0 && args.strict {
+            println!("\n{}", "ERROR: Output reaches full scale".red().bold());
+            std::process::exit(1);
+        }
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Seed phrase and its seed, the FNV-1a hash of the phrase shifted right by one bit
+    const SEED_PHRASE: &str = "monday-protocol-A";
+    const GOLDEN_PHRASE_SEED: i64 = 7161227644504006941;
+
+    /// The seeds of recorded phrases depend on the mapping, it must never change
+    #[test]
+    fn seed_phrase_is_pinned() {
+        assert_eq!(phrase_seed(SEED_PHRASE), GOLDEN_PHRASE_SEED);
+    }
+
+    /// Stimulation durations (ms) of the snap test, which don't divide a second, the frequency asked and the snapped
+    /// one: a multiple of 1000 / gcd(1000, 30) = 100 and 1000 / gcd(1000, 40) = 25
+    const SNAP_STIMDURATIONS: [i64; 2] = [30, 40];
+    const SNAP_STIMFREQ: i64 = 250;
+    const SNAPPED_STIMFREQ: i64 = 300;
+
+    /// --snap-stimfreq snaps to the nearest frequency with a whole number of periods in every stimulation duration,
+    /// also for durations that don't divide a second, and the snapped frequency doesn't warn
+    #[test]
+    fn snap_stimfreq_gives_whole_periods() {
+        let durations = SNAP_STIMDURATIONS.map(|duration| duration.to_string()).join(",");
+        let mut args = Arguments::parse_from(["f2heal-v2", "-s", "1", "--samplerate", "8000", "--channels", "2",
+            "--stimduration", &durations, "--stimfreq", &SNAP_STIMFREQ.to_string(), "--snap-stimfreq"]);
+        assert!(args.verify_argvalues().contains(&ConfigError::StimFreqMismatch));
+
+        args.apply_snap_stimfreq();
+        assert_eq!(args.stimfreq, SNAPPED_STIMFREQ);
+        for duration in SNAP_STIMDURATIONS {
+            assert_eq!(args.stimfreq * duration % 1000, 0, "{}Hz is no whole number of periods in {}ms", args.stimfreq, duration);
+        }
+        assert!(!args.verify_argvalues().contains(&ConfigError::StimFreqMismatch));
+    }
+
+    /// A stimulation duration above a second is refused instead of snapping or checking the frequency with it
+    #[test]
+    fn stimduration_above_second_is_refused() {
+        let mut args = Arguments::parse_from(["f2heal-v2", "-s", "1", "--stimduration", "1500", "--snap-stimfreq"]);
+        args.apply_snap_stimfreq();
+
+        assert!(args.verify_argvalues().contains(&ConfigError::StimdurationRange(1500)));
+    }
+
+    /// Short render at a low rate, written to memory and to FLAC by the sink test
+    const SINK_TEST_ARGS: [&str; 11] = ["f2heal-v2", "-s", "1", "--samplerate", "8000", "--channels", "4", "--jitter", "25",
+        "--randomseed", "1"];
+
+    /// The frames the render loop writes to a FLAC file decode to the frames it writes to memory
+    #[test]
+    fn flac_sink_writes_rendered_frames() {
+        let args = Arguments::parse_from(SINK_TEST_ARGS);
+        let fname = std::env::temp_dir().join(format!("f2heal-sink-test-{}.flac", std::process::id()));
+        let mut frames = Vec::new();
+
+        {
+            let mut flac_outfile = File::create(&fname).unwrap();
+            let mut flac_outwrap = flac_bound::WriteWrapper(&mut flac_outfile);
+            let flac_encoder = flac_bound::FlacEncoder::new().unwrap()
+                .channels(args.output_channels())
+                .bits_per_sample(16)
+                .sample_rate(args.samplerate as u32)
+                .compression_level(8)
+                .init_write(&mut flac_outwrap)
+                .unwrap();
+
+            let mut sinks : [Box<dyn Sink>; 2] = [
+                Box::new(FlacSink::new(flac_encoder, args.output_channels(), args.buffer_frames)),
+                Box::new(sink::VecSink::new(&mut frames)),
+            ];
+            let mut render = Render::verified(&args);
+            while let Some(frame) = render.next() {
+                for sink in sinks.iter_mut() {
+                    write_frame(sink.as_mut(), &render, &frame).unwrap();
+                }
+            }
+            for sink in sinks {
+                sink.finish().unwrap();
+            }
+        }
+
+        let decoded = decode::decode(&fname.to_string_lossy()).unwrap();
+        let _ = std::fs::remove_file(&fname);
+
+        assert_eq!(frames.len(), args.estimated_samples() as usize * args.output_channels() as usize);
+        assert!(decoded.samples == frames, "the FLAC file differs from the frames written to memory");
+    }
+
+    /// Envelope of a stimulation of 800 samples at 8000Hz: attack, decay, sustain (%) and release of 80, 160 and 240
+    /// samples, and one exceeding the stimulation twice, scaled down to 240, 160 and 400 samples
+    const ENVELOPE: [&str; 4] = ["10", "20", "50", "30"];
+    const ENVELOPE_SCALED: [&str; 4] = ["60", "40", "50", "100"];
+
+    /// Sample in the stimulation and level of every stage boundary of each envelope
+    const ENVELOPE_LEVELS: [(i64, f64); 8] = [(0, 0.0), (40, 0.5), (80, 1.0), (160, 0.75), (240, 0.5), (560, 0.5), (680, 0.25), (800, 0.0)];
+    const ENVELOPE_SCALED_LEVELS: [(i64, f64); 6] = [(0, 0.0), (120, 0.5), (240, 1.0), (400, 0.5), (600, 0.25), (800, 0.0)];
+
+    /// The ADSR envelope of the default stimulation with --attack-ms, --decay-ms, --sustain-level and --release-ms
+    fn envelope_args(adsr: [&str; 4]) -> Arguments {
+        Arguments::parse_from(["f2heal-v2", "-s", "1", "--samplerate", "8000", "--attack-ms", adsr[0], "--decay-ms", adsr[1],
+            "--sustain-level", adsr[2], "--release-ms", adsr[3]])
+    }
+
+    /// The envelope rises over the attack, falls to the sustain level over the decay, holds it and falls to 0 over
+    /// the release, also when attack, decay and release are scaled down to fit the stimulation
+    #[test]
+    fn envelope_stage_boundaries() {
+        for (adsr, levels) in [(ENVELOPE, &ENVELOPE_LEVELS[..]), (ENVELOPE_SCALED, &ENVELOPE_SCALED_LEVELS[..])] {
+            let args = envelope_args(adsr);
+
+            for (rel_sample, level) in levels {
+                let enveloped = envelope(&args, 0, *rel_sample);
+                assert!((enveloped - level).abs() < 1e-9, "envelope {:?} at sample {} is {}, expected {}", adsr, rel_sample, enveloped, level);
+            }
+        }
+    }
+
+    /// A fixed order of 4 channels in slots of 800 samples, pauzing every other cycle of 3200 samples
+    const EVENT_TEST_ARGS: [&str; 14] = ["f2heal-v2", "-s", "1", "--samplerate", "8000", "--channels", "4", "--cycleperiod", "400",
+        "--norandom", "--pauzecycleperiod", "2", "--pauzes", "1"];
+
+    /// Events of the first 3 cycles of the event test, with the sample they happen at
+    const EVENT_STREAM: [(i64, Event); 15] = [
+        (800, Event::CycleStart(1)), (1600, Event::CycleStart(2)), (2400, Event::CycleStart(3)),
+        (3200, Event::NewPattern), (3200, Event::CycleStart(0)), (3200, Event::PauzeStart),
+        (4000, Event::CycleStart(1)), (4800, Event::CycleStart(2)), (5600, Event::CycleStart(3)),
+        (6400, Event::NewPattern), (6400, Event::CycleStart(0)), (6400, Event::PauzeEnd),
+        (7200, Event::CycleStart(1)), (8000, Event::CycleStart(2)), (8800, Event::CycleStart(3)),
+    ];
+
+    /// The generator reports every slot start, new pattern and pauze transition of a known schedule, in order and
+    /// at its sample
+    #[test]
+    fn next_sample_reports_events() {
+        let args = Arguments::parse_from(EVENT_TEST_ARGS);
+        let mut sg = Render::verified(&args).sg;
+        let mut events = Vec::new();
+
+        while sg.sample < args.cycles_to_samples(3) - 1 {
+            let sample_events = sg.next_sample(&args);
+            events.extend(sample_events.into_iter().map(|event| (sg.sample, event)));
+        }
+
+        assert_eq!(events, EVENT_STREAM);
+    }
+
+    /// Amplitude jitter (%) and cycles over which the drawn amplitudes are checked
+    const AMP_JITTER: i64 = 30;
+    const AMP_JITTER_CYCLES: i64 = 20;
+
+    /// Amplitudes of every channel drawn for every pattern of seed over the test cycles
+    fn drawn_amplitudes(seed: i64) -> Vec<Vec<f64>> {
+        let args = Arguments::parse_from(["f2heal-v2", "-s", "1", "--samplerate", "8000", "--channels", "4",
+            "--amp-jitter", &AMP_JITTER.to_string(), "--randomseed", &seed.to_string()]);
+        let mut sg = Render::verified(&args).sg;
+        let mut drawn = vec![sg.amplitude.clone()];
+
+        while sg.sample < args.cycles_to_samples(AMP_JITTER_CYCLES) {
+            if sg.next_sample(&args).contains(&Event::NewPattern) {
+                drawn.push(sg.amplitude.clone());
+            }
+        }
+
+        drawn
+    }
+
+    /// --amp-jitter draws every amplitude from [1 - A, 1], as 1 + A is clamped to full scale, the same for a seed
+    /// and different for another seed
+    #[test]
+    fn amp_jitter_stays_in_band() {
+        let drawn = drawn_amplitudes(1);
+        let lowest = 1.0 - AMP_JITTER as f64 / 100.0;
+        assert!(drawn.len() as i64 >= AMP_JITTER_CYCLES, "only {} patterns drawn", drawn.len());
+
+        for amplitudes in &drawn {
+            assert!(amplitudes.iter().all(|amplitude| (lowest..=1.0).contains(amplitude)),
+                "amplitudes {:?} outside [{}, 1]", amplitudes, lowest);
+        }
+        assert!(drawn.iter().flatten().any(|amplitude| *amplitude < 1.0), "no amplitude is jittered");
+
+        assert!(drawn_amplitudes(1) == drawn, "seed 1 draws other amplitudes the second time");
+        assert!(drawn_amplitudes(2) != drawn, "seeds 1 and 2 draw the same amplitudes");
+    }
+
+    /// Cycles over which the schedule of a random jittered configuration is checked, and the configurations
+    /// checked per property
+    const JITTER_TEST_CYCLES: i64 = 6;
+    const JITTER_TEST_CASES: u32 = 64;
+
+    /// A blocked configuration of channels with jitter (%) and seed
+    fn jitter_args(channels: u32, jitter: i64, seed: i64) -> Arguments {
+        Arguments::parse_from(["f2heal-v2", "-s", "1", "--samplerate", "8000", "--channels", &channels.to_string(),
+            "--jitter", &jitter.to_string(), "--randomseed", &seed.to_string()])
+    }
+
+    /// Sample, cycle and channel of every slot start of the schedule of args over the test cycles, in order
+    fn slot_starts(args: &Arguments) -> Vec<(i64, i64, u32)> {
+        let mut sg = Render::verified(args).sg;
+        let mut starts = vec![(0, 0, sg.channelorder[0])];
+
+        while sg.sample < args.cycles_to_samples(JITTER_TEST_CYCLES) {
+            for event in sg.next_sample(args) {
+                if let Event::CycleStart(cycle) = event {
+                    starts.push((sg.sample, cycle, sg.channelorder[cycle as usize]));
+                }
+            }
+        }
+
+        starts
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(JITTER_TEST_CASES))]
+
+        /// Whatever the jitter, exactly one channel is active at every sample in blocked mode
+        #[test]
+        fn jitter_keeps_one_channel_active(channels in 2u32..=8, jitter in 0i64..=100, seed in 0i64..1_000) {
+            let args = jitter_args(channels, jitter, seed);
+            let mut sg = Render::verified(&args).sg;
+
+            while sg.sample < args.cycles_to_samples(JITTER_TEST_CYCLES) {
+                let active = (0..channels).filter(|channel| sg.is_active(*channel)).count();
+                prop_assert_eq!(active, 1, "{} channels active at sample {}", active, sg.sample);
+                sg.next_sample(&args);
+            }
+        }
+
+        /// Whatever the jitter, the slots of a cycle start in order and every channel starts once per cycle
+        #[test]
+        fn jitter_starts_every_channel_once_per_cycle(channels in 2u32..=8, jitter in 0i64..=100, seed in 0i64..1_000) {
+            let args = jitter_args(channels, jitter, seed);
+            let starts = slot_starts(&args);
+            prop_assert!(starts.len() as i64 >= JITTER_TEST_CYCLES * i64::from(channels), "only {} slots started", starts.len());
+
+            for (cycle, slots) in starts.chunks(channels as usize).enumerate() {
+                let mut started : Vec<u32> = slots.iter().map(|(_, _, channel)| *channel).collect();
+                prop_assert!(slots.iter().enumerate().all(|(i, (_, slot, _))| *slot == i as i64),
+                    "slots of cycle {} start out of order: {:?}", cycle, slots);
+
+                started.sort_unstable();
+                started.dedup();
+                prop_assert_eq!(started.len(), slots.len(), "a channel starts twice in cycle {}: {:?}", cycle, slots);
+            }
+        }
+
+        /// Every slot, and so the stimulation in it, starts within the jitter of its nominal start: J % of half a
+        /// slot either way
+        #[test]
+        fn jitter_starts_within_window(channels in 2u32..=8, jitter in 0i64..=100, seed in 0i64..1_000) {
+            let args = jitter_args(channels, jitter, seed);
+            let window = args.cycles_to_samples(jitter) / (200 * i64::from(channels)) + 1;
+
+            let mut nominal = 0;
+            for (slot, (sample, _, channel)) in slot_starts(&args).iter().enumerate() {
+                while args.elapsed_slots(nominal, i64::from(channels)) < slot as i64 {
+                    nominal += 1;
+                }
+                prop_assert!((sample - nominal).abs() <= window,
+                    "slot {} (channel {}) starts at sample {}, {} samples from its nominal start", slot, channel, sample, sample - nominal);
+            }
+        }
+    }
+}
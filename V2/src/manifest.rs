@@ -0,0 +1,109 @@
+//! Reproducibility manifest written next to the FLAC output.
+//!
+//! The manifest is a JSON file holding everything needed to reproduce or audit a run: the
+//! resolved configuration, crate version and git commit, seed, command line, timestamp and a hash of the
+//! output file. The hash is a 64-bit FNV-1a over the file bytes, which is stable across
+//! platforms and versions.
+
+use std::fs;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::Arguments;
+
+/// Returns the manifest filename for an output filename
+pub fn manifest_fname(fname: &str) -> String {
+    match fname.strip_suffix(".flac").or_else(|| fname.strip_suffix(".wav")) {
+        Some(stem) => format!("{}.manifest.json", stem),
+        None => format!("{}.manifest.json", fname),
+    }
+}
+
+/// Write the manifest for the output file `fname`
+pub fn write_manifest(args: &Arguments, fname: &str) -> io::Result<String> {
+    let output = fs::read(fname)?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let cmdline : Vec<String> = std::env::args().collect();
+
+    let mut json = String::from("{\n");
+    json.push_str(&format!("  \"version\": {},\n", string(env!("CARGO_PKG_VERSION"))));
+    json.push_str(&format!("  \"commit\": {},\n", string(env!("F2HEAL_GIT_COMMIT"))));
+    json.push_str(&format!("  \"seed\": {},\n", args.seed()));
+    json.push_str(&format!("  \"shuffle_algorithm\": {},\n", string(&args.shuffle_algorithm())));
+    json.push_str(&format!("  \"command_line\": {},\n", strings(&cmdline)));
+    json.push_str(&format!("  \"timestamp\": {},\n", timestamp));
+    json.push_str(&format!("  \"output\": {},\n", string(fname)));
+    json.push_str(&format!("  \"output_fnv1a64\": \"{:016x}\",\n", fnv1a64(&output)));
+    json.push_str(&format!("  \"config\": {}\n", args.to_json()));
+    json.push_str("}\n");
+
+    let manifest = manifest_fname(fname);
+    fs::write(&manifest, json)?;
+
+    Ok(manifest)
+}
+
+/// 64-bit FNV-1a hash
+pub fn fnv1a64(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+
+    for byte in data {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    hash
+}
+
+/// JSON string literal
+pub fn string(value: &str) -> String {
+    let mut result = String::from("\"");
+
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+
+    result.push('"');
+    result
+}
+
+/// JSON array of strings
+pub fn strings(values: &[String]) -> String {
+    let items : Vec<String> = values.iter().map(|v| string(v)).collect();
+    format!("[{}]", items.join(", "))
+}
+
+/// JSON array of numbers
+pub fn numbers<T: ToString>(values: &[T]) -> String {
+    let items : Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    format!("[{}]", items.join(", "))
+}
+
+/// JSON number or null
+pub fn option<T: ToString>(value: &Option<T>) -> String {
+    match value {
+        Some(v) => v.to_string(),
+        None => "null".to_owned(),
+    }
+}
+
+/// JSON string or null
+pub fn option_string(value: &Option<String>) -> String {
+    match value {
+        Some(v) => string(v),
+        None => "null".to_owned(),
+    }
+}
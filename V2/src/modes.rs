@@ -0,0 +1,114 @@
+//! Stimulation modes, for `--list-modes` and `--describe-mode`.
+//!
+//! A mode is a combination of options that selects how the stimulation schedule is generated.
+//! The help on the parameters of a mode is taken from the command line definition, so it can't
+//! get out of sync with the options themselves.
+
+use clap::CommandFactory;
+
+use crate::Arguments;
+
+/// A stimulation mode
+pub struct Mode {
+    pub name: &'static str,
+    pub summary: &'static str,
+    /// Long names of the options that select or tune the mode
+    pub parameters: &'static [&'static str],
+    pub details: &'static str,
+    pub example: &'static str,
+}
+
+/// All stimulation modes
+pub const MODES: &[Mode] = &[
+    Mode {
+        name: "blocked",
+        summary: "One channel at a time, in a new random order every cycle (default)",
+        parameters: &["channels", "stimfreq", "stimduration", "cycleperiod", "ibi", "randomseed"],
+        details: "Every cycle is divided in one slot per channel. Every slot stimulates a single channel, the order of \
+                  the channels is shuffled every cycle, without stimulating the same channel twice in a row.",
+        example: "f2heal-v2 -s 600 --channels 4 --cycleperiod 666 --randomseed 1",
+    },
+    Mode {
+        name: "ordered",
+        summary: "One channel at a time, always in the order 0, 1, 2, ...",
+        parameters: &["norandom"],
+        details: "Like blocked mode, but the channel order is fixed instead of random.",
+        example: "f2heal-v2 -s 600 --norandom",
+    },
+    Mode {
+        name: "jitter",
+        summary: "Blocked mode with randomly shifted stimulation starts",
+        parameters: &["jitter", "clamp-jitter-to-slot"],
+        details: "Every channel but the first one in a cycle starts up to J % of half a slot earlier or later than \
+                  its nominal start. Above the safe jitter stimulations get cut short, unless the jitter is clamped.",
+        example: "f2heal-v2 -s 600 --jitter 20",
+    },
+    Mode {
+        name: "chord",
+        summary: "Several random channels at once in every slot",
+        parameters: &["chord-size"],
+        details: "Every slot stimulates K randomly chosen channels together, each scaled by 1/K so the chord has the \
+                  amplitude of a single stimulation.",
+        example: "f2heal-v2 -s 600 --chord-size 2",
+    },
+    Mode {
+        name: "sequence",
+        summary: "Replay the channels of every slot from a file",
+        parameters: &["sequence-file"],
+        details: "Every line of the file lists the channel(s) of one slot, the sequence loops when exhausted.",
+        example: "f2heal-v2 -s 600 --sequence-file sequence.txt",
+    },
+    Mode {
+        name: "overlap",
+        summary: "Stimulations longer than a slot continue into the next slots",
+        parameters: &["overlap", "stimduration"],
+        details: "Without overlap a stimulation is cut off when the next slot starts. With overlap it continues, \
+                  stimulations overlapping on the same channel are averaged.",
+        example: "f2heal-v2 -s 600 --stimduration 300 --overlap",
+    },
+    Mode {
+        name: "fsk",
+        summary: "Frequency-shift keyed stimulation encoding data",
+        parameters: &["fsk-data", "fsk-freqs", "fsk-stop"],
+        details: "Every stimulation takes the next symbol of the data and is played at that symbol's frequency.",
+        example: "f2heal-v2 -s 60 --fsk-data data.txt --fsk-freqs 150,250 --fsk-stop",
+    },
+];
+
+/// Returns the mode with name
+pub fn find(name: &str) -> Option<&'static Mode> {
+    MODES.iter().find(|mode| mode.name == name)
+}
+
+/// Print every mode with its summary and parameters
+pub fn list_modes() {
+    println!("Stimulation modes (see --describe-mode <name>):");
+
+    for mode in MODES {
+        println!("   {:<10} {}", mode.name, mode.summary);
+        println!("   {:<10} parameters: {}", "", mode.parameters.iter().map(|p| format!("--{}", p)).collect::<Vec<_>>().join(", "));
+    }
+}
+
+/// Print the details of a mode, with the help of its parameters and an example
+pub fn describe_mode(mode: &Mode) {
+    let command = Arguments::command();
+
+    println!("{}: {}", mode.name, mode.summary);
+    println!();
+    println!("{}", mode.details);
+    println!();
+    println!("Parameters:");
+    for parameter in mode.parameters {
+        let help = command.get_arguments()
+            .find(|arg| arg.get_long() == Some(parameter))
+            .and_then(|arg| arg.get_help())
+            .map(|help| help.to_string())
+            .unwrap_or_default();
+
+        println!("   --{:<22} {}", parameter, help);
+    }
+    println!();
+    println!("Example:");
+    println!("   $ {}", mode.example);
+}
@@ -0,0 +1,67 @@
+//! Noise carrier for the stimulations (`--carrier noise`).
+//!
+//! Every channel has its own noise generator, seeded from the random seed on a stream of its own, so the
+//! noise is reproducible from the seed and doesn't change the draws of the schedule. The generator only
+//! advances while its channel is stimulated.
+//!
+//! White noise is uniform in [-1, 1]. Pink noise (-3 dB per octave) filters the white noise with
+//! Paul Kellet's refined filter (accurate to about 0.05 dB above 9.2 Hz at 44100 Hz). Its RMS is about a
+//! third of the white noise, so its peaks stay within [-1, 1] (rare outliers are limited). Both are band
+//! limited by the sample rate only.
+
+use rand::Rng;
+
+use crate::rng::{GenRng, RngKind};
+
+/// Spectrum of the noise carrier
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum NoiseColor {
+    /// Equal power per Hz
+    White,
+    /// Equal power per octave (-3 dB per octave)
+    Pink,
+}
+
+/// First random stream of the noise generators, above the streams of the channels (--channel-seeding)
+const NOISE_STREAM: u64 = 1 << 32;
+
+/// Scale of the pink filter output, keeps the peaks within full scale but for rare outliers
+const PINK_GAIN: f64 = 0.11;
+
+/// Noise generator of one channel
+#[derive(Debug, Clone)]
+pub struct NoiseGen {
+    rng: GenRng,
+    color: NoiseColor,
+    /// State of the pink filter
+    b: [f64; 7],
+}
+
+impl NoiseGen {
+    /// Noise generator of channel for seed
+    pub fn new(kind: RngKind, seed: u64, channel: u32, color: NoiseColor) -> NoiseGen {
+        NoiseGen { rng: GenRng::new(kind, seed, NOISE_STREAM + channel as u64), color, b: [0.0; 7] }
+    }
+
+    /// Next noise sample, in [-1, 1]
+    pub fn next_sample(&mut self) -> f64 {
+        let white = self.rng.gen_range(-1.0..=1.0);
+
+        match self.color {
+            NoiseColor::White => white,
+            NoiseColor::Pink => {
+                let b = &mut self.b;
+                b[0] = 0.99886 * b[0] + white * 0.0555179;
+                b[1] = 0.99332 * b[1] + white * 0.0750759;
+                b[2] = 0.96900 * b[2] + white * 0.1538520;
+                b[3] = 0.86650 * b[3] + white * 0.3104856;
+                b[4] = 0.55000 * b[4] + white * 0.5329522;
+                b[5] = -0.7616 * b[5] - white * 0.0168980;
+                let pink = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+                b[6] = white * 0.115926;
+
+                (pink * PINK_GAIN).clamp(-1.0, 1.0)
+            }
+        }
+    }
+}
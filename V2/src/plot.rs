@@ -0,0 +1,68 @@
+//! Waveform overview of the first cycles as PNG, for a quick visual check of the output.
+//!
+//! Every output channel gets a row with its waveform, drawn as the min/max envelope of the
+//! samples per pixel column, written as an 8-bit grayscale PNG.
+
+use std::fs::File;
+use std::io::{self, BufWriter};
+
+use crate::{Arguments, Render};
+
+/// Width of the image in pixels
+const WIDTH: usize = 1200;
+/// Height of one channel row in pixels
+const ROW_HEIGHT: usize = 48;
+/// Number of cycles shown
+const CYCLES: i64 = 2;
+
+/// Render the first cycles of the output and write the waveform overview to fname
+pub fn write_png(args: &Arguments, fname: &str) -> io::Result<()> {
+    let mut overview = args.clone();
+    overview.verbosity = 0;
+
+    let frames = args.cycles_to_samples(CYCLES) as usize;
+    let channels = args.output_channels() as usize;
+    let samples : Vec<Vec<i32>> = Render::verified(&overview).take(frames).collect();
+
+    let height = ROW_HEIGHT * channels;
+    let mut pixels = vec![255u8; WIDTH * height];
+
+    for channel in 0..channels {
+        let top = channel * ROW_HEIGHT;
+
+        // separator and zero line
+        for x in 0..WIDTH {
+            pixels[top * WIDTH + x] = 160;
+            pixels[(top + ROW_HEIGHT / 2) * WIDTH + x] = 220;
+        }
+
+        for x in 0..WIDTH {
+            let column = &samples[(x * samples.len() / WIDTH).min(samples.len())..((x + 1) * samples.len() / WIDTH).min(samples.len())];
+            if column.is_empty() {
+                continue;
+            }
+
+            let min = column.iter().map(|frame| frame[channel]).min().unwrap();
+            let max = column.iter().map(|frame| frame[channel]).max().unwrap();
+
+            let y = |sample: i32| {
+                let scaled = (i16::MAX as i64 - sample as i64) * (ROW_HEIGHT as i64 - 2) / (2 * i16::MAX as i64 + 1);
+                top + 1 + scaled.clamp(0, ROW_HEIGHT as i64 - 2) as usize
+            };
+
+            for row in y(max)..=y(min) {
+                pixels[row * WIDTH + x] = 0;
+            }
+        }
+    }
+
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(fname)?), WIDTH as u32, height as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels)?;
+    writer.finish()?;
+
+    Ok(())
+}
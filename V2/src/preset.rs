@@ -0,0 +1,441 @@
+//! Named parameter sets (`--preset`, `--list-presets`, `--save-preset`).
+//!
+//! A preset is a TOML file of options by their long name, e.g.
+//!
+//! ```toml
+//! # 4 fingers, 3 cycles on, 2 off
+//! channels = 4
+//! cycleperiod = 666
+//! pauzes = [3, 4]
+//! clamp-jitter-to-slot = true
+//! ```
+//!
+//! Only flat `key = value` lines are read: strings, numbers, booleans and single line arrays, `#` starts a
+//! comment (the first comment line is the summary shown by `--list-presets`). The built-in presets are compiled
+//! in, user presets are `<name>.toml` files in the presets directory (`$XDG_CONFIG_HOME/f2heal/presets` or
+//! `~/.config/f2heal/presets`). A built-in preset can't be redefined by a user preset of the same name.
+//!
+//! A preset is expanded into command line options before the command line is parsed, so it goes through the
+//! same checks as typed options. An option given on the command line replaces the preset's value of that option.
+//! A key that isn't an option is an error, never ignored, with the nearest option name as a suggestion.
+
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, CommandFactory};
+
+use crate::{manifest, Arguments};
+
+/// A preset compiled into the binary
+pub struct Preset {
+    pub name: &'static str,
+    pub summary: &'static str,
+    pub toml: &'static str,
+}
+
+/// The built-in presets
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "vcr-classic",
+        summary: "4 fingers at 250Hz, 1.5Hz cycles, 3 cycles on and 2 off",
+        toml: "channels = 4\nstimfreq = 250\nstimduration = 100\ncycleperiod = 666\npauzecycleperiod = 5\npauzes = [3, 4]\n",
+    },
+    Preset {
+        name: "vcr-jitter",
+        summary: "vcr-classic with 23% jitter of the stimulation starts",
+        toml: "channels = 4\nstimfreq = 250\nstimduration = 100\ncycleperiod = 666\npauzecycleperiod = 5\npauzes = [3, 4]\n\
+               jitter = 23\n",
+    },
+    Preset {
+        name: "vcr-bilateral",
+        summary: "8 fingers (both hands) at 250Hz, 888ms cycles, 3 cycles on and 2 off",
+        toml: "channels = 8\nstimfreq = 250\nstimduration = 100\ncycleperiod = 888\npauzecycleperiod = 5\npauzes = [3, 4]\n",
+    },
+];
+
+/// Options about presets themselves, they can't be part of a preset
+const PRESET_OPTIONS: [&str; 3] = ["preset", "list-presets", "save-preset"];
+
+/// Value of a preset option
+enum Value {
+    Bool(bool),
+    /// The literal text of every value, an array has several
+    Values(Vec<String>),
+}
+
+/// Directory of the user presets
+pub fn presets_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()).map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|config| config.join("f2heal").join("presets"))
+}
+
+/// Names of the user presets, sorted
+fn user_presets() -> Vec<String> {
+    let mut names : Vec<String> = presets_dir()
+        .and_then(|dir| std::fs::read_dir(dir).ok())
+        .map(|entries| entries.filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "toml"))
+            .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+            .collect())
+        .unwrap_or_default();
+
+    names.sort();
+    names
+}
+
+/// The TOML of preset name, built-in or from the presets directory
+fn load(name: &str) -> Result<String, String> {
+    if let Some(preset) = PRESETS.iter().find(|preset| preset.name == name) {
+        return Ok(preset.toml.to_owned());
+    }
+
+    if let Some(path) = presets_dir().map(|dir| dir.join(format!("{}.toml", name))) {
+        if path.exists() {
+            return std::fs::read_to_string(&path).map_err(|e| format!("Cannot read preset {}: {}", path.display(), e));
+        }
+    }
+
+    let mut names : Vec<String> = PRESETS.iter().map(|preset| preset.name.to_owned()).collect();
+    names.extend(user_presets());
+    Err(format!("Unknown preset {}, available: {}", name, names.join(", ")))
+}
+
+/// The first comment line of a preset, as its summary
+fn summary(toml: &str) -> &str {
+    toml.lines().map(|line| line.trim()).find_map(|line| line.strip_prefix('#')).unwrap_or("").trim()
+}
+
+/// Parse a string literal at the start of text, returns its value and the rest of text
+fn parse_string(text: &str) -> Result<(String, &str), String> {
+    let mut value = String::new();
+    let mut chars = text.char_indices().skip(1);
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, &text[i + 1..])),
+            '\\' => match chars.next().map(|(_, c)| c) {
+                Some('"') => value.push('"'),
+                Some('\\') => value.push('\\'),
+                Some('n') => value.push('\n'),
+                Some('r') => value.push('\r'),
+                Some('t') => value.push('\t'),
+                Some('u') => {
+                    let hex : String = (0..4).filter_map(|_| chars.next().map(|(_, c)| c)).collect();
+                    let c = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32)
+                        .ok_or_else(|| format!("invalid escape \\u{}", hex))?;
+                    value.push(c);
+                },
+                c => return Err(format!("invalid escape \\{}", c.map(String::from).unwrap_or_default())),
+            },
+            c => value.push(c),
+        }
+    }
+
+    Err("unterminated string".to_owned())
+}
+
+/// Parse a bare number or boolean at the start of text, up to a separator, returns its text and the rest of text
+fn parse_bare<'a>(text: &'a str, separators: &[char]) -> Result<(&'a str, &'a str), String> {
+    let end = text.find(|c| separators.contains(&c)).unwrap_or(text.len());
+    let bare = text[..end].trim();
+
+    if bare == "true" || bare == "false" || bare.parse::<f64>().is_ok_and(|number| number.is_finite()) {
+        Ok((bare, &text[end..]))
+    } else {
+        Err(format!("expected a string, number, boolean or array: {}", bare))
+    }
+}
+
+/// Only a comment may follow a value
+fn end_of_line(rest: &str) -> Result<(), String> {
+    match rest.trim_start() {
+        rest if rest.is_empty() || rest.starts_with('#') => Ok(()),
+        rest => Err(format!("unexpected text after the value: {}", rest)),
+    }
+}
+
+/// Parse the value of a `key = value` line
+fn parse_value(text: &str) -> Result<Value, String> {
+    let text = text.trim_start();
+
+    if text.starts_with('"') {
+        let (value, rest) = parse_string(text)?;
+        end_of_line(rest)?;
+        Ok(Value::Values(vec![value]))
+    } else if let Some(mut rest) = text.strip_prefix('[') {
+        let mut values = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                end_of_line(after)?;
+                return Ok(Value::Values(values));
+            }
+
+            let (value, after) = if rest.starts_with('"') {
+                parse_string(rest)?
+            } else {
+                parse_bare(rest, &[',', ']']).map(|(value, after)| (value.to_owned(), after))?
+            };
+            values.push(value);
+
+            rest = after.trim_start();
+            rest = rest.strip_prefix(',').unwrap_or(rest);
+            if rest.is_empty() {
+                return Err("unterminated array (arrays must be on one line)".to_owned());
+            }
+        }
+    } else {
+        let (value, rest) = parse_bare(text, &['#'])?;
+        end_of_line(rest)?;
+        Ok(match value {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            number => Value::Values(vec![number.to_owned()]),
+        })
+    }
+}
+
+/// Parse the options of a preset, by long option name (`_` in a key is read as `-`)
+fn parse(toml: &str) -> Result<Vec<(String, Value)>, String> {
+    let mut options : Vec<(String, Value)> = Vec::new();
+
+    for (number, line) in toml.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let in_line = |e: String| format!("line {}: {}", number + 1, e);
+        if line.starts_with('[') {
+            return Err(in_line("tables are not supported, options must be top level".to_owned()));
+        }
+        let (key, value) = line.split_once('=').ok_or_else(|| in_line("expected key = value".to_owned()))?;
+        let key = key.trim().replace('_', "-");
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(in_line(format!("invalid key {}", key)));
+        }
+        if options.iter().any(|(name, _)| *name == key) {
+            return Err(in_line(format!("duplicate key {}", key)));
+        }
+
+        options.push((key, parse_value(value).map_err(in_line)?));
+    }
+
+    Ok(options)
+}
+
+/// Number of single character insertions, deletions and substitutions between a and b (Levenshtein distance)
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b : Vec<char> = b.chars().collect();
+    let mut row : Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + (ca != *cb) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+/// The option a preset can set that is nearest to the unknown key, if it is near enough to be a typo
+fn suggestion(command: &clap::Command, key: &str) -> Option<String> {
+    command.get_arguments()
+        .filter_map(|arg| arg.get_long())
+        .filter(|long| !PRESET_OPTIONS.contains(long))
+        .map(|long| (edit_distance(key, long), long))
+        .filter(|(distance, _)| *distance <= (key.chars().count() / 3).max(2))
+        .min()
+        .map(|(_, long)| long.to_owned())
+}
+
+/// Check that every key of the options is an option a preset can set, the error names all unknown keys
+fn check_keys(command: &clap::Command, options: &[(String, Value)]) -> Result<(), String> {
+    let unknown : Vec<String> = options.iter()
+        .map(|(key, _)| key)
+        .filter(|key| PRESET_OPTIONS.contains(&key.as_str()) || !command.get_arguments().any(|arg| arg.get_long() == Some(key.as_str())))
+        .map(|key| match suggestion(command, key) {
+            Some(option) => format!("{} (did you mean {}?)", key, option),
+            None => key.clone(),
+        })
+        .collect();
+
+    match unknown.len() {
+        0 => Ok(()),
+        1 => Err(format!("unknown option {}", unknown[0])),
+        _ => Err(format!("unknown options {}", unknown.join(", "))),
+    }
+}
+
+/// Whether the command line (without the program name) gives the option
+fn on_command_line(arg: &clap::Arg, command_line: &[OsString]) -> bool {
+    let long = arg.get_long().map(|long| format!("--{}", long));
+    let short = arg.get_short().map(|short| format!("-{}", short));
+
+    command_line.iter().map(|token| token.to_string_lossy()).take_while(|token| token != "--").any(|token| {
+        long.as_ref().is_some_and(|long| token == *long || token.starts_with(&format!("{}=", long)))
+            || short.as_ref().is_some_and(|short| !token.starts_with("--") && token.starts_with(short.as_str()))
+    })
+}
+
+/// Command line options of preset name, leaving out the options in command_line
+fn preset_options(name: &str, command_line: &[OsString]) -> Result<Vec<OsString>, String> {
+    let command = Arguments::command();
+    let options = parse(&load(name)?).map_err(|e| format!("Invalid preset {}, {}", name, e))?;
+    check_keys(&command, &options).map_err(|e| format!("Invalid preset {}, {}", name, e))?;
+    let mut result = Vec::new();
+
+    for (key, value) in options {
+        let arg = command.get_arguments().find(|arg| arg.get_long() == Some(key.as_str())).unwrap();
+        if on_command_line(arg, command_line) {
+            continue;
+        }
+
+        let option = format!("--{}", key);
+        match (arg.get_action(), value) {
+            (ArgAction::SetTrue, Value::Bool(set)) => if set { result.push(option.into()) },
+            (ArgAction::Count, Value::Values(values)) if values.len() == 1 => {
+                let count = values[0].parse::<u8>().map_err(|_| format!("Invalid preset {}, {} must be a count", name, key))?;
+                result.extend((0..count).map(|_| option.clone().into()));
+            },
+            (ArgAction::SetTrue | ArgAction::Count, _) =>
+                return Err(format!("Invalid preset {}, {} must be {}", name, key,
+                    if matches!(arg.get_action(), ArgAction::SetTrue) { "true or false" } else { "a count" })),
+            (_, Value::Bool(set)) => result.push(format!("{}={}", option, set).into()),
+            (_, Value::Values(values)) => match arg.get_value_delimiter() {
+                Some(delimiter) => result.push(format!("{}={}", option, values.join(&delimiter.to_string())).into()),
+                None => result.extend(values.iter().map(|value| format!("{}={}", option, value).into())),
+            },
+        }
+    }
+
+    Ok(result)
+}
+
+/// Insert the options of the preset named on the command line (`--preset NAME`) before the command line options,
+/// returns the command line unchanged without a preset
+pub fn expand(args: Vec<OsString>) -> Result<Vec<OsString>, String> {
+    let command_line = &args[1.min(args.len())..];
+    let mut tokens = command_line.iter().map(|token| token.to_string_lossy()).take_while(|token| token != "--");
+    let name = loop {
+        match tokens.next() {
+            Some(token) if token == "--preset" => break tokens.next().map(|name| name.into_owned()),
+            Some(token) => if let Some(name) = token.strip_prefix("--preset=") { break Some(name.to_owned()) },
+            None => break None,
+        }
+    };
+
+    let Some(name) = name else {
+        return Ok(args);
+    };
+
+    let options = preset_options(&name, command_line)?;
+    let mut result = args[..1.min(args.len())].to_vec();
+    result.extend(options);
+    result.extend(command_line.iter().cloned());
+
+    Ok(result)
+}
+
+/// Print the options of a preset on one line
+fn print_options(toml: &str) {
+    match parse(toml).and_then(|options| check_keys(&Arguments::command(), &options).map(|_| options)) {
+        Ok(options) => {
+            let options : Vec<String> = options.iter().map(|(key, value)| match value {
+                Value::Bool(set) => format!("--{}={}", key, set),
+                Value::Values(values) => format!("--{} {}", key, values.join(",")),
+            }).collect();
+            println!("   {:<16} {}", "", options.join(" "));
+        },
+        Err(e) => println!("   {:<16} invalid: {}", "", e),
+    }
+}
+
+/// Print the built-in and user presets with their summary and options
+pub fn list_presets() {
+    println!("Presets (see --preset <name>):");
+    for preset in PRESETS {
+        println!("   {:<16} {}", preset.name, preset.summary);
+        print_options(preset.toml);
+    }
+
+    let dir = presets_dir().map_or("(no presets directory, HOME is not set)".to_owned(), |dir| dir.display().to_string());
+    let names = user_presets();
+    println!();
+    println!("User presets in {}:", dir);
+    if names.is_empty() {
+        println!("   (none)");
+    }
+    for name in names {
+        if PRESETS.iter().any(|preset| preset.name == name) {
+            println!("   {:<16} (hidden by the built-in preset)", name);
+            continue;
+        }
+        match load(&name) {
+            Ok(toml) => {
+                println!("   {:<16} {}", name, summary(&toml));
+                print_options(&toml);
+            },
+            Err(e) => println!("   {:<16} {}", name, e),
+        }
+    }
+}
+
+/// TOML value of the raw command line values of an option
+fn toml_value(values: &[String]) -> String {
+    let items : Vec<String> = values.iter().map(|value| match value.parse::<f64>() {
+        Ok(number) if number.is_finite() => value.clone(),
+        _ => manifest::string(value),
+    }).collect();
+
+    if items.len() == 1 { items[0].clone() } else { format!("[{}]", items.join(", ")) }
+}
+
+/// Save the options given on the command line (including those of a preset) as user preset name, returns the
+/// file written. An existing preset is not replaced
+pub fn save(name: &str, matches: &ArgMatches) -> Result<PathBuf, String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("Invalid preset name {}, use letters, digits, - and _", name));
+    }
+    if PRESETS.iter().any(|preset| preset.name == name) {
+        return Err(format!("Cannot save preset {}, it is a built-in preset", name));
+    }
+
+    let dir = presets_dir().ok_or_else(|| "Cannot save the preset, HOME is not set".to_owned())?;
+    let path = dir.join(format!("{}.toml", name));
+    if path.exists() {
+        return Err(format!("Preset {} exists, remove it first", path.display()));
+    }
+
+    let mut toml = format!("# Saved with --save-preset {}\n", name);
+    for arg in Arguments::command().get_arguments() {
+        let (id, Some(long)) = (arg.get_id().as_str(), arg.get_long()) else { continue };
+        if PRESET_OPTIONS.contains(&long) || matches.value_source(id) != Some(ValueSource::CommandLine) {
+            continue;
+        }
+
+        let value = match arg.get_action() {
+            ArgAction::SetTrue => matches.get_flag(id).to_string(),
+            ArgAction::Count => matches.get_count(id).to_string(),
+            _ => {
+                let values : Vec<String> = matches.get_raw(id).into_iter().flatten()
+                    .map(|value| value.to_string_lossy().into_owned()).collect();
+                toml_value(&values)
+            },
+        };
+        toml.push_str(&format!("{} = {}\n", long, value));
+    }
+
+    std::fs::create_dir_all(&dir)
+        .and_then(|_| std::fs::write(&path, toml))
+        .map_err(|e| format!("Cannot write preset {}: {}", path.display(), e))?;
+
+    Ok(path)
+}
@@ -0,0 +1,96 @@
+//! Transforms on an existing FLAC file, for when the output can't be regenerated (e.g. the
+//! seed is lost).
+//!
+//! The input is decoded completely, the transforms are applied in a fixed order and the
+//! result is encoded as 16 bit FLAC again:
+//!
+//! 1. trim: keep only the samples from start to end (in seconds),
+//! 2. remap: output channel i takes input channel `remap[i]`, so channels can be reordered,
+//!    duplicated or dropped,
+//! 3. gain: scale all samples by a gain in dB, clipped to full scale.
+
+use std::fs::File;
+use std::io;
+
+use crate::{decode, parse_pair};
+
+/// Transforms applied to an existing file
+pub struct Transforms {
+    pub gain: Option<f64>,
+    pub trim: Option<(f64, f64)>,
+    pub remap: Vec<u32>,
+}
+
+/// Returns the filename for the processed version of fname
+pub fn processed_fname(fname: &str) -> String {
+    match fname.strip_suffix(".flac") {
+        Some(stem) => format!("{}-PROCESSED.flac", stem),
+        None => format!("{}-PROCESSED.flac", fname),
+    }
+}
+
+/// Parse a trim range "start:end" in seconds
+pub fn parse_trim(trim: &str) -> Result<(f64, f64), String> {
+    let (start, end) : (f64, f64) = parse_pair(trim, "start:end")?;
+
+    if start < 0.0 || end <= start {
+        return Err(format!("end must come after start: {}", trim));
+    }
+
+    Ok((start, end))
+}
+
+/// Apply the transforms to the FLAC file fname_in and write the result to fname_out, returns the
+/// number of samples written
+pub fn process(fname_in: &str, fname_out: &str, transforms: &Transforms) -> io::Result<usize> {
+    let decoded = decode::decode(fname_in)?;
+    let channels = decoded.channels as usize;
+
+    let mut frames : Vec<&[i32]> = decoded.samples.chunks(channels).collect();
+
+    if let Some((start, end)) = transforms.trim {
+        let first = ((start * decoded.samplerate as f64) as usize).min(frames.len());
+        let last = ((end * decoded.samplerate as f64) as usize).min(frames.len());
+        frames = frames[first..last].to_vec();
+    }
+
+    let remap : Vec<usize> = if transforms.remap.is_empty() {
+        (0..channels).collect()
+    } else {
+        transforms.remap.iter().map(|channel| *channel as usize).collect()
+    };
+
+    if let Some(channel) = remap.iter().find(|channel| **channel >= channels) {
+        return Err(io::Error::other(format!("remap refers to channel {}, the input has {} channels", channel, channels)));
+    }
+
+    if remap.len() > crate::FLAC_MAX_CHANNELS as usize {
+        return Err(io::Error::other(format!("remap gives {} channels, FLAC supports at most {}", remap.len(), crate::FLAC_MAX_CHANNELS)));
+    }
+
+    let gain = 10f64.powf(transforms.gain.unwrap_or(0.0) / 20.0);
+
+    let mut outfile = File::create(fname_out)?;
+    let mut outwrap = flac_bound::WriteWrapper(&mut outfile);
+    let mut encoder = flac_bound::FlacEncoder::new().unwrap()
+        .channels(remap.len() as u32)
+        .bits_per_sample(16)
+        .sample_rate(decoded.samplerate)
+        .total_samples_estimate(frames.len() as u64)
+        .compression_level(8)
+        .init_write(&mut outwrap)
+        .map_err(|e| io::Error::other(format!("cannot create FLAC encoder: {:?}", e)))?;
+
+    for frame in frames.iter() {
+        let sample : Vec<i32> = remap.iter()
+            .map(|channel| (frame[*channel] as f64 * gain).clamp(i16::MIN as f64, i16::MAX as f64) as i32)
+            .collect();
+
+        encoder.process_interleaved(&sample, 1)
+            .map_err(|_| io::Error::other("cannot encode FLAC output"))?;
+    }
+
+    encoder.finish().map_err(|_| io::Error::other("cannot finish FLAC output"))?;
+
+    Ok(frames.len())
+}
@@ -0,0 +1,75 @@
+//! Machine readable progress of a render (`--progress-json`).
+//!
+//! A wrapping program (e.g. a GUI) reads one JSON object per line from stderr:
+//!
+//! ```json
+//! {"frames_done": 441000, "frames_total": 79380000, "percent": 0.6, "eta_seconds": 35.2}
+//! ```
+//!
+//! A line is written when the render starts, at most every `INTERVAL` while it runs and when it ends. The ETA is
+//! extrapolated from the rate so far, null before the first frame. The total is an estimate when the output
+//! ends after a number of stimulations, the percentage then stays below 100 until the last line.
+
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+/// Shortest time between two progress lines
+pub const INTERVAL: Duration = Duration::from_millis(500);
+
+/// Frames between two looks at the clock
+const CHECK_FRAMES: i64 = 1024;
+
+/// Progress of a render of frames_total frames
+pub struct Progress {
+    frames_total: i64,
+    start: Instant,
+    last: Instant,
+}
+
+impl Progress {
+    /// Start reporting the progress of a render, writes the first line
+    pub fn start(frames_total: i64) -> Progress {
+        let now = Instant::now();
+        let progress = Progress { frames_total, start: now, last: now };
+        progress.write(0, false);
+
+        progress
+    }
+
+    /// Report frames_done, if the last line is at least INTERVAL ago
+    pub fn update(&mut self, frames_done: i64) {
+        if frames_done % CHECK_FRAMES != 0 || self.last.elapsed() < INTERVAL {
+            return;
+        }
+
+        self.last = Instant::now();
+        self.write(frames_done, false);
+    }
+
+    /// Report the end of the render after frames_done, which is then the total
+    pub fn finish(self, frames_done: i64) {
+        self.write(frames_done, true);
+    }
+
+    /// Write a progress line to stderr
+    fn write(&self, frames_done: i64, done: bool) {
+        let frames_total = if done { frames_done } else { self.frames_total.max(frames_done) };
+        let percent = match (done, frames_total) {
+            (true, _) => 100.0,
+            (false, 0) => 0.0,
+            (false, _) => (100.0 * frames_done as f64 / frames_total as f64).min(99.9),
+        };
+        let eta = match (done, frames_done) {
+            (true, _) => "0".to_owned(),
+            (false, 0) => "null".to_owned(),
+            (false, _) => {
+                let seconds = self.start.elapsed().as_secs_f64() * (frames_total - frames_done) as f64 / frames_done as f64;
+                format!("{:.1}", seconds)
+            }
+        };
+
+        // a closed stderr must not end the render
+        let _ = writeln!(std::io::stderr(), "{{\"frames_done\": {}, \"frames_total\": {}, \"percent\": {:.1}, \"eta_seconds\": {}}}",
+            frames_done, frames_total, percent, eta);
+    }
+}
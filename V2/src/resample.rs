@@ -0,0 +1,158 @@
+//! Sample rate conversion of the rendered output (`--synth-rate`).
+//!
+//! The schedule is synthesized at the synthesis rate and converted to the output rate just before
+//! encoding, by band limited interpolation with a windowed sinc (Blackman window, 16 zero crossings
+//! on either side). The filter passes up to 90 % of the Nyquist frequency of the lower of both
+//! rates and is normalized to unity gain at DC.
+//!
+//! The filter rings around sharp edges: a stimulation starting or stopping without a ramp, and the
+//! square trigger pulse, get small ripples before and after the edge. Use an envelope with attack
+//! and release ramps when resampling.
+
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::io;
+
+use crate::sink::Sink;
+
+/// Zero crossings of the sinc on either side of the interpolated point
+const ZERO_CROSSINGS: f64 = 16.0;
+
+/// Passband as fraction of the Nyquist frequency of the lower rate
+pub const PASSBAND: f64 = 0.9;
+
+/// Most fractional positions with a filter of their own, a position in between takes the nearest
+const MAX_PHASES: u64 = 4096;
+
+/// Windowed sinc at x input samples from the interpolated point, with the cutoff relative to the input
+/// Nyquist frequency and the window half width in input samples
+fn kernel(x: f64, cutoff: f64, half: f64) -> f64 {
+    if x.abs() >= half {
+        return 0.0;
+    }
+
+    let sinc = if x == 0.0 { 1.0 } else { (PI * cutoff * x).sin() / (PI * cutoff * x) };
+    let window = 0.42 + 0.5 * (PI * x / half).cos() + 0.08 * (2.0 * PI * x / half).cos();
+
+    sinc * window
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// Converts the frames to another sample rate before passing them on to the destination
+pub struct ResampleSink<'out> {
+    inner: Box<dyn Sink + 'out>,
+    channels: usize,
+    from: u64,
+    to: u64,
+    half: i64,
+    /// Filter taps of every fractional position, for input samples base - half + 1 ..= base + half
+    filters: Vec<Vec<f64>>,
+    /// The input frames still needed, the first one is input sample `first`
+    frames: VecDeque<Vec<i32>>,
+    first: i64,
+    received: u64,
+    written: u64,
+}
+
+impl<'out> ResampleSink<'out> {
+    pub fn new(inner: Box<dyn Sink + 'out>, channels: u32, from: u32, to: u32) -> ResampleSink<'out> {
+        let (from, to) = (from as u64, to as u64);
+        let cutoff = PASSBAND * (to as f64 / from as f64).min(1.0);
+        let half = (ZERO_CROSSINGS / cutoff).ceil() as i64;
+        let phases = (to / gcd(from, to)).min(MAX_PHASES);
+
+        let filters = (0..phases)
+            .map(|phase| {
+                let frac = phase as f64 / phases as f64;
+                let taps : Vec<f64> = (-half + 1..=half)
+                    .map(|n| kernel(n as f64 - frac, cutoff, half as f64))
+                    .collect();
+                let sum : f64 = taps.iter().sum();
+
+                taps.iter().map(|tap| tap / sum).collect()
+            })
+            .collect();
+
+        ResampleSink {
+            inner,
+            channels: channels as usize,
+            from,
+            to,
+            half,
+            filters,
+            frames: VecDeque::new(),
+            first: 0,
+            received: 0,
+            written: 0,
+        }
+    }
+
+    /// Input sample at or before output sample k, with the filter for the fraction in between
+    fn position(&self, k: u64) -> (i64, usize) {
+        let phases = self.filters.len() as u64;
+        let base = k * self.from / self.to;
+        let phase = ((k * self.from % self.to) * phases + self.to / 2) / self.to;
+
+        if phase == phases {
+            (base as i64 + 1, 0)
+        } else {
+            (base as i64, phase as usize)
+        }
+    }
+
+    /// Interpolate output sample k, input samples that are not (yet) received count as silence
+    fn interpolate(&mut self, k: u64) -> Vec<i32> {
+        let (base, phase) = self.position(k);
+        let mut frame = vec![0.0; self.channels];
+
+        for (tap, n) in self.filters[phase].iter().zip(base - self.half + 1..) {
+            if let Some(input) = usize::try_from(n - self.first).ok().and_then(|i| self.frames.get(i)) {
+                for (out, sample) in frame.iter_mut().zip(input) {
+                    *out += tap * *sample as f64;
+                }
+            }
+        }
+
+        // the frames before this filter are no longer needed
+        while self.first < base - self.half + 1 && !self.frames.is_empty() {
+            self.frames.pop_front();
+            self.first += 1;
+        }
+
+        frame.iter().map(|sample| sample.round().clamp(i16::MIN as f64, i16::MAX as f64) as i32).collect()
+    }
+
+    /// Write the output samples whose filter only needs the received input
+    fn flush(&mut self) -> io::Result<()> {
+        while self.position(self.written).0 + self.half < self.received as i64 {
+            let frame = self.interpolate(self.written);
+            self.inner.write_frame(&frame)?;
+            self.written += 1;
+        }
+
+        Ok(())
+    }
+}
+
+impl Sink for ResampleSink<'_> {
+    fn write_frame(&mut self, frame: &[i32]) -> io::Result<()> {
+        self.frames.push_back(frame.to_vec());
+        self.received += 1;
+
+        self.flush()
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        // the output covers the same duration as the input
+        while self.written * self.from < self.received * self.to {
+            let frame = self.interpolate(self.written);
+            self.inner.write_frame(&frame)?;
+            self.written += 1;
+        }
+
+        self.inner.finish()
+    }
+}
@@ -0,0 +1,250 @@
+//! Selectable random number generators.
+//!
+//! ChaCha8 is the default. ChaCha20 trades speed for cryptographic strength, Xoshiro256++ and
+//! PCG32 are fast non-cryptographic generators, implemented here to their reference algorithms
+//! so their streams stay stable and match other implementations. Every generator supports
+//! independent streams for the same seed (used for the per-channel streams).
+//!
+//! The shuffle of `rand` may draw differently in a new version of the crate, `shuffle` is a
+//! Fisher-Yates pinned to this crate so a seed keeps giving the same channel orders.
+
+use rand::{RngCore, SeedableRng};
+use rand_chacha::{ChaCha20Rng, ChaCha8Rng};
+
+/// Random number generator algorithm
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum RngKind {
+    Chacha8,
+    Chacha20,
+    Xoshiro,
+    Pcg,
+}
+
+/// Random number generator of the selected algorithm
+#[derive(Debug, Clone)]
+pub enum GenRng {
+    ChaCha8(ChaCha8Rng),
+    ChaCha20(ChaCha20Rng),
+    Xoshiro(Xoshiro256PlusPlus),
+    Pcg(Pcg32),
+}
+
+impl GenRng {
+    /// Generator of kind for seed, on stream (0 is the main stream)
+    pub fn new(kind: RngKind, seed: u64, stream: u64) -> GenRng {
+        match kind {
+            RngKind::Chacha8 => {
+                let mut rng = ChaCha8Rng::seed_from_u64(seed);
+                if stream != 0 {
+                    rng.set_stream(stream);
+                }
+                GenRng::ChaCha8(rng)
+            }
+            RngKind::Chacha20 => {
+                let mut rng = ChaCha20Rng::seed_from_u64(seed);
+                if stream != 0 {
+                    rng.set_stream(stream);
+                }
+                GenRng::ChaCha20(rng)
+            }
+            RngKind::Xoshiro => GenRng::Xoshiro(Xoshiro256PlusPlus::new(seed, stream)),
+            RngKind::Pcg => GenRng::Pcg(Pcg32::new(seed, stream)),
+        }
+    }
+
+    /// Discard the next draws u64 outputs, so the stream continues where a generator warmed up the same way does
+    pub fn warm_up(&mut self, draws: u64) {
+        for _ in 0..draws {
+            self.next_u64();
+        }
+    }
+}
+
+impl RngCore for GenRng {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            GenRng::ChaCha8(rng) => rng.next_u32(),
+            GenRng::ChaCha20(rng) => rng.next_u32(),
+            GenRng::Xoshiro(rng) => rng.next_u32(),
+            GenRng::Pcg(rng) => rng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            GenRng::ChaCha8(rng) => rng.next_u64(),
+            GenRng::ChaCha20(rng) => rng.next_u64(),
+            GenRng::Xoshiro(rng) => rng.next_u64(),
+            GenRng::Pcg(rng) => rng.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match self {
+            GenRng::ChaCha8(rng) => rng.fill_bytes(dest),
+            GenRng::ChaCha20(rng) => rng.fill_bytes(dest),
+            GenRng::Xoshiro(rng) => rng.fill_bytes(dest),
+            GenRng::Pcg(rng) => rng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Version of `shuffle`, recorded in the manifest. Bump it whenever `shuffle` draws differently
+pub const SHUFFLE_VERSION: u32 = 1;
+
+/// Uniform index in 0..n from one or more u64 draws, rejecting the draws below 2^64 mod n so
+/// there is no modulo bias
+fn uniform_index<R: RngCore>(rng: &mut R, n: u64) -> u64 {
+    let threshold = n.wrapping_neg() % n;
+
+    loop {
+        let x = rng.next_u64();
+        if x >= threshold {
+            return x % n;
+        }
+    }
+}
+
+/// Fisher-Yates shuffle, from the last element down, each element swapped with a uniformly drawn
+/// element at or before it
+pub fn shuffle<T, R: RngCore>(slice: &mut [T], rng: &mut R) {
+    for i in (1..slice.len()).rev() {
+        let j = uniform_index(rng, i as u64 + 1) as usize;
+        slice.swap(i, j);
+    }
+}
+
+/// Fill dest with the little endian bytes of successive u64 values
+fn fill_bytes_via_u64<R: RngCore>(rng: &mut R, dest: &mut [u8]) {
+    for chunk in dest.chunks_mut(8) {
+        let bytes = rng.next_u64().to_le_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
+
+/// SplitMix64 step, to expand a seed into generator state
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Xoshiro256++ (Blackman and Vigna), the state seeded with SplitMix64
+#[derive(Debug, Clone)]
+pub struct Xoshiro256PlusPlus {
+    s: [u64; 4],
+}
+
+impl Xoshiro256PlusPlus {
+    /// Generator for seed, a stream is started from the seed mixed with the stream number
+    pub fn new(seed: u64, stream: u64) -> Xoshiro256PlusPlus {
+        let mut state = seed ^ stream.wrapping_mul(0xd134_2543_de82_ef95);
+
+        Xoshiro256PlusPlus { s: [(); 4].map(|_| splitmix64(&mut state)) }
+    }
+}
+
+impl RngCore for Xoshiro256PlusPlus {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let s = &mut self.s;
+        let result = s[0].wrapping_add(s[3]).rotate_left(23).wrapping_add(s[0]);
+        let t = s[1] << 17;
+
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+
+        result
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        fill_bytes_via_u64(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// PCG32 (PCG-XSH-RR 64/32, O'Neill), seeded like the reference pcg32_srandom
+#[derive(Debug, Clone)]
+pub struct Pcg32 {
+    state: u64,
+    inc: u64,
+}
+
+impl Pcg32 {
+    const MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+
+    /// Generator for seed, the stream selects the increment
+    pub fn new(seed: u64, stream: u64) -> Pcg32 {
+        let mut pcg = Pcg32 { state: 0, inc: (stream << 1) | 1 };
+
+        pcg.next_u32();
+        pcg.state = pcg.state.wrapping_add(seed);
+        pcg.next_u32();
+
+        pcg
+    }
+}
+
+impl RngCore for Pcg32 {
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(Self::MULTIPLIER).wrapping_add(self.inc);
+
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let low = self.next_u32() as u64;
+        let high = self.next_u32() as u64;
+        (high << 32) | low
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        fill_bytes_via_u64(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Permutation of 0..8 by the in-crate shuffle (version 1) with ChaCha8 seed 42
+    const GOLDEN_SHUFFLE: [u32; 8] = [5, 3, 2, 6, 7, 4, 0, 1];
+
+    /// The permutation for a known seed must never change without bumping SHUFFLE_VERSION
+    #[test]
+    fn shuffle_is_pinned() {
+        let mut rng = GenRng::new(RngKind::Chacha8, 42, 0);
+        let mut permutation : Vec<u32> = (0..8).collect();
+        shuffle(&mut permutation, &mut rng);
+
+        assert_eq!(SHUFFLE_VERSION, 1);
+        assert_eq!(permutation, GOLDEN_SHUFFLE);
+    }
+}
@@ -0,0 +1,88 @@
+//! Amplitude scale of the session level changes (`--amplitude-scale`): the session fade and the staircase
+//! give a level between 0 and 1, the scale maps it to the gain, so equal steps of the level can be equal steps
+//! of perceived intensity instead of amplitude.
+//!
+//! * `linear`: the gain is the level (the default)
+//! * `log`: the level is linear in dB over `LOG_RANGE_DB` below full scale, 0 stays silent
+//! * `power:EXP`: the gain is the level to the power EXP, e.g. to invert a power law of perceived intensity
+//!
+//! Every scale maps 0 to 0 and 1 to 1.
+
+/// Range in dB of the log scale, from the level just above 0 to full scale
+pub const LOG_RANGE_DB: f64 = 40.0;
+
+/// Mapping of a level to a gain
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AmplitudeScale {
+    Linear,
+    Log,
+    Power(f64),
+}
+
+impl AmplitudeScale {
+    /// Gain of level (0 to 1)
+    pub fn gain(&self, level: f64) -> f64 {
+        let level = level.clamp(0.0, 1.0);
+
+        match self {
+            AmplitudeScale::Linear => level,
+            AmplitudeScale::Log if level == 0.0 => 0.0,
+            AmplitudeScale::Log => 10f64.powf(LOG_RANGE_DB * (level - 1.0) / 20.0),
+            AmplitudeScale::Power(exp) => level.powf(*exp),
+        }
+    }
+
+    /// Name of the scale, as on the command line
+    pub fn name(&self) -> String {
+        match self {
+            AmplitudeScale::Linear => "linear".to_owned(),
+            AmplitudeScale::Log => "log".to_owned(),
+            AmplitudeScale::Power(exp) => format!("power:{}", exp),
+        }
+    }
+}
+
+/// Parse an amplitude scale: linear, log or power:EXP with EXP above 0
+pub fn parse(scale: &str) -> Result<AmplitudeScale, String> {
+    match scale.split_once(':') {
+        None if scale == "linear" => Ok(AmplitudeScale::Linear),
+        None if scale == "log" => Ok(AmplitudeScale::Log),
+        Some(("power", exp)) => match exp.trim().parse::<f64>() {
+            Ok(exp) if exp > 0.0 && exp.is_finite() => Ok(AmplitudeScale::Power(exp)),
+            _ => Err(format!("invalid exponent, expected a number above 0: {}", exp)),
+        },
+        _ => Err(format!("expected linear, log or power:EXP: {}", scale)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gains of the levels 0, 0.5 and 1.0 on every amplitude scale: log is 20dB down at half level
+    const SCALE_GAINS: [(&str, [f64; 3]); 4] = [
+        ("linear", [0.0, 0.5, 1.0]),
+        ("log", [0.0, 0.1, 1.0]),
+        ("power:2", [0.0, 0.25, 1.0]),
+        ("power:0.5", [0.0, std::f64::consts::FRAC_1_SQRT_2, 1.0]),
+    ];
+
+    #[test]
+    fn scales_map_known_levels() {
+        for (name, expected) in SCALE_GAINS {
+            let scale = parse(name).unwrap();
+            let gains = [0.0, 0.5, 1.0].map(|level| scale.gain(level));
+
+            assert_eq!(scale.name(), name);
+            assert!(gains.iter().zip(expected).all(|(gain, expected)| (gain - expected).abs() <= 1e-12),
+                "amplitude scale {} gives {:?}, expected {:?}", name, gains, expected);
+        }
+    }
+
+    #[test]
+    fn invalid_scales_are_refused() {
+        for scale in ["cubic", "power:0", "power:-1", "power:x"] {
+            assert!(parse(scale).is_err(), "{} accepted", scale);
+        }
+    }
+}
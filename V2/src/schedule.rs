@@ -0,0 +1,177 @@
+//! Dense schedule export (`--dense-schedule`): for every sample, or every Nth, which channels are stimulated and
+//! at what level, as ground truth to correlate with recordings.
+//!
+//! The file is binary, little endian. A header is followed by runs of identical records:
+//!
+//! | field      | type     | |
+//! |------------|----------|-|
+//! | magic      | 4 bytes  | `F2DS` |
+//! | version    | u8       | 1 |
+//! | channels   | u8       | stimulation channels |
+//! | samplerate | u32      | Hz, of the schedule (the synthesis rate with `--synth-rate`) |
+//! | downsample | u32      | a record every `downsample` samples |
+//!
+//! Every run is a u32 count of consecutive records, a u16 mask of the stimulated channels (bit c for
+//! channel c) and a u16 level for every channel. Record k holds sample `k * downsample`. The level is the
+//! envelope of the stimulation relative to full scale (envelope, amplitude jitter, chord gain, emphasis,
+//! fades and staircase, without the carrier) times 65535, 0 for a channel that isn't stimulated. During a
+//! pauze or intercycle gap no channel is stimulated. Without envelope ramps the records only change at the
+//! start and end of a stimulation, so the runs keep the file small.
+//!
+//! `read` loads a file back, see `Schedule`.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+/// File signature
+pub const MAGIC: &[u8; 4] = b"F2DS";
+
+/// Format version
+pub const VERSION: u8 = 1;
+
+/// Stimulation state of all channels at one sample
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    /// Bit c is set when channel c is stimulated
+    pub mask: u16,
+    /// Level of every channel relative to full scale, times 65535
+    pub levels: Vec<u16>,
+}
+
+impl Record {
+    /// Record of the levels of the channels, None for a channel that isn't stimulated
+    pub fn new(levels: &[Option<f64>]) -> Record {
+        Record {
+            mask: levels.iter().enumerate().filter(|(_, level)| level.is_some()).map(|(channel, _)| 1 << channel).sum(),
+            levels: levels.iter().map(|level| (level.unwrap_or(0.0).clamp(0.0, 1.0) * u16::MAX as f64).round() as u16).collect(),
+        }
+    }
+
+    /// Whether channel is stimulated
+    pub fn active(&self, channel: u32) -> bool {
+        self.mask & (1 << channel) != 0
+    }
+
+    /// Level of channel relative to full scale
+    pub fn level(&self, channel: u32) -> f64 {
+        self.levels[channel as usize] as f64 / u16::MAX as f64
+    }
+}
+
+/// Writes the schedule sample by sample
+pub struct ScheduleWriter {
+    out: BufWriter<File>,
+    downsample: u32,
+    sample: u64,
+    run: Option<(u32, Record)>,
+}
+
+impl ScheduleWriter {
+    pub fn create(path: &str, channels: u32, samplerate: u32, downsample: u32) -> io::Result<ScheduleWriter> {
+        let mut out = BufWriter::new(File::create(path)?);
+
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION, channels as u8])?;
+        out.write_all(&samplerate.to_le_bytes())?;
+        out.write_all(&downsample.to_le_bytes())?;
+
+        Ok(ScheduleWriter { out, downsample, sample: 0, run: None })
+    }
+
+    /// Pass the levels of the channels at the next sample, None for a channel that isn't stimulated
+    pub fn write(&mut self, levels: &[Option<f64>]) -> io::Result<()> {
+        let sample = self.sample;
+        self.sample += 1;
+
+        if !sample.is_multiple_of(self.downsample as u64) {
+            return Ok(());
+        }
+
+        let record = Record::new(levels);
+        match &mut self.run {
+            Some((count, current)) if *current == record && *count < u32::MAX => *count += 1,
+            _ => {
+                self.flush()?;
+                self.run = Some((1, record));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write the pending run
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some((count, record)) = self.run.take() {
+            self.out.write_all(&count.to_le_bytes())?;
+            self.out.write_all(&record.mask.to_le_bytes())?;
+            for level in record.levels.iter() {
+                self.out.write_all(&level.to_le_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write the remaining records and close the file
+    pub fn finish(mut self) -> io::Result<()> {
+        self.flush()?;
+        self.out.flush()
+    }
+}
+
+/// Schedule read back from a file, with the runs expanded to a record per recorded sample
+pub struct Schedule {
+    pub channels: u32,
+    pub samplerate: u32,
+    pub downsample: u32,
+    pub records: Vec<Record>,
+}
+
+impl Schedule {
+    /// Record at sample, the nearest recorded sample at or before it
+    pub fn at(&self, sample: u64) -> Option<&Record> {
+        self.records.get((sample / self.downsample as u64) as usize)
+    }
+}
+
+fn read_u16(input: &mut impl Read) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    input.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Read a dense schedule file
+pub fn read(path: &str) -> io::Result<Schedule> {
+    let mut input = BufReader::new(File::open(path)?);
+
+    let mut header = [0; 6];
+    input.read_exact(&mut header)?;
+    if &header[..4] != MAGIC || header[4] != VERSION {
+        return Err(io::Error::other(format!("{} is not a dense schedule of version {}", path, VERSION)));
+    }
+
+    let channels = header[5] as u32;
+    let samplerate = read_u32(&mut input)?;
+    let downsample = read_u32(&mut input)?;
+    let mut records = Vec::new();
+
+    loop {
+        let count = match read_u32(&mut input) {
+            Ok(count) => count,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        };
+        let mask = read_u16(&mut input)?;
+        let levels = (0..channels).map(|_| read_u16(&mut input)).collect::<io::Result<Vec<u16>>>()?;
+
+        records.extend(std::iter::repeat_n(Record { mask, levels }, count as usize));
+    }
+
+    Ok(Schedule { channels, samplerate, downsample, records })
+}
@@ -0,0 +1,345 @@
+//! Destinations of the rendered output.
+//!
+//! The render loop passes every frame (one sample of every channel, interleaved) to a `Sink`,
+//! so the generation does not depend on where the output goes. A new output format or target
+//! only needs its own implementation of the trait.
+
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::ptr;
+
+use flac_sys::*;
+
+/// Destination of rendered frames
+pub trait Sink {
+    /// Write one frame, a sample of every channel
+    fn write_frame(&mut self, frame: &[i32]) -> io::Result<()>;
+
+    /// Write the remaining output and close the destination
+    fn finish(self: Box<Self>) -> io::Result<()>;
+
+    /// Whether the sink takes the frames before the conversion to integers, with `write_float_frame`
+    fn takes_floats(&self) -> bool {
+        false
+    }
+
+    /// Write one frame relative to full scale, before the conversion to integers. Only for sinks that take floats
+    fn write_float_frame(&mut self, _frame: &[f64]) -> io::Result<()> {
+        Err(io::Error::other("the output takes integer samples"))
+    }
+}
+
+/// Frames kept in memory as interleaved samples, to test what the render loop writes
+#[cfg(test)]
+pub struct VecSink<'v> {
+    samples: &'v mut Vec<i32>,
+}
+
+#[cfg(test)]
+impl<'v> VecSink<'v> {
+    pub fn new(samples: &'v mut Vec<i32>) -> VecSink<'v> {
+        VecSink { samples }
+    }
+}
+
+#[cfg(test)]
+impl Sink for VecSink<'_> {
+    fn write_frame(&mut self, frame: &[i32]) -> io::Result<()> {
+        self.samples.extend_from_slice(frame);
+
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// FLAC encoder, the frames are passed to libFLAC per buffer to save calls
+pub struct FlacSink<'out> {
+    encoder: flac_bound::FlacEncoder<'out>,
+    buffer: Vec<i32>,
+    buffered: u32,
+    buffer_frames: u32,
+}
+
+impl<'out> FlacSink<'out> {
+    pub fn new(encoder: flac_bound::FlacEncoder<'out>, channels: u32, buffer_frames: u32) -> FlacSink<'out> {
+        FlacSink {
+            encoder,
+            buffer: Vec::with_capacity(buffer_frames as usize * channels as usize),
+            buffered: 0,
+            buffer_frames,
+        }
+    }
+
+    /// Pass the buffered frames to the encoder
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffered > 0 {
+            self.encoder.process_interleaved(&self.buffer, self.buffered)
+                .map_err(|_| io::Error::other("cannot encode FLAC output"))?;
+            self.buffer.clear();
+            self.buffered = 0;
+        }
+
+        Ok(())
+    }
+}
+
+impl Sink for FlacSink<'_> {
+    fn write_frame(&mut self, frame: &[i32]) -> io::Result<()> {
+        self.buffer.extend_from_slice(frame);
+        self.buffered += 1;
+
+        if self.buffered == self.buffer_frames {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.flush()?;
+
+        self.encoder.finish().map(|_| ()).map_err(|_| io::Error::other("cannot finish FLAC output"))
+    }
+}
+
+/// FLAC file with a seek table, encoded by libFLAC directly through flac-sys as flac-bound can't add
+/// metadata. The seek points are given as sample numbers up front, libFLAC fills in their byte offsets
+/// when finishing, by seeking back in the file
+pub struct FlacSeekSink {
+    encoder: *mut FLAC__StreamEncoder,
+    seektable: *mut FLAC__StreamMetadata,
+    buffer: Vec<i32>,
+    buffered: u32,
+    buffer_frames: u32,
+}
+
+impl FlacSeekSink {
+    pub fn create(path: &str, channels: u32, samplerate: u32, total_samples: u64, buffer_frames: u32,
+        seek_points: &[u64]) -> io::Result<FlacSeekSink> {
+        let error = |msg: &str| io::Error::other(format!("{}: {}", path, msg));
+        let fname = CString::new(path).map_err(|_| error("invalid filename"))?;
+
+        // SAFETY: the encoder and seek table are owned by the sink and freed on drop. libFLAC copies the
+        // array of metadata pointers, the seek table itself lives until the encoder is deleted
+        unsafe {
+            let sink = FlacSeekSink {
+                encoder: FLAC__stream_encoder_new(),
+                seektable: FLAC__metadata_object_new(FLAC__MetadataType_FLAC__METADATA_TYPE_SEEKTABLE),
+                buffer: Vec::with_capacity(buffer_frames as usize * channels as usize),
+                buffered: 0,
+                buffer_frames,
+            };
+            if sink.encoder.is_null() || sink.seektable.is_null() {
+                return Err(error("cannot create FLAC encoder"));
+            }
+
+            let mut points = seek_points.to_vec();
+            let mut metadata = [sink.seektable];
+            let configured = FLAC__metadata_object_seektable_template_append_points(sink.seektable, points.as_mut_ptr(), points.len() as u32) != 0
+                && FLAC__metadata_object_seektable_template_sort(sink.seektable, 1) != 0
+                && FLAC__stream_encoder_set_channels(sink.encoder, channels) != 0
+                && FLAC__stream_encoder_set_bits_per_sample(sink.encoder, 16) != 0
+                && FLAC__stream_encoder_set_sample_rate(sink.encoder, samplerate) != 0
+                && FLAC__stream_encoder_set_compression_level(sink.encoder, 8) != 0
+                && FLAC__stream_encoder_set_total_samples_estimate(sink.encoder, total_samples) != 0
+                && FLAC__stream_encoder_set_metadata(sink.encoder, metadata.as_mut_ptr(), 1) != 0;
+            if !configured {
+                return Err(error("cannot configure FLAC encoder"));
+            }
+
+            if FLAC__stream_encoder_init_file(sink.encoder, fname.as_ptr(), None, ptr::null_mut())
+                != FLAC__StreamEncoderInitStatus_FLAC__STREAM_ENCODER_INIT_STATUS_OK {
+                return Err(error("cannot initialize FLAC encoder"));
+            }
+
+            Ok(sink)
+        }
+    }
+
+    /// Pass the buffered frames to the encoder
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffered > 0 {
+            // SAFETY: the buffer holds buffered complete frames of the configured channels
+            if unsafe { FLAC__stream_encoder_process_interleaved(self.encoder, self.buffer.as_ptr(), self.buffered) } == 0 {
+                return Err(io::Error::other("cannot encode FLAC output"));
+            }
+            self.buffer.clear();
+            self.buffered = 0;
+        }
+
+        Ok(())
+    }
+}
+
+impl Sink for FlacSeekSink {
+    fn write_frame(&mut self, frame: &[i32]) -> io::Result<()> {
+        self.buffer.extend_from_slice(frame);
+        self.buffered += 1;
+
+        if self.buffered == self.buffer_frames {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.flush()?;
+
+        // SAFETY: the encoder was initialized in create
+        if unsafe { FLAC__stream_encoder_finish(self.encoder) } == 0 {
+            return Err(io::Error::other("cannot finish FLAC output"));
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for FlacSeekSink {
+    fn drop(&mut self) {
+        // SAFETY: both were created in create and are not used after this
+        unsafe {
+            if !self.encoder.is_null() {
+                FLAC__stream_encoder_delete(self.encoder);
+            }
+            if !self.seektable.is_null() {
+                FLAC__metadata_object_delete(self.seektable);
+            }
+        }
+    }
+}
+
+/// Raw PCM, 16 bit signed little endian interleaved, with a gain clipped to full scale
+pub struct PcmSink {
+    out: BufWriter<File>,
+    gain: f64,
+}
+
+impl PcmSink {
+    pub fn create(path: &str, gain: f64) -> io::Result<PcmSink> {
+        Ok(PcmSink { out: BufWriter::new(File::create(path)?), gain })
+    }
+}
+
+impl Sink for PcmSink {
+    fn write_frame(&mut self, frame: &[i32]) -> io::Result<()> {
+        for sample in frame {
+            let sample = (*sample as f64 * self.gain).clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+            self.out.write_all(&sample.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Sums the channels of every frame into fewer channels before passing it on, input channel c goes to output
+/// channel `map[c]`. The sum is clipped by the destination
+pub struct DownmixSink<'out> {
+    inner: Box<dyn Sink + 'out>,
+    map: Vec<usize>,
+    channels: usize,
+}
+
+impl<'out> DownmixSink<'out> {
+    pub fn new(inner: Box<dyn Sink + 'out>, map: &[u32], channels: u32) -> DownmixSink<'out> {
+        DownmixSink { inner, map: map.iter().map(|channel| *channel as usize).collect(), channels: channels as usize }
+    }
+}
+
+impl Sink for DownmixSink<'_> {
+    fn write_frame(&mut self, frame: &[i32]) -> io::Result<()> {
+        let mut mixed = vec![0; self.channels];
+        for (sample, channel) in frame.iter().zip(self.map.iter()) {
+            mixed[*channel] += sample;
+        }
+
+        self.inner.write_frame(&mixed)
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        self.inner.finish()
+    }
+}
+
+/// WAV file of 32-bit IEEE float samples relative to full scale, written from the frames before the conversion
+/// to integers so they are not quantized. The sizes in the header are filled in when finishing
+pub struct WavF32Sink {
+    out: BufWriter<File>,
+    channels: u32,
+    samplerate: u32,
+    frames: u64,
+}
+
+impl WavF32Sink {
+    /// Size of the header up to the samples: RIFF, fmt (18 bytes), fact and data chunk headers
+    const HEADER_BYTES: u64 = 58;
+
+    pub fn create(path: &str, channels: u32, samplerate: u32) -> io::Result<WavF32Sink> {
+        let mut sink = WavF32Sink { out: BufWriter::new(File::create(path)?), channels, samplerate, frames: 0 };
+        sink.write_header()?;
+
+        Ok(sink)
+    }
+
+    /// Write the header for the frames written so far
+    fn write_header(&mut self) -> io::Result<()> {
+        let data_bytes = (self.frames * self.channels as u64 * 4) as u32;
+        let block_align = self.channels as u16 * 4;
+        let out = &mut self.out;
+
+        out.write_all(b"RIFF")?;
+        out.write_all(&(Self::HEADER_BYTES as u32 - 8 + data_bytes).to_le_bytes())?;
+        out.write_all(b"WAVE")?;
+
+        // WAVE_FORMAT_IEEE_FLOAT, without extension
+        out.write_all(b"fmt ")?;
+        out.write_all(&18u32.to_le_bytes())?;
+        out.write_all(&3u16.to_le_bytes())?;
+        out.write_all(&(self.channels as u16).to_le_bytes())?;
+        out.write_all(&self.samplerate.to_le_bytes())?;
+        out.write_all(&(self.samplerate * block_align as u32).to_le_bytes())?;
+        out.write_all(&block_align.to_le_bytes())?;
+        out.write_all(&32u16.to_le_bytes())?;
+        out.write_all(&0u16.to_le_bytes())?;
+
+        out.write_all(b"fact")?;
+        out.write_all(&4u32.to_le_bytes())?;
+        out.write_all(&(self.frames as u32).to_le_bytes())?;
+
+        out.write_all(b"data")?;
+        out.write_all(&data_bytes.to_le_bytes())
+    }
+}
+
+impl Sink for WavF32Sink {
+    fn write_frame(&mut self, _frame: &[i32]) -> io::Result<()> {
+        Err(io::Error::other("a float WAV takes the frames before the conversion to integers"))
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.out.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.out.flush()
+    }
+
+    fn takes_floats(&self) -> bool {
+        true
+    }
+
+    fn write_float_frame(&mut self, frame: &[f64]) -> io::Result<()> {
+        for sample in frame {
+            self.out.write_all(&(*sample as f32).to_le_bytes())?;
+        }
+        self.frames += 1;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+//! Characterization sweep of the transducers (`--char-sweep`): every channel in turn plays a tone at each of the
+//! frequencies, held for `--char-hold` ms, instead of the stimulation schedule. Channel 0 plays all frequencies,
+//! then channel 1 and so on, the other channels are silent meanwhile.
+//!
+//! The tones are sines at full scale, ramped in and out over `RAMP_MS` so the segments don't click. Every tone is
+//! a segment of the output with a seek point at its first sample, labeled by a comment
+//! `F2HEAL_SEGMENT=<first sample> <channel label> <freq>Hz`.
+
+use std::f64::consts::PI;
+
+use crate::tags::PREFIX;
+
+/// Ramp in and out of every tone, at most a quarter of the hold
+pub const RAMP_MS: i64 = 5;
+
+/// A tone of the sweep
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub channel: u32,
+    pub freq: i64,
+    /// First sample of the tone
+    pub start: i64,
+}
+
+/// All channels in turn through the frequencies
+pub struct Sweep {
+    pub segments: Vec<Segment>,
+    /// Samples of every tone
+    pub hold: i64,
+    freqs: Vec<i64>,
+    hold_ms: i64,
+    channels: u32,
+    samplerate: i64,
+    ramp: i64,
+}
+
+impl Sweep {
+    pub fn new(channels: u32, freqs: &[i64], hold_ms: i64, samplerate: i64) -> Sweep {
+        let hold = hold_ms * samplerate / 1000;
+
+        let mut segments = Vec::new();
+        for channel in 0..channels {
+            for freq in freqs {
+                segments.push(Segment { channel, freq: *freq, start: segments.len() as i64 * hold });
+            }
+        }
+
+        Sweep { segments, hold, freqs: freqs.to_vec(), hold_ms, channels, samplerate, ramp: (RAMP_MS * samplerate / 1000).min(hold / 4) }
+    }
+
+    /// Length of the sweep in samples
+    pub fn samples(&self) -> i64 {
+        self.segments.len() as i64 * self.hold
+    }
+
+    /// Returns the frame at sample relative to full scale, the tone of its segment on one channel
+    pub fn frame(&self, sample: i64) -> Vec<f64> {
+        let segment = &self.segments[(sample / self.hold) as usize];
+        let t = sample - segment.start;
+        let ramp = match self.ramp {
+            0 => 1.0,
+            ramp => (t.min(self.hold - 1 - t) as f64 / ramp as f64).min(1.0),
+        };
+
+        let mut frame = vec![0.0; self.channels as usize];
+        frame[segment.channel as usize] = ramp * ((t * segment.freq * 2) as f64 * PI / self.samplerate as f64).sin();
+
+        frame
+    }
+
+    /// Default name of the output, for {default} in the name template
+    pub fn name(&self) -> String {
+        let freqs : Vec<String> = self.freqs.iter().map(|freq| freq.to_string()).collect();
+
+        format!("CharSweep--{}HZ-{}MSHOLD-{}CH-{}SR", freqs.join("_"), self.hold_ms, self.channels, self.samplerate)
+    }
+
+    /// Comments labeling every segment with its channel (labels[channel]) and frequency
+    pub fn comments(&self, labels: &[String]) -> Vec<String> {
+        let mut comments = vec![
+            format!("{}VERSION={}", PREFIX, env!("CARGO_PKG_VERSION")),
+            format!("{}COMMIT={}", PREFIX, env!("F2HEAL_GIT_COMMIT")),
+        ];
+        for segment in self.segments.iter() {
+            comments.push(format!("{}SEGMENT={} {} {}Hz", PREFIX, segment.start, labels[segment.channel as usize], segment.freq));
+        }
+
+        comments
+    }
+}
+
+/// Parse the comma separated frequencies of a sweep, each in Hz below the Nyquist frequency of samplerate
+pub fn parse_freqs(freqs: &str, samplerate: i64) -> Result<Vec<i64>, String> {
+    let freqs = freqs.split(',')
+        .map(|freq| freq.trim().parse::<i64>().map_err(|_| format!("invalid sweep frequency: {}", freq.trim())))
+        .collect::<Result<Vec<i64>, String>>()?;
+
+    match freqs.iter().find(|freq| **freq <= 0 || **freq * 2 >= samplerate) {
+        Some(freq) => Err(format!("sweep frequency {}Hz outside 1Hz to the Nyquist frequency ({}Hz)", freq, samplerate / 2)),
+        None => Ok(freqs),
+    }
+}
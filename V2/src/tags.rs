@@ -0,0 +1,154 @@
+//! Parameters of a render as Vorbis comments in an existing FLAC file (`--tag`).
+//!
+//! Every resolved configuration value becomes a comment `F2HEAL_<NAME>=<value>`, with the value as in the
+//! `config` of the manifest (JSON), next to the version, commit and seed. The comments are written in place
+//! with the libFLAC metadata chain, the audio is not re-encoded. Comments with the prefix already in the file
+//! are replaced, other comments are kept.
+
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{self, Read};
+use std::mem::MaybeUninit;
+
+use flac_sys::*;
+
+use crate::Arguments;
+
+/// Prefix of the comment names written by f2heal
+pub const PREFIX: &str = "F2HEAL_";
+
+/// Comments of the parameters of a render
+pub fn comments(args: &Arguments) -> Vec<String> {
+    let mut comments = vec![
+        format!("{}VERSION={}", PREFIX, env!("CARGO_PKG_VERSION")),
+        format!("{}COMMIT={}", PREFIX, env!("F2HEAL_GIT_COMMIT")),
+        format!("{}SEED={}", PREFIX, args.seed()),
+    ];
+    for (name, value) in args.config_fields() {
+        comments.push(format!("{}{}={}", PREFIX, name.to_uppercase(), value));
+    }
+
+    comments
+}
+
+/// Write the comments of the parameters into the FLAC file at `path`, returns the number of f2heal comments
+/// replaced. The file must have the channels and sample rate of the parameters
+pub fn write_tags(args: &Arguments, path: &str) -> io::Result<usize> {
+    let error = |msg: String| io::Error::other(format!("{}: {}", path, msg));
+
+    let mut magic = [0u8; 4];
+    File::open(path).map_err(|e| error(e.to_string()))?.read_exact(&mut magic).map_err(|_| error("not a FLAC file".to_owned()))?;
+    if &magic != b"fLaC" {
+        return Err(error("not a FLAC file".to_owned()));
+    }
+
+    let fname = CString::new(path).map_err(|_| error("invalid filename".to_owned()))?;
+
+    // SAFETY: libFLAC fills the stream info block on success, only the stream info fields are read then
+    let streaminfo = unsafe {
+        let mut block = MaybeUninit::<FLAC__StreamMetadata>::zeroed();
+        if FLAC__metadata_get_streaminfo(fname.as_ptr(), block.as_mut_ptr()) == 0 {
+            return Err(error("cannot read the FLAC stream info".to_owned()));
+        }
+        block.assume_init().data.stream_info
+    };
+    if streaminfo.channels != args.output_channels() || streaminfo.sample_rate as i64 != args.samplerate {
+        return Err(error(format!("{} channels at {} Hz, the parameters give {} channels at {} Hz",
+            streaminfo.channels, streaminfo.sample_rate, args.output_channels(), args.samplerate)));
+    }
+
+    write_comments(path, &comments(args))
+}
+
+/// Write comments into the FLAC file at `path`, replacing the f2heal comments already in it. Returns the number
+/// of f2heal comments replaced
+pub fn write_comments(path: &str, comments: &[String]) -> io::Result<usize> {
+    let error = |msg: String| io::Error::other(format!("{}: {}", path, msg));
+    let fname = CString::new(path).map_err(|_| error("invalid filename".to_owned()))?;
+
+    // SAFETY: the chain and iterator are deleted before returning. A new comment block is owned by the chain
+    // once inserted, the comment entries are copied by libFLAC
+    unsafe {
+        let chain = FLAC__metadata_chain_new();
+        let iterator = FLAC__metadata_iterator_new();
+        let result = if chain.is_null() || iterator.is_null() {
+            Err(error("cannot create FLAC metadata chain".to_owned()))
+        } else {
+            update_chain(chain, iterator, &fname, comments).map_err(error)
+        };
+
+        if !iterator.is_null() {
+            FLAC__metadata_iterator_delete(iterator);
+        }
+        if !chain.is_null() {
+            FLAC__metadata_chain_delete(chain);
+        }
+
+        result
+    }
+}
+
+/// Replace the f2heal comments in the chain of the file and write it, returns the number replaced
+///
+/// # Safety
+///
+/// `chain` and `iterator` must be valid, newly created objects
+unsafe fn update_chain(chain: *mut FLAC__Metadata_Chain, iterator: *mut FLAC__Metadata_Iterator, fname: &CString,
+    comments: &[String]) -> Result<usize, String> {
+    if FLAC__metadata_chain_read(chain, fname.as_ptr()) == 0 {
+        return Err(format!("cannot read FLAC metadata (status {})", FLAC__metadata_chain_status(chain)));
+    }
+
+    // find the comment block, or add one after the stream info
+    FLAC__metadata_iterator_init(iterator, chain);
+    let mut block = std::ptr::null_mut();
+    loop {
+        if FLAC__metadata_iterator_get_block_type(iterator) == FLAC__MetadataType_FLAC__METADATA_TYPE_VORBIS_COMMENT {
+            block = FLAC__metadata_iterator_get_block(iterator);
+            break;
+        }
+        if FLAC__metadata_iterator_next(iterator) == 0 {
+            break;
+        }
+    }
+    if block.is_null() {
+        block = FLAC__metadata_object_new(FLAC__MetadataType_FLAC__METADATA_TYPE_VORBIS_COMMENT);
+        if block.is_null() {
+            return Err("cannot create FLAC comment block".to_owned());
+        }
+        FLAC__metadata_iterator_init(iterator, chain);
+        if FLAC__metadata_iterator_insert_block_after(iterator, block) == 0 {
+            FLAC__metadata_object_delete(block);
+            return Err("cannot add FLAC comment block".to_owned());
+        }
+    }
+
+    // remove the comments of an earlier --tag, from the back so the indices stay valid. Removing reallocates
+    // the comment array, so it is looked up for every comment
+    let mut replaced = 0;
+    for index in (0..(*block).data.vorbis_comment.num_comments).rev() {
+        let entry = *(*block).data.vorbis_comment.comments.add(index as usize);
+        let comment = std::slice::from_raw_parts(entry.entry, entry.length as usize);
+        if comment.starts_with(PREFIX.as_bytes()) {
+            if FLAC__metadata_object_vorbiscomment_delete_comment(block, index) == 0 {
+                return Err("cannot remove FLAC comment".to_owned());
+            }
+            replaced += 1;
+        }
+    }
+
+    for comment in comments {
+        let mut bytes = comment.clone().into_bytes();
+        let entry = FLAC__StreamMetadata_VorbisComment_Entry { length: bytes.len() as u32, entry: bytes.as_mut_ptr() };
+        if FLAC__metadata_object_vorbiscomment_append_comment(block, entry, 1) == 0 {
+            return Err("cannot add FLAC comment".to_owned());
+        }
+    }
+
+    FLAC__metadata_chain_sort_padding(chain);
+    if FLAC__metadata_chain_write(chain, 1, 0) == 0 {
+        return Err(format!("cannot write FLAC metadata (status {})", FLAC__metadata_chain_status(chain)));
+    }
+
+    Ok(replaced)
+}
@@ -0,0 +1,114 @@
+//! Re-render whenever an arguments file changes, for iterative tuning (`--watch`).
+//!
+//! The file holds command line options as they would be typed (separated by whitespace or newlines,
+//! `#` starts a comment, no quoting). It is polled for changes, and once a save has settled the output
+//! is rendered by a child process. A change during a render interrupts it as Ctrl-C would, so the
+//! interrupted file is still valid and only the latest settings render to the end (on Unix, elsewhere the
+//! render is stopped outright).
+//!
+//! Polling the modification time instead of file system notifications and an options file instead of
+//! TOML are deliberate, see the README.
+
+use std::io;
+use std::process::{Child, Command};
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
+
+use colored::Colorize;
+
+/// Interval between checks of the file
+const POLL: Duration = Duration::from_millis(200);
+
+/// A change is only picked up when the file stays unchanged this long, so a burst of saves renders once
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Modification time of the file, None when it can't be read (e.g. while an editor replaces it)
+fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Read the command line options from the arguments file
+fn read_args(path: &str) -> io::Result<Vec<String>> {
+    let content = std::fs::read_to_string(path)?;
+
+    Ok(content.lines()
+        .map(|line| line.split('#').next().unwrap())
+        .flat_map(|line| line.split_whitespace())
+        .map(|arg| arg.to_owned())
+        .collect())
+}
+
+/// Interrupt a running render and wait until it has finished its output
+#[cfg(unix)]
+fn interrupt(child: &mut Child) {
+    // SAFETY: plain kill(2) on the pid of our own child
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, libc::SIGINT);
+    }
+
+    let _ = child.wait();
+}
+
+/// Stop a running render, without SIGINT it can't finish its output
+#[cfg(not(unix))]
+fn interrupt(child: &mut Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Render with the options of the file every time it changes, until the program is stopped
+pub fn watch(path: &str) -> ! {
+    let exe = std::env::current_exe().unwrap_or_else(|e| panic!("!!!ERROR: Cannot locate the executable: {}", e));
+
+    let mut seen : Option<SystemTime> = None;
+    let mut render : Option<Child> = None;
+    let mut renders = 0;
+
+    println!("Watching {} for changes (Ctrl-C to stop)", path);
+
+    loop {
+        if let Some(child) = &mut render {
+            if let Ok(Some(status)) = child.try_wait() {
+                if status.success() {
+                    println!("Render #{} done", renders);
+                } else {
+                    println!("{}", format!("Render #{} failed ({})", renders, status).red().bold());
+                }
+                render = None;
+            }
+        }
+
+        let current = modified(path);
+        if current.is_some() && current != seen {
+            sleep(DEBOUNCE);
+
+            // still being written, check again on the next poll
+            if modified(path) != current {
+                continue;
+            }
+            seen = current;
+
+            if let Some(mut child) = render.take() {
+                println!("Render #{} interrupted", renders);
+                interrupt(&mut child);
+            }
+
+            match read_args(path) {
+                Ok(args) if args.iter().any(|arg| arg == "--watch") =>
+                    println!("{}", format!("ERROR: {} can't contain --watch", path).red().bold()),
+                Ok(args) => {
+                    renders += 1;
+                    println!("Change detected, render #{}: {}", renders, args.join(" "));
+
+                    match Command::new(&exe).args(&args).spawn() {
+                        Ok(child) => render = Some(child),
+                        Err(e) => println!("{}", format!("ERROR: Cannot start render: {}", e).red().bold()),
+                    }
+                }
+                Err(e) => println!("{}", format!("ERROR: Cannot read {}: {}", path, e).red().bold()),
+            }
+        }
+
+        sleep(POLL);
+    }
+}
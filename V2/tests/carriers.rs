@@ -0,0 +1,302 @@
+//! What sounds in and around the stimulations of the smoke render: the carrier, the baseline tone, markers,
+//! alternating frequencies, the characterization sweep and the sample path.
+
+mod common;
+
+use common::{reference, stimulations, Smoke, TempDir, CHANNELS, SAMPLERATE};
+use f2heal_v2::decode::Decoded;
+
+/// --carrier noise is noise where the smoke render stimulates and exactly 0 where it is silent
+#[test]
+fn noise_within_stimulations() {
+    let dir = TempDir::new("noise");
+    let reference = reference(&dir);
+    let decoded = Smoke::new(&dir).args(["--carrier", "noise"]).decoded("noise.flac");
+    assert_eq!(decoded.samples.len(), reference.samples.len());
+
+    let channels = reference.channels as usize;
+    let mut stimulated = 0;
+    let mut noise = 0;
+
+    for (i, (sample, sine)) in decoded.samples.iter().zip(&reference.samples).enumerate() {
+        // a sine crosses 0 on single samples within a stimulation, silence lasts longer
+        let neighbours = [i.checked_sub(channels), Some(i + channels)];
+        let silent = *sine == 0 && neighbours.iter().flatten().all(|j| reference.samples.get(*j).is_none_or(|s| *s == 0));
+
+        assert!(!silent || *sample == 0, "noise sample {} of channel {} in silence", i / channels, i % channels);
+        if !silent {
+            stimulated += 1;
+            noise += (*sample != 0) as usize;
+        }
+    }
+
+    // all but the samples at the very start of the envelope
+    assert!(noise * 10 >= stimulated * 9, "only {} of {} stimulated samples are noise", noise, stimulated);
+}
+
+/// Baseline level of the continuous render, and the default --stimfreq of its baseline tone
+const BASELINE_LEVEL: f64 = 0.25;
+const BASELINE_FREQ: u32 = 250;
+
+/// --continuous-mode plays the baseline tone on every channel where the smoke render is silent
+#[test]
+fn continuous_mode_fills_silence() {
+    let dir = TempDir::new("continuous");
+    let reference = reference(&dir);
+    let decoded = Smoke::new(&dir).args(["--continuous-mode", "--baseline-level", &BASELINE_LEVEL.to_string()]).decoded("continuous.flac");
+    assert_eq!(decoded.samples.len(), reference.samples.len());
+
+    let channels = reference.channels as usize;
+    let mut silent_channels = vec![0; channels];
+
+    for (i, (sample, sine)) in decoded.samples.iter().zip(&reference.samples).enumerate() {
+        let neighbours = [i.checked_sub(channels), Some(i + channels)];
+        if *sine != 0 || neighbours.iter().flatten().any(|j| reference.samples.get(*j).is_some_and(|s| *s != 0)) {
+            continue;
+        }
+
+        let phase = ((i / channels) as u64 * BASELINE_FREQ as u64 * 2) as f64 * std::f64::consts::PI / SAMPLERATE as f64;
+        let baseline = (BASELINE_LEVEL * phase.sin() * i16::MAX as f64) as i32;
+        assert!((sample - baseline).abs() <= 1, "sample {} of channel {} is {} in silence, expected the baseline {}",
+            i / channels, i % channels, sample, baseline);
+        silent_channels[i % channels] += 1;
+    }
+
+    assert!(!silent_channels.contains(&0), "the smoke render has no silence on every channel: {:?}", silent_channels);
+}
+
+/// On a CPU with the vector instructions the batched sines (the default) and --force-scalar render the same
+#[cfg(feature = "simd")]
+#[test]
+fn force_scalar_renders_the_same() {
+    if f2heal_v2::simd::detect().is_none() {
+        eprintln!("skipped, the CPU has no vector instructions for the batched sines");
+        return;
+    }
+
+    let dir = TempDir::new("scalar");
+    let reference = reference(&dir);
+
+    assert!(Smoke::new(&dir).arg("--force-scalar").decoded("scalar.flac").samples == reference.samples,
+        "the render with --force-scalar differs from the selected sample path");
+}
+
+/// Beat and stimulation duration (ms) of the beat render, a stimulation spans two beat periods
+const BEAT: u32 = 20;
+const BEAT_STIMDURATION: u32 = 100;
+const BEAT_STIMFREQ: u32 = 250;
+
+/// Peak of every carrier period of the samples of channel from first to last, the envelope of a stimulation
+fn peaks(decoded: &Decoded, channel: u32, (first, last): (usize, usize), period: usize) -> Vec<i32> {
+    (first..=last).step_by(period)
+        .map(|start| (start..(start + period).min(last + 1))
+            .map(|i| decoded.samples[i * decoded.channels as usize + channel as usize].abs())
+            .max()
+            .unwrap_or(0))
+        .collect()
+}
+
+/// With --beat the envelope of every stimulation beats at the beat frequency: the two carriers start in phase and
+/// cancel half a beat period later, so the envelope has a null every beat period (to a carrier period, the
+/// resolution of the peaks)
+#[test]
+fn beat_period_in_envelope() {
+    let dir = TempDir::new("beat");
+    let decoded = Smoke::new(&dir).args(["--beat", &BEAT.to_string(), "--stimduration", &BEAT_STIMDURATION.to_string()]).decoded("beat.flac");
+    let period = (SAMPLERATE / BEAT_STIMFREQ) as usize;
+    let beat_periods = (SAMPLERATE / BEAT / period as u32) as usize;
+
+    for channel in 0..CHANNELS {
+        for span in stimulations(&decoded, channel) {
+            let peaks = peaks(&decoded, channel, span, period);
+            if peaks.len() < 2 * beat_periods {
+                continue;
+            }
+
+            // the nulls of the first and the second beat period, a beat period apart
+            let loudest = *peaks.iter().max().unwrap();
+            let null = |range: std::ops::Range<usize>| range.min_by_key(|i| peaks[*i]).unwrap();
+            let (first, second) = (null(0..beat_periods), null(beat_periods..2 * beat_periods));
+            let apart = ((second - first) * period).abs_diff((SAMPLERATE / BEAT) as usize);
+            assert!(apart <= period && peaks[first] * 4 < loudest && peaks[second] * 4 < loudest,
+                "stimulation at sample {} of channel {} has its nulls at carrier periods {} and {} ({} and {} of {}), expected a beat period apart",
+                span.0, channel, first, second, peaks[first], peaks[second], loudest);
+        }
+    }
+}
+
+/// Channels of the chord of the random phase render, all channels of the smoke render
+const PHASE_CHORD: u32 = CHANNELS;
+
+/// Largest magnitude of the sum of the channels of a frame
+fn summed_peak(decoded: &Decoded) -> i32 {
+    decoded.samples.chunks(decoded.channels as usize).map(|frame| frame.iter().sum::<i32>().abs()).max().unwrap_or(0)
+}
+
+/// With --random-phase the simultaneous stimulations of a chord don't add up coherently, so the peak of the summed
+/// channels is lower than with all carriers starting at phase 0
+#[test]
+fn random_phase_lowers_summed_peak() {
+    let dir = TempDir::new("random-phase");
+    let chord = ["--chord-size", &PHASE_CHORD.to_string()];
+    let coherent = summed_peak(&Smoke::new(&dir).args(chord).decoded("coherent.flac"));
+    let random = summed_peak(&Smoke::new(&dir).args(chord).arg("--random-phase").decoded("random-phase.flac"));
+
+    assert!(coherent > 0 && random < coherent, "summed peak of a chord of {} channels is {} with random phases, {} without",
+        PHASE_CHORD, random, coherent);
+}
+
+/// Marker tone of the dual carrier render, the stimulation frequency of the smoke render, a frequency in neither,
+/// and the largest relative deviation of the amplitudes found
+const MARKER_FREQ: u32 = 1000;
+const MARKER_LEVEL: f64 = 0.3;
+const MARKER_STIMFREQ: u32 = 250;
+const MARKER_CONTROL_FREQ: u32 = 600;
+const MARKER_TOLERANCE: f64 = 0.05;
+
+/// Amplitude of the component at freq in the samples of channel from first to last, relative to full scale
+fn amplitude_at(decoded: &Decoded, channel: u32, (first, last): (usize, usize), freq: u32) -> f64 {
+    let (mut re, mut im) = (0.0, 0.0);
+    for i in first..=last {
+        let sample = decoded.samples[i * decoded.channels as usize + channel as usize] as f64 / i16::MAX as f64;
+        let phase = (i as u64 * freq as u64 * 2) as f64 * std::f64::consts::PI / decoded.samplerate as f64;
+        re += sample * phase.cos();
+        im += sample * phase.sin();
+    }
+
+    2.0 * (re * re + im * im).sqrt() / (last - first + 1) as f64
+}
+
+/// --dual-carrier holds the stimulation carrier and the marker tone at their levels in every stimulation of the
+/// smoke render, which has no marker
+#[test]
+fn dual_carrier_adds_marker() {
+    let dir = TempDir::new("dual");
+    let reference = reference(&dir);
+    let decoded = Smoke::new(&dir)
+        .args(["--dual-carrier", "--marker-freq", &MARKER_FREQ.to_string(), "--marker-level", &MARKER_LEVEL.to_string()])
+        .decoded("dual.flac");
+
+    for channel in 0..CHANNELS {
+        let spans = stimulations(&decoded, channel);
+        assert_eq!(spans.len(), stimulations(&reference, channel).len(), "dual carrier stimulations of channel {}", channel);
+
+        for (span, reference_span) in spans.iter().zip(stimulations(&reference, channel)) {
+            let carrier = amplitude_at(&decoded, channel, *span, MARKER_STIMFREQ);
+            let marker = amplitude_at(&decoded, channel, *span, MARKER_FREQ);
+            let control = amplitude_at(&decoded, channel, *span, MARKER_CONTROL_FREQ);
+            let ratio = marker / carrier;
+            assert!((ratio / (MARKER_LEVEL / (1.0 - MARKER_LEVEL)) - 1.0).abs() <= MARKER_TOLERANCE && control <= MARKER_TOLERANCE * marker,
+                "stimulation at sample {} of channel {} has the carrier at {:.3}, the marker at {:.3} and {:.3} at {}Hz",
+                span.0, channel, carrier, marker, control, MARKER_CONTROL_FREQ);
+
+            let without = amplitude_at(&reference, channel, reference_span, MARKER_FREQ);
+            assert!(without <= MARKER_TOLERANCE * marker, "stimulation at sample {} of channel {} of the smoke render has {:.3} at the marker frequency",
+                reference_span.0, channel, without);
+        }
+    }
+}
+
+/// Alternating frequencies of the alternation render, far enough apart to tell by zero crossings
+const ALT_FREQS: (u32, u32) = (100, 300);
+
+/// Zero crossings of every stimulation of channel, in order. A stimulation ends at a run of silent samples
+fn crossings(decoded: &Decoded, channel: u32) -> Vec<u32> {
+    const SILENCE: usize = 8;
+
+    let mut result = Vec::new();
+    let mut previous = 0;
+    let mut zeros = SILENCE;
+
+    for sample in decoded.samples.iter().skip(channel as usize).step_by(decoded.channels as usize) {
+        if *sample == 0 {
+            zeros += 1;
+            continue;
+        }
+
+        if zeros >= SILENCE {
+            result.push(0);
+        } else if (*sample > 0) != (previous > 0) {
+            *result.last_mut().unwrap() += 1;
+        }
+        previous = *sample;
+        zeros = 0;
+    }
+
+    result
+}
+
+/// --alt-freqs toggles every channel between the two frequencies on its successive stimulations, F1 first
+#[test]
+fn alt_freqs_toggle() {
+    let dir = TempDir::new("alternation");
+    let decoded = Smoke::new(&dir).args(["--alt-freqs", &format!("{}:{}", ALT_FREQS.0, ALT_FREQS.1)]).decoded("alternation.flac");
+
+    // crossings per stimulation are 2 * frequency * duration, so the F2 ones are 3 times the F1 ones
+    for channel in 0..CHANNELS {
+        let counts = crossings(&decoded, channel);
+        let toggles = counts.len() >= 2 && counts[0] * 2 < counts[1]
+            && counts.iter().enumerate().all(|(i, count)| count.abs_diff(counts[i % 2]) <= 1);
+
+        assert!(toggles, "channel {} doesn't alternate between {}Hz and {}Hz: zero crossings {:?}", channel, ALT_FREQS.0, ALT_FREQS.1, counts);
+    }
+}
+
+/// Frequencies and hold (ms) of the characterization sweep
+const SWEEP_FREQS: [u32; 2] = [100, 200];
+const SWEEP_HOLD: u32 = 250;
+
+/// --char-sweep plays every channel in turn at every frequency, each tone for the hold with the other channels
+/// silent. The tone is ramped in from and out to 0, so it has a zero crossing less than periods
+#[test]
+fn char_sweep_plays_every_channel_in_turn() {
+    let dir = TempDir::new("sweep");
+    let freqs : Vec<String> = SWEEP_FREQS.iter().map(|freq| freq.to_string()).collect();
+    let decoded = Smoke::new(&dir).args(["--char-sweep", &freqs.join(","), "--char-hold", &SWEEP_HOLD.to_string()]).decoded("sweep.flac");
+
+    let hold = (SWEEP_HOLD * SAMPLERATE / 1000) as usize;
+    let tones = CHANNELS as usize * SWEEP_FREQS.len();
+    assert_eq!(decoded.samples.len(), tones * hold * CHANNELS as usize, "samples of {} tones of {} frames", tones, hold);
+
+    for tone in 0..tones {
+        let (channel, freq) = (tone / SWEEP_FREQS.len(), SWEEP_FREQS[tone % SWEEP_FREQS.len()]);
+        let frames = decoded.samples[tone * hold * CHANNELS as usize..(tone + 1) * hold * CHANNELS as usize].chunks(CHANNELS as usize);
+
+        let mut sounding : Vec<i32> = Vec::new();
+        for frame in frames {
+            assert!(frame.iter().enumerate().all(|(other, sample)| other == channel || *sample == 0),
+                "another channel sounds during the sweep tone of channel {} at {}Hz", channel, freq);
+            sounding.extend(Some(frame[channel]).filter(|sample| *sample != 0));
+        }
+
+        let crossings = sounding.windows(2).filter(|pair| (pair[0] > 0) != (pair[1] > 0)).count();
+        assert_eq!(crossings, (2 * freq * SWEEP_HOLD / 1000) as usize - 1, "zero crossings of the sweep tone of channel {} at {}Hz", channel, freq);
+    }
+}
+
+/// Length and fade (s) of the faded render, with a second in the middle at full level, and the frames at either end
+/// that must be near silent: within 1 % of full scale
+const FADE_SECONDS: u32 = 3;
+const FADE: u32 = 1;
+const FADE_EDGE_FRAMES: usize = 200;
+const FADE_EDGE_LEVEL: i32 = i16::MAX as i32 / 100;
+
+/// --session-fade-sec fades the render in from and out to silence, and leaves the samples between the fades as they
+/// are without it
+#[test]
+fn session_fade_tapers_edges() {
+    let dir = TempDir::new("fade");
+    let reference = Smoke::new(&dir).seconds(FADE_SECONDS).decoded("unfaded.flac");
+    let decoded = Smoke::new(&dir).seconds(FADE_SECONDS).args(["--session-fade-sec", &FADE.to_string()]).decoded("faded.flac");
+    let channels = decoded.channels as usize;
+    let (fade, edge) = ((FADE * SAMPLERATE) as usize * channels, FADE_EDGE_FRAMES * channels);
+    assert_eq!(decoded.samples.len(), reference.samples.len());
+
+    let edges = decoded.samples[..edge].iter().chain(&decoded.samples[decoded.samples.len() - edge..]);
+    assert!(edges.clone().all(|sample| sample.abs() <= FADE_EDGE_LEVEL),
+        "the faded render peaks at {} in its first or last {} frames", edges.map(|sample| sample.abs()).max().unwrap(), FADE_EDGE_FRAMES);
+
+    let middle = fade..decoded.samples.len() - fade;
+    assert!(decoded.samples[middle.clone()] == reference.samples[middle], "the fade changes the samples between the fades");
+    assert!(decoded.samples[..fade].iter().any(|sample| *sample != 0), "the fade in is silent");
+}
@@ -0,0 +1,193 @@
+//! Shared by the end to end tests: render tiny files with the f2heal-v2 binary in a temporary directory and decode
+//! them.
+//!
+//! Every test renders in its own temporary directory, removed afterwards. Most tests render the smoke configuration
+//! (1 second, 4 channels, 8000Hz, fixed seed) with an option and check what the option does, often against the smoke
+//! render itself.
+
+// every test file uses a part of the helpers
+#![allow(dead_code)]
+
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use f2heal_v2::decode;
+
+pub const SECONDS: u32 = 1;
+pub const SAMPLERATE: u32 = 8000;
+pub const CHANNELS: u32 = 4;
+pub const CYCLEPERIOD: u32 = 400;
+
+/// Temporary directory of a test, removed when dropped
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(test: &str) -> TempDir {
+        let dir = std::env::temp_dir().join(format!("f2heal-test-{}-{}", test, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap_or_else(|e| panic!("cannot create {}: {}", dir.display(), e));
+
+        TempDir(dir)
+    }
+
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// The f2heal-v2 binary, with the presets of the test directory instead of those of the user
+pub fn f2heal(dir: &TempDir) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_f2heal-v2"));
+    command.env("XDG_CONFIG_HOME", dir.join("config"));
+
+    command
+}
+
+/// Run command, which must succeed
+pub fn run(command: &mut Command) -> Output {
+    let output = command.output().unwrap_or_else(|e| panic!("cannot run f2heal-v2: {}", e));
+    assert!(output.status.success(), "f2heal-v2 failed ({}): {}", output.status, String::from_utf8_lossy(&output.stdout));
+
+    output
+}
+
+/// Decode fname
+pub fn decode(fname: &Path) -> decode::Decoded {
+    decode::decode(&fname.to_string_lossy()).unwrap_or_else(|e| panic!("cannot decode {}: {}", fname.display(), e))
+}
+
+/// A render of the smoke configuration: `SECONDS` at `SAMPLERATE` of `CHANNELS` channels, a `CYCLEPERIOD` and
+/// seed 1. A test changes the parts it checks and adds its options
+pub struct Smoke<'a> {
+    dir: &'a TempDir,
+    samplerate: u32,
+    channels: u32,
+    cycleperiod: u32,
+    length: [String; 2],
+    seed: [String; 2],
+    options: Vec<OsString>,
+}
+
+impl<'a> Smoke<'a> {
+    pub fn new(dir: &'a TempDir) -> Smoke<'a> {
+        Smoke {
+            dir,
+            samplerate: SAMPLERATE,
+            channels: CHANNELS,
+            cycleperiod: CYCLEPERIOD,
+            length: ["-s".to_owned(), SECONDS.to_string()],
+            seed: ["--randomseed".to_owned(), "1".to_owned()],
+            options: Vec::new(),
+        }
+    }
+
+    pub fn samplerate(mut self, samplerate: u32) -> Smoke<'a> {
+        self.samplerate = samplerate;
+        self
+    }
+
+    pub fn channels(mut self, channels: u32) -> Smoke<'a> {
+        self.channels = channels;
+        self
+    }
+
+    pub fn cycleperiod(mut self, cycleperiod: u32) -> Smoke<'a> {
+        self.cycleperiod = cycleperiod;
+        self
+    }
+
+    pub fn seconds(mut self, seconds: u32) -> Smoke<'a> {
+        self.length = ["-s".to_owned(), seconds.to_string()];
+        self
+    }
+
+    /// Render cycles whole cycles instead of seconds
+    pub fn cycles(mut self, cycles: usize) -> Smoke<'a> {
+        self.length = ["--cycles".to_owned(), cycles.to_string()];
+        self
+    }
+
+    /// Seed the render with phrase instead of seed 1
+    pub fn seed_phrase(mut self, phrase: &str) -> Smoke<'a> {
+        self.seed = ["--seed-phrase".to_owned(), phrase.to_owned()];
+        self
+    }
+
+    pub fn arg<S: AsRef<OsStr>>(mut self, option: S) -> Smoke<'a> {
+        self.options.push(option.as_ref().to_owned());
+        self
+    }
+
+    pub fn args<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(mut self, options: I) -> Smoke<'a> {
+        self.options.extend(options.into_iter().map(|option| option.as_ref().to_owned()));
+        self
+    }
+
+    /// The options of the render, without the output file
+    pub fn options(&self) -> Vec<OsString> {
+        let mut options : Vec<OsString> = self.length.iter().map(OsString::from).collect();
+        for (option, value) in [("--samplerate", self.samplerate), ("--channels", self.channels), ("--cycleperiod", self.cycleperiod)] {
+            options.extend([option.into(), value.to_string().into()]);
+        }
+        options.extend(self.seed.iter().map(OsString::from));
+        options.extend(self.options.iter().cloned());
+
+        options
+    }
+
+    /// The command rendering to fname
+    pub fn command(&self, fname: &Path) -> Command {
+        let mut command = f2heal(self.dir);
+        command.args(self.options()).arg("--name-template").arg(fname);
+
+        command
+    }
+
+    /// Render to fname, which must succeed
+    pub fn render(&self, fname: &Path) -> Output {
+        run(&mut self.command(fname))
+    }
+
+    /// Render to name in the test directory and decode it
+    pub fn decoded(&self, name: &str) -> decode::Decoded {
+        self.render(&self.dir.join(name));
+        decode(&self.dir.join(name))
+    }
+}
+
+/// Render the smoke file itself, the reference of most tests
+pub fn reference(dir: &TempDir) -> decode::Decoded {
+    Smoke::new(dir).decoded("smoke.flac")
+}
+
+/// First and last sounding sample of every stimulation of channel, a stimulation ends at a run of silent samples
+pub fn stimulations(decoded: &decode::Decoded, channel: u32) -> Vec<(usize, usize)> {
+    const SILENCE: usize = 8;
+
+    let mut result = Vec::new();
+    let mut current : Option<(usize, usize)> = None;
+
+    for (i, sample) in decoded.samples.iter().skip(channel as usize).step_by(decoded.channels as usize).enumerate() {
+        if *sample == 0 {
+            continue;
+        }
+
+        current = match current {
+            Some((first, last)) if i - last < SILENCE => Some((first, i)),
+            Some(stimulation) => {
+                result.push(stimulation);
+                Some((i, i))
+            }
+            None => Some((i, i)),
+        };
+    }
+    result.extend(current);
+
+    result
+}
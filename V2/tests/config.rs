@@ -0,0 +1,121 @@
+//! How a render is configured: presets, the seed phrase and the warm-up of the generator.
+
+mod common;
+
+use clap::Parser;
+use common::{f2heal, reference, Smoke, TempDir, SECONDS};
+use f2heal_v2::{render_to_vec, Arguments, ConfigError, Render};
+
+/// Options saved as a preset and loaded again, they must render the same file
+const PRESET_OPTIONS: [&str; 10] = ["--stimduration", "50", "--pauzecycleperiod", "2", "--pauzes", "1", "--jitter", "10",
+    "--clamp-jitter-to-slot", "--amplitude-scale=log"];
+
+/// Options saved with --save-preset render the same file when loaded with --preset, and an option on the command
+/// line replaces the preset's
+#[test]
+fn preset_renders_as_typed() {
+    let dir = TempDir::new("preset");
+    let preset = ["--preset", "smoke"];
+    let override_options = ["--stimfreq", "300"];
+
+    let typed = Smoke::new(&dir).args(PRESET_OPTIONS).decoded("typed.flac");
+    Smoke::new(&dir).args(PRESET_OPTIONS).args(["--save-preset", "smoke"]).render(&dir.join("unused.flac"));
+    assert!(Smoke::new(&dir).args(preset).decoded("preset.flac").samples == typed.samples,
+        "the render of the saved preset differs from the typed options");
+
+    let typed = Smoke::new(&dir).args(PRESET_OPTIONS).args(override_options).decoded("typed-override.flac");
+    assert!(Smoke::new(&dir).args(preset).args(override_options).decoded("preset-override.flac").samples == typed.samples,
+        "an option on the command line doesn't replace the preset's");
+}
+
+/// Misspelled option in a preset, and the option it must suggest
+const PRESET_TYPO: &str = "stimfreqency";
+const PRESET_TYPO_MEANT: &str = "stimfreq";
+
+/// A preset with a misspelled option is refused, suggesting the option meant
+#[test]
+fn preset_typo_suggests_option() {
+    let dir = TempDir::new("typo");
+    let presets = dir.join("config").join("f2heal").join("presets");
+    std::fs::create_dir_all(&presets).unwrap();
+    std::fs::write(presets.join("typo.toml"), format!("{} = 300\n", PRESET_TYPO)).unwrap();
+
+    let output = f2heal(&dir)
+        .args(["-s", &SECONDS.to_string(), "--preset", "typo"])
+        .arg("--name-template").arg(dir.join("typo.flac"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success() && !dir.join("typo.flac").exists(), "a preset with unknown option {} renders", PRESET_TYPO);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(&format!("{} (did you mean {}?)", PRESET_TYPO, PRESET_TYPO_MEANT)),
+        "unknown option {} doesn't suggest {}: {}", PRESET_TYPO, PRESET_TYPO_MEANT, stdout.trim());
+}
+
+/// Seed phrase and its pinned seed, see the seed phrase test of the binary
+const SEED_PHRASE: &str = "monday-protocol-A";
+const GOLDEN_PHRASE_SEED: i64 = 7161227644504006941;
+
+/// --seed-phrase renders with the pinned seed of its phrase, read from a filename with {seed}
+#[test]
+fn seed_phrase_names_seed() {
+    let dir = TempDir::new("phrase");
+    Smoke::new(&dir).seed_phrase(SEED_PHRASE).render(&dir.join("phrase-{seed}.flac"));
+
+    assert!(dir.join(&format!("phrase-{}.flac", GOLDEN_PHRASE_SEED)).exists(),
+        "seed phrase {} doesn't render with seed {}", SEED_PHRASE, GOLDEN_PHRASE_SEED);
+}
+
+/// Draws discarded by the warm-up renders
+const RNG_WARMUP: u64 = 100;
+
+/// Two renders with --rng-warmup are identical, and differ from the smoke render of the same seed
+#[test]
+fn rng_warmup_is_reproducible() {
+    let dir = TempDir::new("warmup");
+    let reference = reference(&dir);
+    let warmup = ["--rng-warmup", &RNG_WARMUP.to_string()];
+
+    let warmed = Smoke::new(&dir).args(warmup).decoded("warmup.flac");
+    assert!(Smoke::new(&dir).args(warmup).decoded("warmup-again.flac").samples == warmed.samples, "two renders with --rng-warmup differ");
+    assert!(warmed.samples != reference.samples, "--rng-warmup renders the same as without warm-up");
+}
+
+/// Sequence file with an invalid channel on its second line
+const INVALID_SEQUENCE: &str = "0,1\n2,x\n";
+
+/// --validate-only reports an invalid input file as an error in its JSON, with the file and line, and shows only the
+/// JSON also with -v
+#[test]
+fn validate_only_reports_invalid_file() {
+    let dir = TempDir::new("validate");
+    let sequence = dir.join("sequence.txt");
+    std::fs::write(&sequence, INVALID_SEQUENCE).unwrap();
+
+    let output = Smoke::new(&dir).args(["--validate-only", "-v"]).arg("--sequence-file").arg(&sequence)
+        .command(&dir.join("validate.flac"))
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert_eq!(output.status.code(), Some(2), "an invalid sequence file doesn't fail validation: {}", stdout.trim());
+    assert!(stdout.starts_with('{') && stdout.trim_end().ends_with('}'), "validation output isn't only JSON: {}", stdout.trim());
+    assert!(stdout.contains("\"code\": \"invalid_file\"") && stdout.contains(&format!("{} line 2", sequence.display())),
+        "the invalid line of the sequence file isn't reported: {}", stdout.trim());
+}
+
+/// The library refuses to render an invalid sequence file, returning the error of the binary instead of panicking
+#[test]
+fn library_reports_invalid_file() {
+    let dir = TempDir::new("library");
+    let sequence = dir.join("sequence.txt");
+    std::fs::write(&sequence, INVALID_SEQUENCE).unwrap();
+
+    let smoke = Smoke::new(&dir).arg("--sequence-file").arg(&sequence);
+    let args = Arguments::parse_from(std::iter::once("f2heal-v2".into()).chain(smoke.options()));
+    let invalid = |errors: &[ConfigError]| errors.iter().any(|error| matches!(error, ConfigError::InvalidFile { line: Some(2), .. }));
+
+    assert!(matches!(args.validate(), Err(errors) if invalid(&errors)), "validate doesn't report the invalid sequence file");
+    assert!(matches!(render_to_vec(&args), Err(errors) if invalid(&errors)), "render_to_vec doesn't report the invalid sequence file");
+    assert!(matches!(Render::new(&args), Err(errors) if invalid(&errors)), "Render::new doesn't report the invalid sequence file");
+}
@@ -0,0 +1,188 @@
+//! What the smoke render writes besides its samples, and the output formats: resampling, the dense schedule, the
+//! interval statistics and float WAV.
+
+mod common;
+
+use common::{decode, reference, stimulations, Smoke, TempDir, CHANNELS, CYCLEPERIOD, SAMPLERATE, SECONDS};
+use f2heal_v2::decode::Decoded;
+use f2heal_v2::schedule;
+
+/// Output rate of the resampled render, synthesized at `SAMPLERATE`
+const RESAMPLED_RATE: u32 = 12000;
+
+/// Largest relative difference in energy between the smoke and the resampled render
+const RESAMPLED_ENERGY_TOLERANCE: f64 = 0.01;
+
+/// Largest difference of a float sample at full scale from its value before the conversion to 32 bit float
+const FLOAT_TOLERANCE: f64 = 0.01;
+
+/// Energy of the decoded samples: the sum of the squared samples relative to full scale over the sample rate
+fn energy(decoded: &Decoded) -> f64 {
+    let full_scale = i16::MAX as f64;
+
+    decoded.samples.iter().map(|sample| (*sample as f64 / full_scale).powi(2)).sum::<f64>() / decoded.samplerate as f64
+}
+
+/// --synth-rate (synthesized at the smoke rate, resampled for the output) keeps the duration and the energy
+#[test]
+fn synth_rate_keeps_duration_and_energy() {
+    let dir = TempDir::new("resample");
+    let expected_energy = energy(&reference(&dir));
+
+    Smoke::new(&dir).samplerate(RESAMPLED_RATE).args(["--synth-rate", &SAMPLERATE.to_string()]).render(&dir.join("resampled.flac"));
+    let decoded = decode(&dir.join("resampled.flac"));
+
+    assert_eq!(decoded.samplerate, RESAMPLED_RATE);
+    assert_eq!(decoded.frames(), (SECONDS * RESAMPLED_RATE) as usize);
+
+    let energy = energy(&decoded);
+    assert!((energy / expected_energy - 1.0).abs() <= RESAMPLED_ENERGY_TOLERANCE,
+        "energy {:.4} after resampling, {:.4} before", energy, expected_energy);
+}
+
+/// The dense schedule marks every sounding sample of the smoke render as stimulated, and every sample it doesn't
+/// mark is silent
+#[test]
+fn dense_schedule_marks_stimulations() {
+    let dir = TempDir::new("schedule");
+    let reference = reference(&dir);
+    let schedule_fname = dir.join("smoke.schedule");
+    Smoke::new(&dir).arg("--dense-schedule").arg(&schedule_fname).render(&dir.join("schedule.flac"));
+
+    let schedule = schedule::read(&schedule_fname.to_string_lossy()).unwrap();
+    let channels = reference.channels as usize;
+    assert_eq!(schedule.records.len() * channels, reference.samples.len());
+
+    let mut active = 0;
+    let mut zeros = 0;
+
+    for (i, sample) in reference.samples.iter().enumerate() {
+        let record = &schedule.records[i / channels];
+        let channel = (i % channels) as u32;
+
+        assert!(record.active(channel) || *sample == 0, "sample {} of channel {} sounds but isn't stimulated", i / channels, channel);
+        if record.active(channel) {
+            active += 1;
+            zeros += (*sample == 0) as usize;
+        }
+    }
+
+    // a sine crosses 0 on some samples and the envelope starts at 0
+    assert!(zeros * 10 <= active, "{} of {} stimulated samples are silent", zeros, active);
+}
+
+/// The interval histogram of --render-stats-per-channel counts the intervals between the stimulations of every
+/// channel of the smoke render, in slots of the cycle period over the channels
+#[test]
+fn interval_histogram() {
+    let dir = TempDir::new("intervals");
+    let reference = reference(&dir);
+    let csv_fname = dir.join("intervals.csv");
+    Smoke::new(&dir).arg("--render-stats-per-channel").arg(&csv_fname).render(&dir.join("intervals.flac"));
+
+    let slot = (CYCLEPERIOD * SAMPLERATE / 1000 / CHANNELS) as f64;
+    let mut expected = Vec::new();
+    for channel in 0..CHANNELS {
+        let starts : Vec<usize> = stimulations(&reference, channel).iter().map(|(first, _)| *first).collect();
+        let mut slots : Vec<i64> = starts.windows(2).map(|pair| ((pair[1] - pair[0]) as f64 / slot).round() as i64).collect();
+        slots.sort();
+        for group in slots.chunk_by(|a, b| a == b) {
+            expected.push(format!("Ch{},{},{}", channel, group[0], group.len()));
+        }
+    }
+
+    let csv = std::fs::read_to_string(&csv_fname).unwrap();
+    let lines : Vec<&str> = csv.lines().skip(1).collect();
+    assert!(!expected.is_empty());
+    assert_eq!(lines, expected);
+}
+
+/// --format wav-f32 (its .flac becomes .wav) holds the samples of the smoke render before their conversion to
+/// integers: every float truncates to the smoke sample and they are not quantized
+#[test]
+fn float_wav_holds_unquantized_samples() {
+    let dir = TempDir::new("float");
+    let reference = reference(&dir);
+    Smoke::new(&dir).args(["--format", "wav-f32"]).render(&dir.join("float.flac"));
+
+    let wav = std::fs::read(dir.join("float.wav")).unwrap();
+    let word = |at: usize| u16::from_le_bytes([wav[at], wav[at + 1]]) as u32;
+    let dword = |at: usize| u32::from_le_bytes([wav[at], wav[at + 1], wav[at + 2], wav[at + 3]]);
+
+    // RIFF header, fmt of 18 bytes, fact and data chunks
+    assert!(wav.len() >= 58 && &wav[..4] == b"RIFF" && &wav[12..16] == b"fmt " && &wav[50..54] == b"data"
+        && (word(20), word(22), dword(24), word(34)) == (3, reference.channels, SAMPLERATE, 32),
+        "not a 32-bit float WAV of {} channels at {}Hz", reference.channels, SAMPLERATE);
+
+    let floats : Vec<f32> = wav[58..].chunks_exact(4).map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])).collect();
+    assert_eq!(floats.len(), reference.samples.len());
+    assert_eq!(dword(54) as usize, floats.len() * 4);
+
+    let mut fractional = 0;
+    for (i, (float, sample)) in floats.iter().zip(&reference.samples).enumerate() {
+        let scaled = *float as f64 * i16::MAX as f64;
+        assert!((scaled - *sample as f64).abs() < 1.0 + FLOAT_TOLERANCE, "float sample {} of channel {} is {}, the reference {}",
+            i / reference.channels as usize, i % reference.channels as usize, scaled, sample);
+        fractional += ((scaled - scaled.round()).abs() > FLOAT_TOLERANCE) as usize;
+    }
+    assert!(fractional > 0, "the float samples are quantized to 16 bit");
+}
+
+/// Output delay of every channel of the delayed render (ms), whole samples at `SAMPLERATE`, and the shift of every
+/// channel in samples: the negative delay is realized by delaying the other channels by as much
+const CHANNEL_DELAYS: [&str; CHANNELS as usize] = ["0", "1.5", "-0.5", "0"];
+const CHANNEL_SHIFTS: [usize; CHANNELS as usize] = [4, 16, 0, 4];
+
+/// --channel-delay shifts every channel of the smoke render by its delay: silent up to the shift, then sample for
+/// sample the smoke channel
+#[test]
+fn channel_delay_shifts_channels() {
+    let dir = TempDir::new("delay");
+    let reference = reference(&dir);
+    let decoded = Smoke::new(&dir).args(["--channel-delay", &CHANNEL_DELAYS.join(",")]).decoded("delayed.flac");
+    let channels = reference.channels as usize;
+    assert_eq!(decoded.samples.len(), reference.samples.len());
+
+    for (channel, shift) in CHANNEL_SHIFTS.iter().enumerate() {
+        let delayed : Vec<i32> = decoded.samples.iter().skip(channel).step_by(channels).copied().collect();
+        let smoke : Vec<i32> = reference.samples.iter().skip(channel).step_by(channels).copied().collect();
+
+        assert!(delayed[..*shift].iter().all(|sample| *sample == 0) && delayed[*shift..] == smoke[..smoke.len() - shift],
+            "channel {} isn't the smoke channel shifted by {} samples", channel, shift);
+    }
+}
+
+/// Emphasis curve with a NaN gain, which every stimulation at its frequency (the default --stimfreq) takes
+const NAN_EMPHASIS_CURVE: &str = "250 NaN\n";
+
+/// A NaN sample, here from a malformed emphasis curve, stops the render with an error naming the channel and sample
+/// instead of writing corrupt audio, and removes the output
+#[test]
+fn nan_sample_stops_render() {
+    let dir = TempDir::new("nan");
+    let (curve, fname) = (dir.join("emphasis.txt"), dir.join("nan.flac"));
+    std::fs::write(&curve, NAN_EMPHASIS_CURVE).unwrap();
+
+    let output = Smoke::new(&dir).arg("--emphasis-curve").arg(&curve).command(&fname).output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert_eq!(output.status.code(), Some(1), "a NaN sample doesn't fail the render: {}", stdout.trim());
+    assert!(stdout.contains("Invalid (NaN or infinite) sample on channel"), "the NaN sample isn't reported: {}", stdout.trim());
+    assert!(!fname.exists(), "the output with a NaN sample is left behind");
+}
+
+/// Buffer sizes (frames) of the buffered renders: one frame per call to the encoder, the per-sample path, and one
+/// that leaves a partial buffer at the end
+const BUFFER_FRAMES: [u32; 2] = [1, 3000];
+
+/// The buffered encoding writes the samples of the smoke render whatever the --buffer-frames
+#[test]
+fn buffer_frames_keep_samples() {
+    let dir = TempDir::new("buffer");
+    let reference = reference(&dir);
+
+    for frames in BUFFER_FRAMES {
+        let decoded = Smoke::new(&dir).args(["--buffer-frames", &frames.to_string()]).decoded(&format!("buffer-{}.flac", frames));
+        assert!(decoded.samples == reference.samples, "the render with --buffer-frames {} differs from the reference", frames);
+    }
+}
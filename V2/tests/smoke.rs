@@ -0,0 +1,97 @@
+//! The smoke render (1 second, 4 channels, 8000Hz, fixed seed) must have the channel count, length and sample rate,
+//! and its decoded samples must match a golden hash, so any change in the generated output is caught. Update
+//! `GOLDEN_FNV1A64` only for intended output changes.
+
+mod common;
+
+use clap::Parser;
+use common::{f2heal, reference, run, Smoke, TempDir, CHANNELS, SAMPLERATE, SECONDS};
+use f2heal_v2::{render_to_vec, Arguments, Render};
+
+/// FNV-1a hash of the decoded samples (interleaved, 32 bit little endian) of the smoke render
+const GOLDEN_FNV1A64: u64 = 0x1284_1601_caa1_b20d;
+
+fn fnv1a64(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+
+    for byte in data {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    hash
+}
+
+/// The smoke file has its channels, length and sample rate, isn't silent and matches the golden hash
+#[test]
+fn smoke_render() {
+    let dir = TempDir::new("smoke");
+    let decoded = reference(&dir);
+
+    assert_eq!(decoded.channels, CHANNELS);
+    assert_eq!(decoded.samplerate, SAMPLERATE);
+    assert_eq!(decoded.frames(), (SECONDS * SAMPLERATE) as usize);
+    assert!(decoded.samples.iter().any(|sample| *sample != 0), "output is silent");
+
+    let bytes : Vec<u8> = decoded.samples.iter().flat_map(|sample| sample.to_le_bytes()).collect();
+    let hash = fnv1a64(&bytes);
+    assert_eq!(hash, GOLDEN_FNV1A64, "samples hash to {:016x}, expected {:016x}", hash, GOLDEN_FNV1A64);
+}
+
+/// The library renders in memory what the binary writes to the smoke file: `render_to_vec` all at once, and
+/// `Render` a frame of every channel per sample
+#[test]
+fn render_in_memory_matches_file() {
+    let dir = TempDir::new("memory");
+    let smoke = Smoke::new(&dir);
+    let args = Arguments::parse_from(std::iter::once("f2heal-v2".into()).chain(smoke.options()));
+    let samples = render_to_vec(&args).unwrap();
+
+    assert!(samples == smoke.decoded("smoke.flac").samples, "render_to_vec differs from the smoke file");
+
+    let frames : Vec<Vec<i32>> = Render::new(&args).unwrap().collect();
+    assert_eq!(frames.len(), (SECONDS * SAMPLERATE) as usize);
+    assert!(frames.iter().all(|frame| frame.len() == CHANNELS as usize) && frames.concat() == samples,
+        "the frames of Render differ from render_to_vec");
+}
+
+/// Value of a number field of a JSON line of --progress-json, None when missing or not a number
+fn progress_field(line: &str, field: &str) -> Option<f64> {
+    let (_, rest) = line.split_once(&format!("\"{}\": ", field))?;
+    rest.split([',', '}']).next()?.trim().parse().ok()
+}
+
+/// --progress-json writes JSON lines to stderr, from 0 frames up to all frames at 100 %
+#[test]
+fn progress_json_reports_render() {
+    let dir = TempDir::new("progress");
+    let output = Smoke::new(&dir).arg("--progress-json").render(&dir.join("progress.flac"));
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("frames_done"), "progress written to stdout");
+
+    let frames = (SECONDS * SAMPLERATE) as f64;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let mut previous = 0.0;
+    let mut lines = Vec::new();
+    for line in stderr.lines() {
+        let fields : Vec<Option<f64>> = ["frames_done", "frames_total", "percent"].iter()
+            .map(|field| progress_field(line, field))
+            .collect();
+        let [Some(done), Some(total), Some(percent)] = fields[..] else {
+            panic!("invalid progress line: {}", line);
+        };
+        assert!(line.starts_with('{') && line.contains("\"eta_seconds\": ") && done >= previous, "invalid progress line: {}", line);
+        previous = done;
+        lines.push((done, total, percent));
+    }
+
+    assert!(matches!((lines.first(), lines.last()), (Some((first, _, _)), Some(last)) if *first == 0.0 && *last == (frames, frames, 100.0)),
+        "progress of {} frames doesn't go from 0 to 100 %: {}", frames, stderr.trim());
+}
+
+/// The self-tests of the binary (--self-test) pass: the verbose diagnostics keep the schedule and the slot
+/// boundaries of both timing models land on the exact samples over a million samples
+#[test]
+fn self_test_passes() {
+    let dir = TempDir::new("self-test");
+    run(f2heal(&dir).arg("--self-test"));
+}
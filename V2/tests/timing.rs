@@ -0,0 +1,406 @@
+//! When the stimulations of the smoke render sound: the grid anchor, durations, the order of the channels, rests
+//! between stimulations and the length of the render.
+
+mod common;
+
+use common::{stimulations, reference, Smoke, TempDir, CHANNELS, CYCLEPERIOD, SAMPLERATE};
+use f2heal_v2::decode::Decoded;
+use f2heal_v2::schedule;
+
+/// Grid anchor of the anchored render in ms, a whole number of samples at `SAMPLERATE`
+const GRID_ANCHOR_MS: u32 = 50;
+
+/// --grid-anchor shifts the smoke render by the anchor: silent up to it, then sample for sample the smoke render
+#[test]
+fn grid_anchor_shifts_render() {
+    let dir = TempDir::new("anchor");
+    let reference = reference(&dir);
+    let decoded = Smoke::new(&dir).args(["--grid-anchor", &GRID_ANCHOR_MS.to_string()]).decoded("anchored.flac");
+    let shift = (GRID_ANCHOR_MS * SAMPLERATE / 1000 * reference.channels) as usize;
+
+    assert_eq!(decoded.samples.len(), reference.samples.len());
+    if let Some(i) = decoded.samples[..shift].iter().position(|sample| *sample != 0) {
+        panic!("anchored sample {} before the anchor is not silent", i / reference.channels as usize);
+    }
+    if let Some(i) = decoded.samples[shift..].iter().zip(&reference.samples).position(|(a, b)| a != b) {
+        panic!("anchored sample {} differs from the reference", (shift + i) / reference.channels as usize);
+    }
+}
+
+/// Stimulation duration of every channel in the durations render (ms), the longest fills its slot
+const DURATIONS: [u32; CHANNELS as usize] = [100, 50, 40, 20];
+
+/// Largest relative difference in energy per stimulation between the channels of the normalized render
+const NORMALIZED_ENERGY_TOLERANCE: f64 = 0.01;
+
+/// The durations render: a --stimduration per channel, at a --stimfreq with whole periods in every duration
+fn durations(dir: &TempDir) -> Smoke<'_> {
+    let durations : Vec<String> = DURATIONS.iter().map(|duration| duration.to_string()).collect();
+
+    Smoke::new(dir).args(["--stimduration", &durations.join(","), "--stimfreq", "200"])
+}
+
+/// With a --stimduration per channel every stimulation of a channel lasts its duration. The sine is 0 on the first
+/// and last sample of a stimulation, so the sounding span is a sample shorter
+#[test]
+fn stimduration_per_channel() {
+    let dir = TempDir::new("durations");
+    let decoded = durations(&dir).decoded("durations.flac");
+
+    for channel in 0..CHANNELS {
+        let expected = (DURATIONS[channel as usize] * SAMPLERATE / 1000) as usize - 1;
+        let spans : Vec<usize> = stimulations(&decoded, channel).iter().map(|(first, last)| last - first + 1).collect();
+
+        // the last stimulation can be cut off by the end of the file
+        let complete = &spans[..spans.len().saturating_sub(1)];
+        assert!(!complete.is_empty() && complete.iter().all(|span| span.abs_diff(expected) <= 1),
+            "channel {} stimulations last {:?} samples, expected {}", channel, spans, expected);
+    }
+}
+
+/// With --energy-normalize every complete stimulation of every channel of the durations render has the same energy
+#[test]
+fn energy_normalize_equalizes_stimulations() {
+    let dir = TempDir::new("normalized");
+    let decoded = durations(&dir).arg("--energy-normalize").decoded("normalized.flac");
+    let channels = decoded.channels as usize;
+    let mut energies = Vec::new();
+
+    for channel in 0..CHANNELS {
+        let spans = stimulations(&decoded, channel);
+
+        // the last stimulation can be cut off by the end of the file
+        for (first, last) in &spans[..spans.len().saturating_sub(1)] {
+            let energy : f64 = (*first..=*last)
+                .map(|i| (decoded.samples[i * channels + channel as usize] as f64 / i16::MAX as f64).powi(2))
+                .sum();
+            energies.push((channel, energy));
+        }
+    }
+
+    let least = energies.iter().map(|(_, energy)| *energy).fold(f64::INFINITY, f64::min);
+    let most = energies.iter().map(|(_, energy)| *energy).fold(0.0, f64::max);
+    assert!(energies.iter().map(|(channel, _)| *channel).max() == Some(CHANNELS - 1) && most / least - 1.0 <= NORMALIZED_ENERGY_TOLERANCE,
+        "normalized stimulation energies from {:.3} to {:.3}: {:?}", least, most, energies);
+}
+
+/// Stimulation duration (ms) of the overlap render, a quarter longer than the slot, at a --stimfreq with whole periods
+const OVERLAP_STIMDURATION: u32 = 125;
+const OVERLAP_STIMFREQ: u32 = 200;
+
+/// With --overlap a stimulation longer than its slot isn't cut off by the next one: every complete stimulation lasts
+/// its duration and the channels of successive slots sound together where they overlap
+#[test]
+fn overlap_sums_slots() {
+    let dir = TempDir::new("overlap");
+    let decoded = Smoke::new(&dir)
+        .args(["--overlap", "--stimduration", &OVERLAP_STIMDURATION.to_string(), "--stimfreq", &OVERLAP_STIMFREQ.to_string()])
+        .decoded("overlap.flac");
+    let expected = (OVERLAP_STIMDURATION * SAMPLERATE / 1000) as usize - 1;
+
+    for channel in 0..CHANNELS {
+        let spans : Vec<usize> = stimulations(&decoded, channel).iter().map(|(first, last)| last - first + 1).collect();
+
+        // the last stimulation can be cut off by the end of the file
+        let complete = &spans[..spans.len().saturating_sub(1)];
+        assert!(!complete.is_empty() && complete.iter().all(|span| span.abs_diff(expected) <= 1),
+            "overlapping stimulations of channel {} last {:?} samples, expected {}", channel, spans, expected);
+    }
+
+    let together = decoded.samples.chunks(CHANNELS as usize).filter(|frame| frame.iter().filter(|sample| **sample != 0).count() >= 2).count();
+    let overlap = ((OVERLAP_STIMDURATION * CHANNELS - CYCLEPERIOD) * SAMPLERATE / 1000 / CHANNELS) as usize;
+    assert!(together >= overlap, "{} samples with several channels sounding, a slot overlaps the next by {}", together, overlap);
+}
+
+/// Without --overlap a stimulation longer than its slot stops where the next slot starts: every stimulation sounds
+/// within its own slot, over the whole slot (the sine is 0 on the first sample), and no two channels sound at once
+#[test]
+fn without_overlap_cuts_at_slot() {
+    let dir = TempDir::new("cut-off");
+    let decoded = Smoke::new(&dir)
+        .args(["--stimduration", &OVERLAP_STIMDURATION.to_string(), "--stimfreq", &OVERLAP_STIMFREQ.to_string()])
+        .decoded("cut-off.flac");
+    let slot = (CYCLEPERIOD * SAMPLERATE / 1000 / CHANNELS) as usize;
+
+    for channel in 0..CHANNELS {
+        let spans = stimulations(&decoded, channel);
+        assert!(!spans.is_empty() && spans.iter().all(|(first, last)| first % slot <= 1 && first / slot == last / slot && last - first + 2 >= slot),
+            "stimulations of channel {} at {:?} don't fill their slots of {} samples", channel, spans, slot);
+    }
+
+    let together = decoded.samples.chunks(CHANNELS as usize).filter(|frame| frame.iter().filter(|sample| **sample != 0).count() >= 2).count();
+    assert_eq!(together, 0, "several channels sound at once in {} samples", together);
+}
+
+/// Channels stimulated at once by the chord render
+const CHORD_SIZE: u32 = 2;
+
+/// With --chord-size every slot stimulates exactly K channels at once, and never the chord of the slot before it.
+/// The stimulations are shorter than their slots, so a channel in two successive chords has two stimulations
+#[test]
+fn chord_size_stimulates_k_channels() {
+    let dir = TempDir::new("chord");
+    let decoded = Smoke::new(&dir).args(["--chord-size", &CHORD_SIZE.to_string(), "--stimduration", "50"]).decoded("chord.flac");
+    let slot = (CYCLEPERIOD * SAMPLERATE / 1000 / CHANNELS) as usize;
+    let slots = decoded.frames() / slot;
+
+    let mut chords = vec![Vec::new(); slots];
+    for channel in 0..CHANNELS {
+        for (first, _) in stimulations(&decoded, channel) {
+            chords[first / slot].push(channel);
+        }
+    }
+
+    for (i, chord) in chords.iter().enumerate() {
+        assert_eq!(chord.len(), CHORD_SIZE as usize, "slot {} stimulates channels {:?}", i, chord);
+    }
+    assert!(chords.windows(2).all(|pair| pair[0] != pair[1]), "a chord repeats in the next slot: {:?}", chords);
+}
+
+/// Stimulation duration (ms) of the extreme jitter renders, half a slot, which makes 50 % the largest safe jitter
+const EXTREME_JITTER_STIMDURATION: u32 = 50;
+
+/// Number of sounding channels at every frame of decoded
+fn sounding_channels(decoded: &Decoded) -> Vec<usize> {
+    decoded.samples.chunks(decoded.channels as usize).map(|frame| frame.iter().filter(|sample| **sample != 0).count()).collect()
+}
+
+/// With the largest jitter of 100 % no two channels ever sound at once, whether the jitter cuts stimulations short
+/// or --clamp-jitter-to-slot limits it so every stimulation is complete
+#[test]
+fn extreme_jitter_never_overlaps() {
+    let dir = TempDir::new("extreme-jitter");
+    let jittered = Smoke::new(&dir).args(["--jitter", "100", "--stimduration", &EXTREME_JITTER_STIMDURATION.to_string()]);
+    let expected = (EXTREME_JITTER_STIMDURATION * SAMPLERATE / 1000) as usize - 1;
+
+    let renders = [("jittered", jittered.decoded("jittered.flac")), ("clamped", jittered.arg("--clamp-jitter-to-slot").decoded("clamped.flac"))];
+
+    for (name, decoded) in &renders {
+        if let Some(frame) = sounding_channels(decoded).iter().position(|sounding| *sounding > 1) {
+            panic!("several channels of the {} render sound at frame {}", name, frame);
+        }
+        assert!((0..CHANNELS).all(|channel| !stimulations(decoded, channel).is_empty()), "a channel of the {} render is silent", name);
+    }
+
+    for channel in 0..CHANNELS {
+        let spans : Vec<usize> = stimulations(&renders[1].1, channel).iter().map(|(first, last)| last - first + 1).collect();
+
+        // the last stimulation can be cut off by the end of the file
+        assert!(spans[..spans.len() - 1].iter().all(|span| span.abs_diff(expected) <= 1),
+            "clamped stimulations of channel {} last {:?} samples, expected {}", channel, spans, expected);
+    }
+}
+
+/// Offset of the stimulation of every channel within its slot in the offset render (ms), and the resulting shift in
+/// samples at `SAMPLERATE`. The stimulations last half a slot, so every offset keeps them within their slots
+const CHANNEL_OFFSETS: [&str; CHANNELS as usize] = ["0", "5", "10", "2.5"];
+const CHANNEL_OFFSET_SHIFTS: [usize; CHANNELS as usize] = [0, 40, 80, 20];
+
+/// With --channel-offset every stimulation of a channel starts its offset later than in the render without, and
+/// lasts as long
+#[test]
+fn channel_offset_shifts_onsets() {
+    let dir = TempDir::new("offset");
+    let reference = Smoke::new(&dir).args(["--stimduration", "50"]).decoded("reference.flac");
+    let decoded = Smoke::new(&dir).args(["--stimduration", "50", "--channel-offset", &CHANNEL_OFFSETS.join(",")]).decoded("offset.flac");
+
+    for (channel, shift) in CHANNEL_OFFSET_SHIFTS.iter().enumerate() {
+        let expected : Vec<(usize, usize)> = stimulations(&reference, channel as u32).iter()
+            .map(|(first, last)| (first + shift, last + shift))
+            .filter(|(_, last)| *last < decoded.frames())
+            .collect();
+        let offset = stimulations(&decoded, channel as u32);
+
+        assert!(!expected.is_empty() && offset.starts_with(&expected),
+            "stimulations of channel {} at {:?}, expected {:?} shifted by {} samples", channel, offset, expected, shift);
+    }
+}
+
+/// Band of the intercycle gap (ms) of the gap render and its length (s), long enough for several cycles
+const INTERCYCLE_GAP: (u32, u32) = (10, 30);
+const INTERCYCLE_GAP_SECONDS: u32 = 3;
+
+/// With --intercycle-gap successive cycles, here the stimulations of the first channel of a fixed order, are a cycle
+/// period plus a gap within the band apart, and the gaps vary
+#[test]
+fn intercycle_gap_varies_within_band() {
+    let dir = TempDir::new("gap");
+    let gap = format!("{}:{}", INTERCYCLE_GAP.0, INTERCYCLE_GAP.1);
+    let decoded = Smoke::new(&dir).seconds(INTERCYCLE_GAP_SECONDS)
+        .args(["--norandom", "--stimduration", "50", "--intercycle-gap", &gap])
+        .decoded("gap.flac");
+    let cycle = (CYCLEPERIOD * SAMPLERATE / 1000) as usize;
+    let band = (cycle + (INTERCYCLE_GAP.0 * SAMPLERATE / 1000) as usize)..=(cycle + (INTERCYCLE_GAP.1 * SAMPLERATE / 1000) as usize);
+
+    let onsets : Vec<usize> = stimulations(&decoded, 0).iter().map(|(first, _)| *first).collect();
+    let spacings : Vec<usize> = onsets.windows(2).map(|pair| pair[1] - pair[0]).collect();
+
+    assert!(spacings.len() >= 4 && spacings.iter().all(|spacing| band.contains(spacing)),
+        "cycles {:?} samples apart, expected {:?}", spacings, band);
+    assert!(spacings.iter().any(|spacing| *spacing != spacings[0]), "every gap is {} samples", spacings[0] - cycle);
+}
+
+/// Seed phrase of the second seed of the norandom test, the first is the smoke seed 1
+const NORANDOM_SEED_PHRASE: &str = "another-seed";
+
+/// With --norandom nothing in the schedule depends on the seed: renders at two seeds are identical, with the channels
+/// in order as well as with the fixed jitter pattern
+#[test]
+fn norandom_ignores_seed() {
+    let dir = TempDir::new("norandom");
+
+    for (name, options) in [("ordered", &["--norandom"][..]), ("jittered", &["--norandom", "--jitter", "50"][..])] {
+        let seeded = Smoke::new(&dir).args(options).decoded(&format!("{}.flac", name));
+        let reseeded = Smoke::new(&dir).seed_phrase(NORANDOM_SEED_PHRASE).args(options).decoded(&format!("{}-reseeded.flac", name));
+
+        assert!(seeded.samples.iter().any(|sample| *sample != 0), "the {} render is silent", name);
+        assert!(seeded.samples == reseeded.samples, "the {} --norandom render differs between seeds", name);
+    }
+}
+
+/// With --alternate-direction every other block (a stimulation of every channel) is the block before it reversed.
+/// The stimulations are shorter than their slots, so a channel that ends one block and starts the next one has two
+/// stimulations
+#[test]
+fn alternate_direction_reverses_blocks() {
+    let dir = TempDir::new("direction");
+    let decoded = Smoke::new(&dir).args(["--alternate-direction", "--stimduration", "50"]).decoded("direction.flac");
+
+    let mut onsets : Vec<(usize, u32)> = (0..CHANNELS)
+        .flat_map(|channel| stimulations(&decoded, channel).into_iter().map(move |(first, _)| (first, channel)))
+        .collect();
+    onsets.sort();
+
+    let order : Vec<u32> = onsets.iter().map(|(_, channel)| *channel).collect();
+    let blocks : Vec<&[u32]> = order.chunks_exact(CHANNELS as usize).collect();
+
+    assert!(blocks.len() >= 2, "only {} complete blocks: {:?}", blocks.len(), order);
+    for pair in blocks.chunks_exact(2) {
+        assert!(pair[0].iter().eq(pair[1].iter().rev()), "blocks {:?} and {:?} are not reversed", pair[0], pair[1]);
+    }
+}
+
+/// Sequence file of the sequence render, a cycle (slot) per line with an empty line and a simultaneous step
+const SEQUENCE_FILE: &str = "0\n\n0,2\n3\n1\n";
+const SEQUENCE: [&[u32]; 4] = [&[0], &[0, 2], &[3], &[1]];
+
+/// With --sequence-file every slot, read back from the dense schedule, stimulates the channels of its step and no
+/// others, the simultaneous ones at the same samples, and the sequence loops
+#[test]
+fn sequence_file_replays_steps() {
+    let dir = TempDir::new("sequence");
+    let (sequence_fname, schedule_fname) = (dir.join("steps.txt"), dir.join("sequence.schedule"));
+    std::fs::write(&sequence_fname, SEQUENCE_FILE).unwrap();
+    Smoke::new(&dir)
+        .args(["--stimduration", "50"])
+        .arg("--sequence-file").arg(&sequence_fname)
+        .arg("--dense-schedule").arg(&schedule_fname)
+        .render(&dir.join("sequence.flac"));
+
+    let schedule = schedule::read(&schedule_fname.to_string_lossy()).unwrap();
+    let slot = (CYCLEPERIOD * SAMPLERATE / 1000 / CHANNELS) as usize;
+    let slots = schedule.records.len() / slot;
+    assert!(slots > SEQUENCE.len(), "{} slots don't loop the sequence of {} steps", slots, SEQUENCE.len());
+
+    for (i, records) in schedule.records.chunks_exact(slot).enumerate() {
+        let expected : u16 = SEQUENCE[i % SEQUENCE.len()].iter().map(|channel| 1 << channel).sum();
+        let masks : Vec<u16> = records.iter().map(|record| record.mask).filter(|mask| *mask != 0).collect();
+
+        assert!(!masks.is_empty() && masks.iter().all(|mask| *mask == expected),
+            "slot {} stimulates {:?}, expected the channels {:04b} of step {}", i, masks.first(), expected, i % SEQUENCE.len());
+    }
+}
+
+/// Channels, duration (s), cycle period (ms) and minimum channel rest (slots) of the channel rest render
+const REST_CHANNELS: u32 = 8;
+const REST_SECONDS: u32 = 60;
+const REST_CYCLEPERIOD: u32 = 800;
+const MIN_CHANNEL_REST: usize = 5;
+
+/// Over a minute of 8 channels with --min-channel-rest, read back from the dense schedule, no channel is stimulated
+/// again within the rest, and the rest is reached
+#[test]
+fn min_channel_rest_holds() {
+    let dir = TempDir::new("rest");
+    let schedule_fname = dir.join("rest.schedule");
+    Smoke::new(&dir)
+        .seconds(REST_SECONDS)
+        .channels(REST_CHANNELS)
+        .cycleperiod(REST_CYCLEPERIOD)
+        .args(["--stimduration", "50", "--min-channel-rest", &MIN_CHANNEL_REST.to_string()])
+        .arg("--dense-schedule").arg(&schedule_fname)
+        .render(&dir.join("rest.flac"));
+
+    let schedule = schedule::read(&schedule_fname.to_string_lossy()).unwrap();
+
+    // the channel of every stimulation in order, a stimulation starts where its channel becomes active
+    let mut slots = Vec::new();
+    for (previous, record) in std::iter::once(None).chain(schedule.records.iter().map(Some)).zip(&schedule.records) {
+        slots.extend((0..schedule.channels).filter(|channel| record.active(*channel) && !previous.is_some_and(|p| p.active(*channel))));
+    }
+    assert!(slots.len() >= (REST_SECONDS * 1000 / REST_CYCLEPERIOD * REST_CHANNELS) as usize - REST_CHANNELS as usize,
+        "{} stimulations in the channel rest render", slots.len());
+
+    let mut shortest = usize::MAX;
+    for channel in 0..REST_CHANNELS {
+        let positions : Vec<usize> = slots.iter().enumerate().filter(|(_, c)| **c == channel).map(|(i, _)| i).collect();
+        for pair in positions.windows(2) {
+            let rest = pair[1] - pair[0] - 1;
+            assert!(rest >= MIN_CHANNEL_REST, "channel {} rests {} slots before stimulation {}, expected at least {}",
+                channel, rest, pair[1], MIN_CHANNEL_REST);
+            shortest = shortest.min(rest);
+        }
+    }
+    assert_eq!(shortest, MIN_CHANNEL_REST, "shortest channel rest");
+}
+
+/// Cycles and stimulation duration (ms) of the cycles render
+const CYCLES: usize = 3;
+const CYCLES_STIMDURATION: u32 = 50;
+
+/// Cycles (of `CYCLEPERIOD`), rounding and whole seconds expected of the rounded renders
+const ROUNDINGS: [(usize, &str, u32); 4] = [(3, "up", 2), (3, "down", 1), (3, "nearest", 1), (4, "nearest", 2)];
+
+/// Render cycles whole cycles (of CYCLES_STIMDURATION stimulations) to name with extra options and decode it
+fn render_cycles(dir: &TempDir, name: &str, cycles: usize, extra: &[&str]) -> Decoded {
+    Smoke::new(dir)
+        .cycles(cycles)
+        .args(["--stimduration", &CYCLES_STIMDURATION.to_string()])
+        .args(extra)
+        .decoded(name)
+}
+
+/// A render of --cycles N holds exactly N whole cycles: N complete stimulations of every channel in the length of
+/// N cycles
+#[test]
+fn cycles_render_whole_cycles() {
+    let dir = TempDir::new("cycles");
+    let decoded = render_cycles(&dir, "cycles.flac", CYCLES, &[]);
+    assert_eq!(decoded.samples.len(), CYCLES * (CYCLEPERIOD * SAMPLERATE / 1000) as usize * CHANNELS as usize);
+
+    // the sine is 0 on the first and last sample of a stimulation
+    let duration = (CYCLES_STIMDURATION * SAMPLERATE / 1000) as usize - 1;
+    for channel in 0..CHANNELS {
+        let spans : Vec<usize> = stimulations(&decoded, channel).iter().map(|(first, last)| last - first + 1).collect();
+        assert!(spans.len() == CYCLES && spans.iter().all(|span| *span == duration),
+            "channel {} has stimulations of {:?} samples in {} cycles, expected {} of {}", channel, spans, CYCLES, CYCLES, duration);
+    }
+}
+
+/// --round-duration rounds the length of a --cycles render to whole seconds in every direction: the rounded render
+/// starts as the render without rounding, and is silent after its end
+#[test]
+fn round_duration_in_every_direction() {
+    let dir = TempDir::new("round");
+    for (cycles, round, seconds) in ROUNDINGS {
+        let unrounded = render_cycles(&dir, &format!("unrounded-{}.flac", cycles), cycles, &[]);
+        let rounded = render_cycles(&dir, &format!("rounded-{}-{}.flac", cycles, round), cycles, &["--round-duration", round]);
+
+        let expected = (seconds * SAMPLERATE * CHANNELS) as usize;
+        assert_eq!(rounded.samples.len(), expected, "samples in {} cycles rounded {}", cycles, round);
+
+        let common = expected.min(unrounded.samples.len());
+        assert!(rounded.samples[..common] == unrounded.samples[..common], "{} cycles rounded {} differ from the render without rounding", cycles, round);
+        assert!(rounded.samples[common..].iter().all(|sample| *sample == 0), "{} cycles rounded {} aren't padded with silence", cycles, round);
+    }
+}